@@ -0,0 +1,169 @@
+// Feed de alterações dos registros.
+// Os handlers publicam cada create/update/delete aqui e os assinantes (SSE e
+// WebSocket em /data/_changes) recebem os eventos em ordem. Os eventos mais
+// recentes ficam guardados para que um cliente que reconectou continue de onde
+// parou (Last-Event-ID).
+use std::collections::VecDeque;
+
+use async_std::channel::{self, Receiver, Sender, TrySendError};
+use serde::{Deserialize, Serialize};
+
+use crate::models::DataEntry;
+use crate::state::now_ms;
+use crate::sync::Mutex;
+
+// Quantidade de eventos guardados para retomar conexões
+const RECENT_EVENTS: usize = 1024;
+
+// Eventos pendentes por assinante. Um assinante lento demais é desconectado
+// e precisa reconectar com Last-Event-ID.
+const SUBSCRIBER_BUFFER: usize = 256;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Create,
+    Update,
+    Delete,
+    // Registro devolvido da lixeira
+    Restore,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Create => "create",
+            ChangeKind::Update => "update",
+            ChangeKind::Delete => "delete",
+            ChangeKind::Restore => "restore",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ChangeEvent {
+    // Id crescente do evento (usado no Last-Event-ID)
+    pub event_id: u64,
+    pub op: ChangeKind,
+    // Id do registro alterado
+    pub id: u32,
+    // Conteúdo atual do registro (ausente no delete)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entry: Option<DataEntry>,
+    pub timestamp_ms: u64,
+}
+
+// Resultado de uma assinatura: eventos perdidos desde o Last-Event-ID e o
+// canal com os próximos eventos
+pub struct Subscription {
+    // true quando não é possível retomar do Last-Event-ID (eventos já descartados
+    // ou id de antes de o servidor reiniciar); o cliente deve recarregar GET /data
+    pub reset: bool,
+    pub backlog: Vec<ChangeEvent>,
+    pub receiver: Receiver<ChangeEvent>,
+}
+
+pub struct ChangeFeed {
+    inner: Mutex<FeedInner>,
+}
+
+struct FeedInner {
+    next_event_id: u64,
+    recent: VecDeque<ChangeEvent>,
+    subscribers: Vec<Sender<ChangeEvent>>,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChangeFeed {
+    pub fn new() -> Self {
+        ChangeFeed {
+            inner: Mutex::new(FeedInner {
+                next_event_id: 1,
+                recent: VecDeque::with_capacity(RECENT_EVENTS),
+                subscribers: Vec::new(),
+            }),
+        }
+    }
+
+    // Publica uma alteração. Deve ser chamado com o estado ainda travado, para
+    // que a ordem dos eventos seja a mesma ordem em que as alterações aconteceram.
+    // Devolve o evento publicado.
+    pub fn publish(&self, op: ChangeKind, id: u32, entry: Option<DataEntry>) -> ChangeEvent {
+        let mut inner = self.inner.lock();
+        let event = ChangeEvent {
+            event_id: inner.next_event_id,
+            op,
+            id,
+            entry,
+            timestamp_ms: now_ms(),
+        };
+        inner.next_event_id += 1;
+
+        // Entrega para os assinantes, removendo os desconectados e os atrasados
+        inner
+            .subscribers
+            .retain(|tx| match tx.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    tracing::warn!("assinante do feed atrasado; desconectando");
+                    false
+                }
+                Err(TrySendError::Closed(_)) => false,
+            });
+
+        if inner.recent.len() == RECENT_EVENTS {
+            inner.recent.pop_front();
+        }
+        inner.recent.push_back(event.clone());
+        event
+    }
+
+    // Assinantes ainda conectados
+    pub fn subscriber_count(&self) -> usize {
+        let inner = self.inner.lock();
+        inner
+            .subscribers
+            .iter()
+            .filter(|tx| !tx.is_closed())
+            .count()
+    }
+
+    // Assina o feed a partir do evento seguinte a `last_event_id`.
+    // Sem `last_event_id`, recebe apenas os eventos novos.
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> Subscription {
+        let mut inner = self.inner.lock();
+        let (tx, rx) = channel::bounded(SUBSCRIBER_BUFFER);
+        // Descarta quem já desconectou, mesmo sem nenhum evento publicado desde então
+        inner.subscribers.retain(|tx| !tx.is_closed());
+        inner.subscribers.push(tx);
+
+        let (reset, backlog) = match last_event_id {
+            None => (false, Vec::new()),
+            Some(last) => {
+                let oldest = inner
+                    .recent
+                    .front()
+                    .map_or(inner.next_event_id, |e| e.event_id);
+                let reset = last.saturating_add(1) < oldest || last >= inner.next_event_id;
+                let backlog = inner
+                    .recent
+                    .iter()
+                    .filter(|e| e.event_id > last)
+                    .cloned()
+                    .collect();
+                (reset, backlog)
+            }
+        };
+
+        Subscription {
+            reset,
+            backlog,
+            receiver: rx,
+        }
+    }
+}
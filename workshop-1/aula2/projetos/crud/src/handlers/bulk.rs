@@ -0,0 +1,244 @@
+use crate::changes::ChangeKind;
+use crate::collections::{self, Collection};
+use crate::history::History;
+use crate::models::DataEntry;
+use crate::state::{self, AppState};
+use crate::trash::Trash;
+use crate::wal::{Op, Pending};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tide::{Request, Response, StatusCode};
+
+// Quantidade máxima de operações em uma única chamada
+const MAX_BULK_OPERATIONS: usize = 10_000;
+
+// Uma operação do lote. O campo "op" escolhe o tipo:
+// { "op": "create", "entry": {...} }
+// { "op": "update", "id": 1, "entry": {...} }
+// { "op": "delete", "id": 1 }
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum BulkOp {
+    Create { entry: DataEntry },
+    Update { id: u32, entry: DataEntry },
+    Delete { id: u32 },
+}
+
+// Resultado de cada operação, na mesma ordem do lote
+#[derive(Serialize)]
+struct BulkResult {
+    index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    op: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u32>,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BulkQuery {
+    #[serde(default)]
+    atomic: bool,
+}
+
+pub async fn bulk_data(mut req: Request<AppState>) -> tide::Result {
+    // Com ?atomic=true, qualquer falha desfaz todas as operações do lote
+    let query: BulkQuery = req
+        .query()
+        .map_err(|_| tide::Error::from_str(400, "Invalid query: esperado atomic=true|false"))?;
+
+    // Lê o lote como uma lista de valores JSON; cada item é validado separadamente
+    let items: Vec<Value> = req.body_json().await?;
+    if items.len() > MAX_BULK_OPERATIONS {
+        return Err(tide::Error::from_str(
+            413,
+            format!("Too many operations: máximo de {MAX_BULK_OPERATIONS}"),
+        ));
+    }
+
+    // Pega a coleção da requisição uma única vez para o lote inteiro
+    let collection = collections::from_request(&req)?;
+    let mut map = collection.data.write().await;
+
+    // Guarda o valor anterior de cada id alterado, para poder desfazer
+    let mut undo: Vec<(u32, Option<DataEntry>)> = Vec::new();
+    // Alterações aplicadas; vão para o histórico, a lixeira e o feed só se o lote
+    // for confirmado (no delete, guarda o registro removido)
+    let mut changes: Vec<(ChangeKind, u32, Option<DataEntry>)> = Vec::new();
+    let mut next_id = collection.next_id();
+    let mut results = Vec::with_capacity(items.len());
+
+    for (index, item) in items.into_iter().enumerate() {
+        let op = match serde_json::from_value::<BulkOp>(item) {
+            Ok(op) => op,
+            Err(e) => {
+                results.push(BulkResult {
+                    index,
+                    op: None,
+                    id: None,
+                    status: 400,
+                    error: Some(format!("Invalid operation: {e}")),
+                });
+                continue;
+            }
+        };
+
+        let result = match op {
+            BulkOp::Create { entry } => {
+                let limits = collection
+                    .check_size(&entry)
+                    .and_then(|()| collection.check_capacity(map.len(), 1));
+                if let Err(err) = limits {
+                    results.push(failed(index, "create", None, err));
+                    continue;
+                }
+                let Some(id) = next_id else {
+                    results.push(failed(index, "create", None, state::ids_exhausted()));
+                    continue;
+                };
+                next_id = id.checked_add(1);
+                changes.push((ChangeKind::Create, id, Some(entry.clone())));
+                undo.push((id, map.insert(id, entry)));
+                ok(index, "create", id, 200)
+            }
+            BulkOp::Update { id, entry } => match map.get_mut(&id) {
+                Some(_) if let Err(err) = collection.check_size(&entry) => {
+                    failed(index, "update", Some(id), err)
+                }
+                Some(current) => {
+                    changes.push((ChangeKind::Update, id, Some(entry.clone())));
+                    undo.push((id, Some(std::mem::replace(current, entry))));
+                    ok(index, "update", id, 200)
+                }
+                None => not_found(index, "update", id),
+            },
+            BulkOp::Delete { id } => match map.remove(&id) {
+                Some(previous) => {
+                    changes.push((ChangeKind::Delete, id, Some(previous.clone())));
+                    undo.push((id, Some(previous)));
+                    ok(index, "delete", id, 204)
+                }
+                None => not_found(index, "delete", id),
+            },
+        };
+        results.push(result);
+    }
+
+    let failed = results.iter().any(|r| r.status >= 400);
+    let committed = !(query.atomic && failed);
+    let pending = if committed {
+        // O lote inteiro vai em um único registro do WAL
+        let pending = match req.state().log_ops(&collection, wal_ops(&changes)) {
+            Ok(pending) => pending,
+            Err(err) => {
+                rollback(&mut map, undo);
+                return Err(err);
+            }
+        };
+        let mut trash = collection.trash.write();
+        let mut history = collection.history.write();
+        commit(&collection, &mut trash, &mut history, changes);
+        pending
+    } else {
+        rollback(&mut map, undo);
+        Pending::ready()
+    };
+    drop(map);
+    pending.durable().await?;
+
+    // Lote atômico com falha responde 409; caso contrário 200 com o resultado de cada item
+    let status = if committed {
+        StatusCode::Ok
+    } else {
+        StatusCode::Conflict
+    };
+    Ok(Response::builder(status)
+        .body(tide::Body::from_json(&serde_json::json!({
+            "committed": committed,
+            "results": results,
+        }))?)
+        .build())
+}
+
+// Leva as alterações de um lote confirmado para o histórico, a lixeira e o feed
+// (no delete, `entry` é o registro removido). Chamado com `data` ainda travado.
+pub fn commit(
+    collection: &Collection,
+    trash: &mut Trash,
+    history: &mut History,
+    changes: Vec<(ChangeKind, u32, Option<DataEntry>)>,
+) {
+    for (op, id, entry) in changes {
+        match (op, entry) {
+            (ChangeKind::Delete, Some(previous)) => {
+                trash.insert(id, previous);
+                collection.publish(op, id, None);
+            }
+            (_, entry) => {
+                if let Some(entry) = &entry {
+                    history.record(id, entry.clone(), None);
+                }
+                collection.publish(op, id, entry);
+            }
+        }
+    }
+}
+
+// Alterações de um lote confirmado, como gravadas no WAL
+pub fn wal_ops(changes: &[(ChangeKind, u32, Option<DataEntry>)]) -> Vec<Op> {
+    changes
+        .iter()
+        .map(|(op, id, entry)| match (op, entry) {
+            (ChangeKind::Delete, _) | (_, None) => Op::Delete { id: *id },
+            (_, Some(entry)) => Op::put(*id, entry.clone()),
+        })
+        .collect()
+}
+
+// Desfaz as operações na ordem inversa em que foram aplicadas
+pub fn rollback(map: &mut HashMap<u32, DataEntry>, undo: Vec<(u32, Option<DataEntry>)>) {
+    for (id, previous) in undo.into_iter().rev() {
+        match previous {
+            Some(entry) => {
+                map.insert(id, entry);
+            }
+            None => {
+                map.remove(&id);
+            }
+        }
+    }
+}
+
+fn ok(index: usize, op: &'static str, id: u32, status: u16) -> BulkResult {
+    BulkResult {
+        index,
+        op: Some(op),
+        id: Some(id),
+        status,
+        error: None,
+    }
+}
+
+// Operação recusada pelos limites da coleção
+fn failed(index: usize, op: &'static str, id: Option<u32>, err: tide::Error) -> BulkResult {
+    BulkResult {
+        index,
+        op: Some(op),
+        id,
+        status: err.status().into(),
+        error: Some(err.to_string()),
+    }
+}
+
+fn not_found(index: usize, op: &'static str, id: u32) -> BulkResult {
+    BulkResult {
+        index,
+        op: Some(op),
+        id: Some(id),
+        status: 404,
+        error: Some("Not found".to_string()),
+    }
+}
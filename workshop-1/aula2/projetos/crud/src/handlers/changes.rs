@@ -0,0 +1,179 @@
+use crate::changes::{ChangeEvent, Subscription};
+use crate::collections;
+use crate::state::AppState;
+use async_std::future;
+use futures_lite::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::pin::pin;
+use std::time::Duration;
+use tide::http::headers::UPGRADE;
+use tide::sse::Sender;
+use tide::{Endpoint, Request};
+use tide_websockets::{Message, WebSocket, WebSocketConnection};
+
+// Header enviado pelo EventSource ao reconectar
+const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+// Intervalo do evento "heartbeat" no SSE sem alterações: é no envio que
+// percebemos que o cliente desconectou
+const SSE_HEARTBEAT: Duration = Duration::from_secs(15);
+
+#[derive(Deserialize)]
+struct ChangesQuery {
+    // Ids de registro separados por vírgula (ex: ?id=1,2)
+    id: Option<String>,
+    // Alternativa ao header Last-Event-ID (o WebSocket do navegador não envia headers)
+    last_event_id: Option<u64>,
+}
+
+// Filtro opcional por id de registro
+#[derive(Clone)]
+struct Filter {
+    ids: Option<HashSet<u32>>,
+}
+
+impl Filter {
+    fn matches(&self, event: &ChangeEvent) -> bool {
+        self.ids.as_ref().is_none_or(|ids| ids.contains(&event.id))
+    }
+}
+
+// Feed de alterações. Responde em SSE (text/event-stream) ou, se o cliente
+// pedir upgrade, em WebSocket com um evento JSON por mensagem.
+pub async fn changes(req: Request<AppState>) -> tide::Result {
+    let query: ChangesQuery = req
+        .query()
+        .map_err(|_| tide::Error::from_str(400, "Invalid query"))?;
+
+    let ids = match query.id {
+        Some(list) => Some(
+            list.split(',')
+                .map(|id| id.trim().parse::<u32>())
+                .collect::<Result<HashSet<_>, _>>()
+                .map_err(|_| tide::Error::from_str(400, "Invalid id filter"))?,
+        ),
+        None => None,
+    };
+    let filter = Filter { ids };
+
+    let last_event_id = match req.header(LAST_EVENT_ID_HEADER) {
+        Some(values) => Some(
+            values
+                .last()
+                .as_str()
+                .parse::<u64>()
+                .map_err(|_| tide::Error::from_str(400, "Invalid Last-Event-ID"))?,
+        ),
+        None => query.last_event_id,
+    };
+
+    // Confere a coleção antes do upgrade, para responder 404 em vez de abrir o stream
+    collections::from_request(&req)?;
+
+    let wants_websocket = req
+        .header(UPGRADE)
+        .is_some_and(|v| v.as_str().eq_ignore_ascii_case("websocket"));
+
+    if wants_websocket {
+        WebSocket::new(move |req, conn| stream_websocket(req, conn, filter.clone(), last_event_id))
+            .call(req)
+            .await
+    } else {
+        Ok(tide::sse::upgrade(req, move |req, sender| {
+            stream_sse(req, sender, filter.clone(), last_event_id)
+        }))
+    }
+}
+
+async fn stream_sse(
+    req: Request<AppState>,
+    sender: Sender,
+    filter: Filter,
+    last_event_id: Option<u64>,
+) -> tide::Result<()> {
+    let Subscription {
+        reset,
+        backlog,
+        receiver,
+    } = collections::from_request(&req)?
+        .changes
+        .subscribe(last_event_id);
+
+    // O cliente perdeu eventos que não temos mais: precisa recarregar GET /data
+    if reset {
+        sender.send("reset", "{}", None).await?;
+    }
+    for event in backlog.iter().filter(|e| filter.matches(e)) {
+        send_sse(&sender, event).await?;
+    }
+    // Termina quando o cliente desconecta (o envio do evento ou do heartbeat falha)
+    loop {
+        let event = match future::timeout(SSE_HEARTBEAT, receiver.recv()).await {
+            Ok(Ok(event)) => event,
+            // Assinante atrasado, removido do feed
+            Ok(Err(_)) => return Ok(()),
+            Err(_) => {
+                sender.send("heartbeat", "", None).await?;
+                continue;
+            }
+        };
+        if filter.matches(&event) {
+            send_sse(&sender, &event).await?;
+        }
+    }
+}
+
+async fn send_sse(sender: &Sender, event: &ChangeEvent) -> tide::Result<()> {
+    let data = serde_json::to_string(event)?;
+    let id = event.event_id.to_string();
+    sender.send(event.op.as_str(), data, Some(&id)).await?;
+    Ok(())
+}
+
+async fn stream_websocket(
+    req: Request<AppState>,
+    conn: WebSocketConnection,
+    filter: Filter,
+    last_event_id: Option<u64>,
+) -> tide::Result<()> {
+    let Subscription {
+        reset,
+        backlog,
+        receiver,
+    } = collections::from_request(&req)?
+        .changes
+        .subscribe(last_event_id);
+
+    if reset {
+        conn.send_json(&json!({ "op": "reset" })).await?;
+    }
+    for event in backlog.iter().filter(|e| filter.matches(e)) {
+        conn.send_json(event).await?;
+    }
+    // Termina quando o cliente fecha a conexão, mesmo sem eventos para enviar
+    let mut closed = pin!(client_closed(conn.clone()));
+    loop {
+        let next = futures_lite::future::or(async { Some(receiver.recv().await) }, async {
+            closed.as_mut().await;
+            None
+        })
+        .await;
+        let Some(Ok(event)) = next else {
+            return Ok(());
+        };
+        if filter.matches(&event) {
+            conn.send_json(&event).await?;
+        }
+    }
+}
+
+// Lê (e ignora) as mensagens do cliente até ele fechar a conexão
+async fn client_closed(mut conn: WebSocketConnection) {
+    while let Some(Ok(message)) = conn.next().await {
+        if let Message::Close(_) = message {
+            break;
+        }
+    }
+}
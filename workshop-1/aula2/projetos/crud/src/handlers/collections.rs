@@ -0,0 +1,79 @@
+use crate::collections::{self, CollectionSettings, DEFAULT_COLLECTION};
+use crate::state::AppState;
+use serde::{Deserialize, Serialize};
+use tide::{Request, Response, StatusCode};
+
+// Corpo de POST /collections: o nome e os limites/permissões da coleção
+#[derive(Deserialize)]
+struct CreateCollection {
+    name: String,
+    #[serde(flatten)]
+    settings: CollectionSettings,
+}
+
+// Uma coleção em GET /collections (as API keys não são mostradas)
+#[derive(Serialize)]
+struct CollectionInfo {
+    name: String,
+    entries: usize,
+    trashed: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_entries: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_entry_bytes: Option<usize>,
+    // Se a coleção tem API keys próprias
+    protected: bool,
+}
+
+pub async fn create_collection(mut req: Request<AppState>) -> tide::Result {
+    let body: CreateCollection = req.body_json().await?;
+    collections::validate_name(&body.name).map_err(|e| tide::Error::from_str(400, e))?;
+
+    if !req
+        .state()
+        .create_collection(&body.name, body.settings)
+        .await?
+    {
+        return Err(tide::Error::from_str(409, "Collection already exists"));
+    }
+    tracing::info!(collection = %body.name, "coleção criada");
+
+    Ok(Response::builder(StatusCode::Created)
+        .header("Location", format!("/collections/{}/data", body.name))
+        .body(tide::Body::from_json(
+            &serde_json::json!({ "name": body.name }),
+        )?)
+        .build())
+}
+
+pub async fn list_collections(req: Request<AppState>) -> tide::Result {
+    let mut infos = Vec::new();
+    for (name, collection) in req.state().all_collections() {
+        infos.push(CollectionInfo {
+            name,
+            entries: collection.data.read().await.len(),
+            trashed: collection.trash.read().all().len(),
+            max_entries: collection.settings.max_entries,
+            max_entry_bytes: collection.settings.max_entry_bytes,
+            protected: !collection.settings.api_keys.is_empty(),
+        });
+    }
+    Ok(tide::Body::from_json(&infos)?.into())
+}
+
+pub async fn drop_collection(req: Request<AppState>) -> tide::Result {
+    let name = req.param("name")?;
+    if name == DEFAULT_COLLECTION {
+        return Err(tide::Error::from_str(
+            409,
+            "The default collection cannot be dropped",
+        ));
+    }
+
+    // Remove a coleção com todos os registros, a lixeira e o histórico
+    if !req.state().drop_collection(name).await? {
+        return Err(tide::Error::from_str(404, "Collection not found"));
+    }
+    tracing::info!(collection = %name, "coleção removida");
+    Ok(Response::new(StatusCode::NoContent))
+}
@@ -1,4 +1,8 @@
+use crate::changes::ChangeKind;
+use crate::collections;
+use crate::middleware::request_log;
 use crate::state::AppState;
+use crate::wal::Op;
 use tide::Request;
 
 pub async fn delete_data(req: Request<AppState>) -> tide::Result {
@@ -7,15 +11,23 @@ pub async fn delete_data(req: Request<AppState>) -> tide::Result {
         Ok(val) => val,
         Err(_) => return Err(tide::Error::from_str(400, "Invalid id")),
     };
+    request_log::record_id(id);
 
-    // Pega o estado global
-    let state = req.state();
-    let mut map = state.lock().unwrap();
+    // Pega a coleção da requisição
+    let collection = collections::from_request(&req)?;
+    let mut map = collection.data.write().await;
 
-    // Remove o registro se existir
-    if map.remove(&id).is_some() {
-        Ok(tide::Response::new(204))
-    } else {
-        Ok(tide::Response::new(404))
+    // Move o registro para a lixeira se existir (o histórico é mantido)
+    if !map.contains_key(&id) {
+        return Ok(tide::Response::new(404));
     }
+    // Enfileira no WAL na mesma ordem em que muda a memória (sem WAL, não faz nada)
+    let pending = req.state().log_ops(&collection, vec![Op::Delete { id }])?;
+    if let Some(entry) = map.remove(&id) {
+        collection.trash.write().insert(id, entry);
+        collection.publish(ChangeKind::Delete, id, None);
+    }
+    drop(map);
+    pending.durable().await?;
+    Ok(tide::Response::new(204))
 }
@@ -0,0 +1,19 @@
+use crate::openapi;
+use crate::state::AppState;
+use tide::{Request, Response, StatusCode};
+
+// Página estática de documentação, embutida no binário
+const DOCS_HTML: &str = include_str!("../../static/docs.html");
+
+pub async fn openapi_spec(_req: Request<AppState>) -> tide::Result {
+    // Retorna o documento OpenAPI como JSON
+    Ok(tide::Body::from_json(&openapi::spec())?.into())
+}
+
+pub async fn docs_page(_req: Request<AppState>) -> tide::Result {
+    // Retorna a página HTML que lê /openapi.json e mostra as rotas
+    Ok(Response::builder(StatusCode::Ok)
+        .body(DOCS_HTML)
+        .content_type(tide::http::mime::HTML)
+        .build())
+}
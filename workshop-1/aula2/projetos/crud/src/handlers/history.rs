@@ -0,0 +1,106 @@
+use crate::changes::ChangeKind;
+use crate::codec;
+use crate::collections::{self, Collection};
+use crate::history::Revision;
+use crate::middleware::request_log;
+use crate::models::DataEntry;
+use crate::state::AppState;
+use crate::wal::{Op, Pending};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tide::Request;
+
+// Header com o número da revisão do registro respondido
+pub const REVISION_HEADER: &str = "X-Revision";
+
+#[derive(Serialize)]
+struct HistoryResponse<'a> {
+    id: u32,
+    revisions: Vec<&'a Revision>,
+}
+
+#[derive(Deserialize)]
+struct RevertBody {
+    // Revisão cujo conteúdo volta a ser o atual
+    rev: u32,
+}
+
+pub async fn read_history(req: Request<AppState>) -> tide::Result {
+    // Extrai o id da URL (ex: /data/:id/history)
+    let id: u32 = match req.param("id")?.parse() {
+        Ok(val) => val,
+        Err(_) => return Err(tide::Error::from_str(400, "Invalid id")),
+    };
+    request_log::record_id(id);
+
+    // Pega o histórico do registro (da revisão mais antiga para a atual). Um
+    // registro na lixeira não tem histórico visível até ser restaurado.
+    let collection = collections::from_request(&req)?;
+    let map = collection.data.read().await;
+    if !map.contains_key(&id) {
+        return Err(tide::Error::from_str(404, "Not found"));
+    }
+    let history = collection.history.read();
+    let revisions = history
+        .revisions(id)
+        .ok_or_else(|| tide::Error::from_str(404, "Not found"))?;
+
+    codec::response(&req, &HistoryResponse { id, revisions })
+}
+
+pub async fn revert_data(mut req: Request<AppState>) -> tide::Result {
+    // Extrai o id da URL (ex: /data/:id/revert)
+    let id: u32 = match req.param("id")?.parse() {
+        Ok(val) => val,
+        Err(_) => return Err(tide::Error::from_str(400, "Invalid id")),
+    };
+    request_log::record_id(id);
+
+    // Lê a revisão de destino do corpo: { "rev": N }
+    let body: RevertBody = req.body_json().await?;
+
+    // Pega a coleção da requisição
+    let collection = collections::from_request(&req)?;
+    let mut map = collection.data.write().await;
+    let (rev, pending) = revert(req.state(), &collection, &mut map, id, body.rev)?;
+    drop(map);
+    pending.durable().await?;
+
+    let mut res: tide::Response =
+        tide::Body::from_json(&serde_json::json!({ "id": id, "rev": rev }))?.into();
+    res.insert_header(REVISION_HEADER, rev.to_string());
+    Ok(res)
+}
+
+// Grava o conteúdo da revisão `target` como uma revisão nova e devolve o número dela.
+// Chamado com `data` travado para escrita (sempre `data` antes de `history`).
+fn revert(
+    state: &AppState,
+    collection: &Collection,
+    map: &mut HashMap<u32, DataEntry>,
+    id: u32,
+    target: u32,
+) -> tide::Result<(u32, Pending)> {
+    let mut history = collection.history.write();
+    let Some(current) = map.get_mut(&id) else {
+        return Err(tide::Error::from_str(404, "Not found"));
+    };
+    let entry = history
+        .get(id, target)
+        .map(|revision| revision.entry.clone())
+        .ok_or_else(|| tide::Error::from_str(404, "Revision not found"))?;
+
+    // Enfileira no WAL na mesma ordem em que muda a memória (sem WAL, não faz nada)
+    let op = Op::Put {
+        id,
+        entry: entry.clone(),
+        reverted_from: Some(target),
+    };
+    let pending = state.log_ops(collection, vec![op])?;
+
+    // O revert não apaga revisões: o conteúdo antigo vira uma revisão nova
+    let rev = history.record(id, entry.clone(), Some(target));
+    collection.publish(ChangeKind::Update, id, Some(entry.clone()));
+    *current = entry;
+    Ok((rev, pending))
+}
@@ -1,13 +1,24 @@
+use crate::codec;
+use crate::collections;
+use crate::handlers::history::REVISION_HEADER;
+use crate::middleware::request_log;
 use crate::state::AppState;
+use serde::Deserialize;
 use tide::Request;
 
 pub async fn read_all_data(req: Request<AppState>) -> tide::Result {
-    // Pega o estado global
-    let state = req.state();
-    let map = state.lock().unwrap();
+    // Pega a coleção da requisição
+    let collection = collections::from_request(&req)?;
+    let map = collection.data.read().await;
 
-    // Retorna todos os registros como JSON
-    Ok(tide::Body::from_json(&*map)?.into())
+    // Retorna todos os registros no formato pedido pelo Accept
+    codec::response(&req, &*map)
+}
+
+#[derive(Deserialize)]
+struct ReadQuery {
+    // Revisão antiga do registro (ver GET /data/:id/history)
+    rev: Option<u32>,
 }
 
 pub async fn read_data(req: Request<AppState>) -> tide::Result {
@@ -16,14 +27,37 @@ pub async fn read_data(req: Request<AppState>) -> tide::Result {
         Ok(val) => val,
         Err(_) => return Err(tide::Error::from_str(400, "Invalid id")),
     };
+    request_log::record_id(id);
+    let query: ReadQuery = req
+        .query()
+        .map_err(|_| tide::Error::from_str(400, "Invalid rev"))?;
 
-    // Pega o estado global
-    let state = req.state();
-    let map = state.lock().unwrap();
+    // Pega a coleção da requisição
+    let collection = collections::from_request(&req)?;
+    let map = collection.data.read().await;
+
+    // Com ?rev=N, responde o registro como estava naquela revisão (só dos
+    // registros ativos: o histórico de um registro na lixeira não é exposto)
+    if let Some(rev) = query.rev {
+        if !map.contains_key(&id) {
+            return Err(tide::Error::from_str(404, "Not found"));
+        }
+        let history = collection.history.read();
+        let revision = history
+            .get(id, rev)
+            .ok_or_else(|| tide::Error::from_str(404, "Revision not found"))?;
+        let mut res = codec::response(&req, &revision.entry)?;
+        res.insert_header(REVISION_HEADER, rev.to_string());
+        return Ok(res);
+    }
 
     // Procura o registro pelo id
     if let Some(entry) = map.get(&id) {
-        Ok(tide::Body::from_json(entry)?.into())
+        let mut res = codec::response(&req, entry)?;
+        if let Some(rev) = collection.history.read().current(id) {
+            res.insert_header(REVISION_HEADER, rev.to_string());
+        }
+        Ok(res)
     } else {
         Ok(tide::Response::new(404))
     }
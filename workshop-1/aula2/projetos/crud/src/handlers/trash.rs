@@ -0,0 +1,65 @@
+use crate::changes::ChangeKind;
+use crate::codec;
+use crate::collections::{self, Collection};
+use crate::middleware::request_log;
+use crate::models::DataEntry;
+use crate::state::AppState;
+use crate::wal::{Op, Pending};
+use std::collections::HashMap;
+use tide::Request;
+
+pub async fn read_trash(req: Request<AppState>) -> tide::Result {
+    // Pega a lixeira do estado global
+    let collection = collections::from_request(&req)?;
+    let trash = collection.trash.read();
+
+    // Retorna os registros removidos com a hora da remoção
+    codec::response(&req, trash.all())
+}
+
+pub async fn restore_data(req: Request<AppState>) -> tide::Result {
+    // Extrai o id da URL (ex: /data/:id/restore)
+    let id: u32 = match req.param("id")?.parse() {
+        Ok(val) => val,
+        Err(_) => return Err(tide::Error::from_str(400, "Invalid id")),
+    };
+    request_log::record_id(id);
+
+    // Pega a coleção da requisição
+    let collection = collections::from_request(&req)?;
+    let mut map = collection.data.write().await;
+    let pending = restore(req.state(), &collection, &mut map, id)?;
+    drop(map);
+    pending.durable().await?;
+
+    Ok(tide::Body::from_json(&serde_json::json!({ "id": id }))?.into())
+}
+
+// Devolve o registro da lixeira para os dados, se o id estiver livre e ainda
+// couber na coleção.
+// Chamado com `data` travado para escrita (sempre `data` antes de `trash`).
+fn restore(
+    state: &AppState,
+    collection: &Collection,
+    map: &mut HashMap<u32, DataEntry>,
+    id: u32,
+) -> tide::Result<Pending> {
+    let mut trash = collection.trash.write();
+    if !trash.contains(id) {
+        return Err(tide::Error::from_str(404, "Not in trash"));
+    }
+    // Não sobrescreve um registro ativo com o mesmo id
+    if map.contains_key(&id) {
+        return Err(tide::Error::from_str(
+            409,
+            "Id in use: já existe um registro ativo com esse id",
+        ));
+    }
+    collection.check_capacity(map.len(), 1)?;
+    // Enfileira no WAL na mesma ordem em que muda a memória (sem WAL, não faz nada)
+    let pending = state.log_ops(collection, vec![Op::Restore { id }])?;
+    let entry = trash.take(id).expect("registro conferido acima");
+    collection.publish(ChangeKind::Restore, id, Some(entry.clone()));
+    map.insert(id, entry);
+    Ok(pending)
+}
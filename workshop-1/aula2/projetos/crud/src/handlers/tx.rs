@@ -0,0 +1,257 @@
+use crate::changes::ChangeKind;
+use crate::collections::{self, Collection};
+use crate::handlers::bulk::{commit, rollback, wal_ops};
+use crate::history::History;
+use crate::models::DataEntry;
+use crate::state::{self, AppState};
+use crate::wal::Pending;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tide::{Request, Response, StatusCode};
+
+// Quantidade máxima de operações em uma transação
+const MAX_TX_OPERATIONS: usize = 1_000;
+
+// Uma operação da transação. As operações rodam em ordem e cada uma enxerga as
+// escritas das anteriores:
+// { "op": "read", "id": 1 }
+// { "op": "check", "id": 1, "version": 3 }   (version 0: o registro não existe)
+// { "op": "create", "entry": {...} }
+// { "op": "update", "id": 1, "entry": {...} }
+// { "op": "delete", "id": 1 }
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum TxOp {
+    Read { id: u32 },
+    Check { id: u32, version: u32 },
+    Create { entry: DataEntry },
+    Update { id: u32, entry: DataEntry },
+    Delete { id: u32 },
+}
+
+// Resultado de cada operação de uma transação confirmada, na mesma ordem
+#[derive(Serialize)]
+struct TxResult {
+    index: usize,
+    op: &'static str,
+    id: u32,
+    // Versão do registro depois da operação (0: o registro não existe)
+    version: u32,
+    // Registro lido (null se não existe); só nas leituras
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry: Option<Option<DataEntry>>,
+}
+
+// Operação que fez a transação ser desfeita
+#[derive(Serialize)]
+struct TxFailure {
+    index: usize,
+    op: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u32>,
+    status: u16,
+    error: String,
+}
+
+// Estado de uma transação em andamento
+struct Tx<'a> {
+    collection: &'a Collection,
+    map: &'a mut HashMap<u32, DataEntry>,
+    history: &'a History,
+    // Versões dos registros escritos pela transação (o histórico só muda no fim)
+    versions: HashMap<u32, u32>,
+    // Valor anterior de cada id alterado, para poder desfazer
+    undo: Vec<(u32, Option<DataEntry>)>,
+    // Alterações aplicadas, confirmadas só se todas as operações passarem
+    changes: Vec<(ChangeKind, u32, Option<DataEntry>)>,
+    // None quando os ids acabaram (ver Collection::next_id)
+    next_id: Option<u32>,
+}
+
+impl Tx<'_> {
+    // Versão atual do registro: a revisão no histórico, ou 0 se ele não existe
+    fn version(&self, id: u32) -> u32 {
+        if !self.map.contains_key(&id) {
+            return 0;
+        }
+        self.latest(id)
+    }
+
+    // Revisão que a próxima escrita do registro vai receber
+    fn next_version(&mut self, id: u32) -> u32 {
+        let version = self.latest(id) + 1;
+        self.versions.insert(id, version);
+        version
+    }
+
+    fn latest(&self, id: u32) -> u32 {
+        self.versions
+            .get(&id)
+            .copied()
+            .or_else(|| self.history.current(id))
+            .unwrap_or(0)
+    }
+
+    fn apply(&mut self, index: usize, op: TxOp) -> Result<TxResult, TxFailure> {
+        let result = |op, id, version, entry| TxResult {
+            index,
+            op,
+            id,
+            version,
+            entry,
+        };
+        let failure = |op, id, err: tide::Error| TxFailure {
+            index,
+            op,
+            id,
+            status: err.status().into(),
+            error: err.to_string(),
+        };
+        let not_found = |op, id| failure(op, Some(id), tide::Error::from_str(404, "Not found"));
+
+        match op {
+            TxOp::Read { id } => {
+                let entry = self.map.get(&id).cloned();
+                Ok(result("read", id, self.version(id), Some(entry)))
+            }
+            TxOp::Check { id, version } => {
+                let current = self.version(id);
+                if current != version {
+                    return Err(failure(
+                        "check",
+                        Some(id),
+                        tide::Error::from_str(
+                            409,
+                            format!("Version mismatch: esperado {version}, atual {current}"),
+                        ),
+                    ));
+                }
+                Ok(result("check", id, current, None))
+            }
+            TxOp::Create { entry } => {
+                self.collection
+                    .check_size(&entry)
+                    .and_then(|()| self.collection.check_capacity(self.map.len(), 1))
+                    .map_err(|err| failure("create", None, err))?;
+                let id = self
+                    .next_id
+                    .ok_or_else(|| failure("create", None, state::ids_exhausted()))?;
+                self.next_id = id.checked_add(1);
+                self.changes
+                    .push((ChangeKind::Create, id, Some(entry.clone())));
+                self.undo.push((id, self.map.insert(id, entry)));
+                Ok(result("create", id, self.next_version(id), None))
+            }
+            TxOp::Update { id, entry } => {
+                if !self.map.contains_key(&id) {
+                    return Err(not_found("update", id));
+                }
+                self.collection
+                    .check_size(&entry)
+                    .map_err(|err| failure("update", Some(id), err))?;
+                self.changes
+                    .push((ChangeKind::Update, id, Some(entry.clone())));
+                self.undo.push((id, self.map.insert(id, entry)));
+                Ok(result("update", id, self.next_version(id), None))
+            }
+            TxOp::Delete { id } => {
+                let Some(previous) = self.map.remove(&id) else {
+                    return Err(not_found("delete", id));
+                };
+                self.changes
+                    .push((ChangeKind::Delete, id, Some(previous.clone())));
+                self.undo.push((id, Some(previous)));
+                Ok(result("delete", id, 0, None))
+            }
+        }
+    }
+}
+
+// Executa leituras, verificações de versão e escritas com tudo-ou-nada: se
+// qualquer operação falhar, nada é gravado e a resposta é 409 com a operação
+// que falhou.
+pub async fn transaction(mut req: Request<AppState>) -> tide::Result {
+    let items: Vec<Value> = req.body_json().await?;
+    if items.len() > MAX_TX_OPERATIONS {
+        return Err(tide::Error::from_str(
+            413,
+            format!("Too many operations: máximo de {MAX_TX_OPERATIONS}"),
+        ));
+    }
+    // Valida todas as operações antes de travar a coleção
+    let ops = items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            serde_json::from_value::<TxOp>(item)
+                .map_err(|e| tide::Error::from_str(400, format!("Invalid operation {index}: {e}")))
+        })
+        .collect::<tide::Result<Vec<_>>>()?;
+
+    let collection = collections::from_request(&req)?;
+    let mut map = collection.data.write().await;
+    let (status, body, pending) = run(req.state(), &collection, &mut map, ops)?;
+    drop(map);
+    pending.durable().await?;
+    Ok(Response::builder(status)
+        .body(tide::Body::from_json(&body)?)
+        .build())
+}
+
+// Roda as operações e confirma ou desfaz a transação. A transação inteira roda
+// com `data` (travado por quem chama), `trash` e `history` travados.
+fn run(
+    state: &AppState,
+    collection: &Collection,
+    map: &mut HashMap<u32, DataEntry>,
+    ops: Vec<TxOp>,
+) -> tide::Result<(StatusCode, Value, Pending)> {
+    let mut trash = collection.trash.write();
+    let mut history = collection.history.write();
+
+    let mut tx = Tx {
+        collection,
+        next_id: collection.next_id(),
+        map,
+        history: &history,
+        versions: HashMap::new(),
+        undo: Vec::new(),
+        changes: Vec::new(),
+    };
+    let mut results = Vec::with_capacity(ops.len());
+    let mut failed = None;
+    for (index, op) in ops.into_iter().enumerate() {
+        match tx.apply(index, op) {
+            Ok(result) => results.push(result),
+            Err(failure) => {
+                failed = Some(failure);
+                break;
+            }
+        }
+    }
+    let Tx {
+        map, undo, changes, ..
+    } = tx;
+
+    match failed {
+        None => {
+            // A transação inteira vai em um único registro do WAL
+            let pending = match state.log_ops(collection, wal_ops(&changes)) {
+                Ok(pending) => pending,
+                Err(err) => {
+                    rollback(map, undo);
+                    return Err(err);
+                }
+            };
+            commit(collection, &mut trash, &mut history, changes);
+            let body = serde_json::json!({ "committed": true, "results": results });
+            Ok((StatusCode::Ok, body, pending))
+        }
+        Some(failure) => {
+            rollback(map, undo);
+            let body = serde_json::json!({ "committed": false, "failed": failure });
+            Ok((StatusCode::Conflict, body, Pending::ready()))
+        }
+    }
+}
@@ -1,5 +1,11 @@
+use crate::changes::ChangeKind;
+use crate::codec;
+use crate::collections;
+use crate::handlers::history::REVISION_HEADER;
+use crate::middleware::request_log;
 use crate::models::DataEntry;
 use crate::state::AppState;
+use crate::wal::Op;
 use tide::Request;
 
 pub async fn update_data(mut req: Request<AppState>) -> tide::Result {
@@ -8,18 +14,32 @@ pub async fn update_data(mut req: Request<AppState>) -> tide::Result {
         Ok(val) => val,
         Err(_) => return Err(tide::Error::from_str(400, "Invalid id")),
     };
+    request_log::record_id(id);
 
-    // Lê o corpo da requisição como JSON
-    let entry: DataEntry = req.body_json().await?;
+    // Lê o corpo da requisição (JSON, CBOR ou MessagePack, ver src/codec.rs)
+    let entry: DataEntry = codec::read_body(&mut req).await?;
 
-    // Pega o estado global
-    let state = req.state();
-    let mut map = state.lock().unwrap();
+    // Pega a coleção da requisição
+    let collection = collections::from_request(&req)?;
+    let mut map = collection.data.write().await;
 
-    // Atualiza o registro se existir
+    // Atualiza o registro se existir, guardando a nova revisão no histórico
+    if map.contains_key(&id) {
+        collection.check_size(&entry)?;
+    }
     if let std::collections::hash_map::Entry::Occupied(mut e) = map.entry(id) {
+        // Enfileira no WAL na mesma ordem em que muda a memória (sem WAL, não faz nada)
+        let pending = req
+            .state()
+            .log_ops(&collection, vec![Op::put(id, entry.clone())])?;
+        let rev = collection.history.write().record(id, entry.clone(), None);
+        collection.publish(ChangeKind::Update, id, Some(entry.clone()));
         e.insert(entry);
-        Ok(tide::Response::new(200))
+        drop(map);
+        pending.durable().await?;
+        let mut res = tide::Response::new(200);
+        res.insert_header(REVISION_HEADER, rev.to_string());
+        Ok(res)
     } else {
         Ok(tide::Response::new(404))
     }
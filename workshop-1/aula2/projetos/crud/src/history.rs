@@ -0,0 +1,124 @@
+// Histórico de revisões dos registros.
+// Cada create/update/revert acrescenta uma revisão (1, 2, 3, ...) ao histórico do
+// registro; revisões antigas nunca são alteradas, apenas descartadas pela política
+// de retenção ([history] na configuração). A revisão atual nunca é descartada.
+use std::collections::{HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::HistoryConfig;
+use crate::models::DataEntry;
+use crate::state::now_ms;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Revision {
+    pub rev: u32,
+    pub timestamp_ms: u64,
+    pub entry: DataEntry,
+    // Revisão de origem quando esta revisão veio de um revert
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverted_from: Option<u32>,
+}
+
+pub struct History {
+    records: HashMap<u32, VecDeque<Revision>>,
+    max_revisions: usize,
+    max_age_ms: Option<u64>,
+}
+
+impl History {
+    // Cria o histórico a partir das revisões salvas no snapshot. Registros sem
+    // histórico (ex: snapshot antigo) começam com a revisão 1.
+    pub fn new(
+        config: &HistoryConfig,
+        entries: &HashMap<u32, DataEntry>,
+        saved: HashMap<u32, Vec<Revision>>,
+    ) -> Self {
+        let mut history = History {
+            records: saved
+                .into_iter()
+                .filter(|(_, revisions)| !revisions.is_empty())
+                .map(|(id, revisions)| (id, revisions.into()))
+                .collect(),
+            max_revisions: config.max_revisions,
+            max_age_ms: (config.max_age_secs > 0).then_some(config.max_age_secs * 1000),
+        };
+        for (id, entry) in entries {
+            if !history.records.contains_key(id) {
+                history.record(*id, entry.clone(), None);
+            }
+        }
+        history
+    }
+
+    // Acrescenta uma revisão e devolve o número dela
+    pub fn record(&mut self, id: u32, entry: DataEntry, reverted_from: Option<u32>) -> u32 {
+        let revisions = self.records.entry(id).or_default();
+        let rev = revisions.back().map_or(1, |r| r.rev + 1);
+        revisions.push_back(Revision {
+            rev,
+            timestamp_ms: now_ms(),
+            entry,
+            reverted_from,
+        });
+        self.prune(id);
+        rev
+    }
+
+    // Esquece o histórico de um registro removido
+    pub fn remove(&mut self, id: u32) {
+        self.records.remove(&id);
+    }
+
+    // Número da revisão atual do registro
+    pub fn current(&self, id: u32) -> Option<u32> {
+        self.records.get(&id).and_then(|r| r.back()).map(|r| r.rev)
+    }
+
+    // Revisões do registro dentro da retenção, da mais antiga para a mais nova.
+    // Só lê (as leituras usam a trava de leitura): as que já passaram da
+    // retenção ficam de fora aqui e saem de vez na próxima gravação do registro.
+    pub fn revisions(&self, id: u32) -> Option<Vec<&Revision>> {
+        let revisions = self.records.get(&id)?;
+        let cutoff = self
+            .max_age_ms
+            .map(|max_age| now_ms().saturating_sub(max_age));
+        let last = revisions.len() - 1;
+        let first = revisions.len().saturating_sub(self.max_revisions.max(1));
+        Some(
+            revisions
+                .iter()
+                .enumerate()
+                .skip(first)
+                .filter(|(i, r)| *i == last || cutoff.is_none_or(|cutoff| r.timestamp_ms >= cutoff))
+                .map(|(_, r)| r)
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, id: u32, rev: u32) -> Option<&Revision> {
+        self.revisions(id)?.into_iter().find(|r| r.rev == rev)
+    }
+
+    // Todas as revisões, para salvar no snapshot
+    pub fn all(&self) -> &HashMap<u32, VecDeque<Revision>> {
+        &self.records
+    }
+
+    // Aplica a retenção por quantidade e por idade, mantendo sempre a revisão atual
+    fn prune(&mut self, id: u32) {
+        let Some(revisions) = self.records.get_mut(&id) else {
+            return;
+        };
+        while revisions.len() > self.max_revisions.max(1) {
+            revisions.pop_front();
+        }
+        if let Some(max_age) = self.max_age_ms {
+            let cutoff = now_ms().saturating_sub(max_age);
+            while revisions.len() > 1 && revisions.front().is_some_and(|r| r.timestamp_ms < cutoff)
+            {
+                revisions.pop_front();
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+// Índices secundários sobre campos derivados dos registros (ver src/query.rs).
+// Os campos indexados vêm de [indexes] fields na configuração; cada índice é um
+// mapa ordenado valor -> ids, o que atende igualdade e intervalos (<, >=...).
+// Assim como o índice de busca, é atualizado em Collection::publish.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound;
+
+use crate::models::DataEntry;
+use crate::query::{Field, Op, Value};
+
+#[derive(Default)]
+pub struct SecondaryIndexes {
+    // campo -> (valor -> ids)
+    indexes: HashMap<Field, BTreeMap<Value, HashSet<u32>>>,
+    // Valores indexados de cada registro (para removê-lo dos índices)
+    entries: HashMap<u32, Vec<(Field, Value)>>,
+}
+
+impl SecondaryIndexes {
+    pub fn new(fields: &[Field], entries: &HashMap<u32, DataEntry>) -> Self {
+        let mut indexes = SecondaryIndexes {
+            indexes: fields.iter().map(|f| (*f, BTreeMap::new())).collect(),
+            entries: HashMap::new(),
+        };
+        for (id, entry) in entries {
+            indexes.insert(*id, entry);
+        }
+        indexes
+    }
+
+    // Se o índice do campo atende o operador (!= e contains em texto exigem leitura)
+    pub fn supports(&self, field: Field, op: Op) -> bool {
+        self.indexes.contains_key(&field)
+            && match op {
+                Op::Ne => false,
+                Op::Contains => field.is_list(),
+                _ => true,
+            }
+    }
+
+    // Indexa (ou reindexa) o registro
+    pub fn insert(&mut self, id: u32, entry: &DataEntry) {
+        self.remove(id);
+        let mut values = Vec::new();
+        for (field, index) in &mut self.indexes {
+            for value in field.values(id, entry) {
+                index.entry(value.clone()).or_default().insert(id);
+                values.push((*field, value));
+            }
+        }
+        self.entries.insert(id, values);
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        for (field, value) in self.entries.remove(&id).unwrap_or_default() {
+            let Some(index) = self.indexes.get_mut(&field) else {
+                continue;
+            };
+            if let Some(ids) = index.get_mut(&value) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    index.remove(&value);
+                }
+            }
+        }
+    }
+
+    // Ids com algum valor do campo que satisfaz a comparação.
+    // None se o campo não tem índice ou o operador não é atendido por ele.
+    pub fn lookup(&self, field: Field, op: Op, value: &Value) -> Option<HashSet<u32>> {
+        if !self.supports(field, op) {
+            return None;
+        }
+        let index = &self.indexes[&field];
+        let range = match op {
+            Op::Lt => (Bound::Unbounded, Bound::Excluded(value)),
+            Op::Le => (Bound::Unbounded, Bound::Included(value)),
+            Op::Gt => (Bound::Excluded(value), Bound::Unbounded),
+            Op::Ge => (Bound::Included(value), Bound::Unbounded),
+            _ => (Bound::Included(value), Bound::Included(value)),
+        };
+        Some(
+            index
+                .range::<Value, _>(range)
+                .flat_map(|(_, ids)| ids.iter().copied())
+                .collect(),
+        )
+    }
+}
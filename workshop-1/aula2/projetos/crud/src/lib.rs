@@ -0,0 +1,78 @@
+// Biblioteca do CRUD: o binário (src/main.rs) só lê a configuração, carrega o
+// estado e chama `build_app`. Outros apps Tide podem embutir as rotas com
+// `app.at("/crud").nest(crud::build_app(state, &config))`, e os testes
+// usam o app direto, sem abrir porta (ver src/testing.rs).
+pub mod changes;
+pub mod client;
+pub mod codec;
+pub mod collections;
+pub mod config;
+pub mod handlers;
+pub mod history;
+pub mod indexes;
+pub mod logging;
+pub mod middleware;
+pub mod models;
+pub mod openapi;
+pub mod query;
+pub mod raft;
+pub mod routes;
+pub mod search;
+pub mod server;
+pub mod state;
+pub mod storage;
+pub mod sync;
+pub mod testing;
+pub mod tls;
+pub mod trash;
+pub mod wal;
+pub mod webhooks;
+
+use config::Config;
+use middleware::auth::{ApiKeyAuth, WebhookAccess};
+use middleware::cluster::Cluster;
+use middleware::cors::Cors;
+use middleware::idempotency::Idempotency;
+use middleware::rate_limit::RateLimiter;
+use middleware::request_limits::RequestLimits;
+use middleware::request_log::RequestLogger;
+use state::AppState;
+use tide::Server;
+
+// Monta o app Tide com os middlewares e todas as rotas sobre o estado dado.
+// Tarefas de fundo (limpeza da lixeira) e o snapshot ficam com quem chama.
+pub fn build_app(state: AppState, config: &Config) -> Server<AppState> {
+    let mut app = tide::with_state(state);
+
+    // Loga cada requisição com um id (X-Request-Id) em um span estruturado
+    app.with(RequestLogger::new());
+
+    // Responde o preflight de CORS antes da autenticação e libera as respostas
+    // para as origens configuradas
+    app.with(Cors::new(&config.cors));
+
+    // Limita a taxa de chamadas por cliente (também as sem API key válida, que
+    // contam pelo IP) e exige API key quando configurada
+    app.with(RateLimiter::from_config(&config.limits, &config.auth));
+    app.with(ApiKeyAuth::new(&config.auth));
+
+    // Limita o tamanho e o tempo de leitura do body e o prazo de cada handler
+    app.with(RequestLimits::from_config(&config.limits));
+
+    // Assinar webhooks de uma coleção exige a mesma API key que as rotas dela
+    app.with(WebhookAccess::new(&config.auth));
+
+    // Repete a primeira resposta das requisições com Idempotency-Key
+    app.with(Idempotency::from_config(&config.idempotency));
+
+    // Em cluster, escritas e leituras linearizáveis passam pelo líder do Raft
+    // (ver src/raft); o nó é ligado ao estado por `raft::Node::start`
+    if config.cluster.is_enabled() {
+        app.with(Cluster::from_config(&config.cluster));
+    }
+
+    // Define as rotas CRUD e de documentação (ver src/routes.rs)
+    routes::register(&mut app);
+
+    app
+}
@@ -0,0 +1,155 @@
+use crate::config::CorsConfig;
+use tide::http::Method;
+use tide::http::headers::{
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use tide::utils::async_trait;
+use tide::{Middleware, Next, Request, Response, StatusCode};
+
+// Middleware de CORS, para páginas de outras origens chamarem a API pelo navegador.
+// - Requisições sem `Origin` (curl, outros servidores) passam sem mudanças.
+// - Preflight (OPTIONS com Access-Control-Request-Method) é respondido aqui
+//   mesmo, antes da autenticação: 204 com os métodos e headers aceitos, ou
+//   403 se a origem, o método ou algum header não forem aceitos.
+// - Nas demais, a resposta ganha Access-Control-Allow-Origin quando a origem é
+//   aceita; sem ele o navegador esconde a resposta da página.
+#[derive(Debug)]
+pub struct Cors {
+    any_origin: bool,
+    origins: Vec<String>,
+    methods: Vec<Method>,
+    any_header: bool,
+    headers: Vec<String>,
+    exposed_headers: String,
+    allow_credentials: bool,
+    max_age_secs: u64,
+}
+
+impl Cors {
+    // Cria o middleware com a configuração (já validada)
+    pub fn new(config: &CorsConfig) -> Self {
+        Cors {
+            any_origin: config.allowed_origins.iter().any(|o| o == "*"),
+            origins: config.allowed_origins.clone(),
+            methods: config
+                .allowed_methods
+                .iter()
+                .filter_map(|m| m.parse().ok())
+                .collect(),
+            any_header: config.allowed_headers.iter().any(|h| h == "*"),
+            headers: config
+                .allowed_headers
+                .iter()
+                .map(|h| h.to_ascii_lowercase())
+                .collect(),
+            exposed_headers: config.exposed_headers.join(", "),
+            allow_credentials: config.allow_credentials,
+            max_age_secs: config.max_age_secs,
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.origins.is_empty()
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.any_origin || self.origins.iter().any(|o| o == origin)
+    }
+
+    fn allows_headers(&self, requested: &str) -> bool {
+        self.any_header
+            || requested
+                .split(',')
+                .map(|h| h.trim().to_ascii_lowercase())
+                .filter(|h| !h.is_empty())
+                .all(|h| self.headers.contains(&h))
+    }
+
+    // Valor do Access-Control-Allow-Origin: "*" só quando qualquer origem é
+    // aceita e sem credenciais; senão, a própria origem da requisição
+    fn allow_origin_value<'a>(&self, origin: &'a str) -> &'a str {
+        if self.any_origin && !self.allow_credentials {
+            "*"
+        } else {
+            origin
+        }
+    }
+
+    // Resposta do preflight; `None` quando algo pedido não é aceito
+    fn preflight(&self, origin: &str, method: &str, headers: Option<&str>) -> Option<Response> {
+        let method_allowed = method
+            .parse::<Method>()
+            .is_ok_and(|m| self.methods.contains(&m));
+        if !self.allows_origin(origin)
+            || !method_allowed
+            || !headers.is_none_or(|h| self.allows_headers(h))
+        {
+            return None;
+        }
+
+        let methods = self
+            .methods
+            .iter()
+            .map(Method::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        // Com "*" devolvemos os headers pedidos: o curinga não vale com credenciais
+        let allowed_headers = match headers {
+            Some(requested) if self.any_header => requested.to_string(),
+            _ => self.headers.join(", "),
+        };
+        let mut res = Response::new(StatusCode::NoContent);
+        res.insert_header(ACCESS_CONTROL_ALLOW_METHODS, methods);
+        res.insert_header(ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+        res.insert_header(ACCESS_CONTROL_MAX_AGE, self.max_age_secs.to_string());
+        Some(res)
+    }
+
+    fn add_origin_headers(&self, res: &mut Response, origin: &str) {
+        res.insert_header(ACCESS_CONTROL_ALLOW_ORIGIN, self.allow_origin_value(origin));
+        if self.allow_credentials {
+            res.insert_header(ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+        }
+    }
+}
+
+#[async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Cors {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let origin = req.header(ORIGIN).map(|values| values.last().to_string());
+        let Some(origin) = origin.filter(|_| self.is_enabled()) else {
+            return Ok(next.run(req).await);
+        };
+
+        let requested_method = req.header(ACCESS_CONTROL_REQUEST_METHOD);
+        if let (Method::Options, Some(method)) = (req.method(), requested_method) {
+            let headers = req
+                .header(ACCESS_CONTROL_REQUEST_HEADERS)
+                .map(|h| h.as_str());
+            let Some(mut res) = self.preflight(&origin, method.as_str(), headers) else {
+                return Err(tide::Error::from_str(
+                    StatusCode::Forbidden,
+                    "CORS preflight rejected: origem, método ou headers não aceitos",
+                ));
+            };
+            self.add_origin_headers(&mut res, &origin);
+            res.append_header(VARY, "Origin");
+            return Ok(res);
+        }
+
+        let mut res = next.run(req).await;
+        if self.allows_origin(&origin) {
+            self.add_origin_headers(&mut res, &origin);
+            if !self.exposed_headers.is_empty() {
+                res.insert_header(ACCESS_CONTROL_EXPOSE_HEADERS, self.exposed_headers.as_str());
+            }
+        }
+        // A resposta muda conforme a origem: caches não podem reaproveitá-la entre origens
+        if self.allow_origin_value(&origin) != "*" {
+            res.append_header(VARY, "Origin");
+        }
+        Ok(res)
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::collections;
+use crate::config::{AuthConfig, LimitsConfig};
+use crate::middleware::auth::{self, ValidApiKey};
+use crate::routes::path_matches;
+use crate::state::AppState;
+use crate::sync::Mutex;
+use tide::http::Method;
+use tide::utils::async_trait;
+use tide::{Middleware, Next, Request, Response, StatusCode};
+
+// Espera máxima informada quando a rota não reabastece fichas
+const MAX_RETRY_AFTER: u64 = 86_400;
+
+// Máximo de baldes na memória: ao chegar nele, removemos os que já estão cheios
+// e, se não bastar, os usados há mais tempo
+const MAX_BUCKETS: usize = 10_000;
+
+// Limite de uma rota: um "balde de fichas" (token bucket) com capacidade
+// máxima `capacity` que é reabastecido a `refill_per_sec` fichas por segundo.
+#[derive(Clone, Debug)]
+pub struct Limit {
+    pub capacity: u32,
+    pub refill_per_sec: f64,
+}
+
+impl Limit {
+    pub fn new(capacity: u32, refill_per_sec: f64) -> Self {
+        Limit {
+            capacity,
+            refill_per_sec,
+        }
+    }
+}
+
+// Associa um método HTTP + padrão de rota (ex: "/data/:id") a um limite
+#[derive(Clone, Debug)]
+struct RouteLimit {
+    method: Method,
+    pattern: String,
+    limit: Limit,
+}
+
+// Estado de um balde de fichas para um cliente em uma rota
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+// Middleware de rate limiting.
+// Fica antes do ApiKeyAuth, para limitar também as requisições que ele recusa:
+// cada cliente é identificado por uma API key válida (conferida aqui) ou, na
+// falta dela, pelo IP.
+// As rotas /collections/:name/... usam os limites e o balde da rota /data...
+// equivalente: trocar de coleção não dá ao cliente um balde novo.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    routes: Vec<RouteLimit>,
+    // API keys globais de [auth] (as das coleções vêm do estado)
+    api_keys: Vec<String>,
+    buckets: Mutex<HashMap<(String, usize), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Cria o limitador com as rotas da configuração (já validada)
+    pub fn from_config(config: &LimitsConfig, auth: &AuthConfig) -> Self {
+        let limiter = RateLimiter {
+            api_keys: auth.api_keys.clone(),
+            ..RateLimiter::new()
+        };
+        config.rate_limits.iter().fold(limiter, |limiter, route| {
+            let Ok(method) = Method::from_str(&route.method) else {
+                return limiter;
+            };
+            let limit = Limit::new(route.capacity, route.refill_per_sec);
+            limiter.route(method, &route.path, limit)
+        })
+    }
+
+    // Registra o limite de uma rota. A primeira rota que casar com a requisição é usada.
+    pub fn route(mut self, method: Method, pattern: &str, limit: Limit) -> Self {
+        self.routes.push(RouteLimit {
+            method,
+            pattern: pattern.to_string(),
+            limit,
+        });
+        self
+    }
+
+    // Procura o limite configurado para o método + caminho da requisição
+    fn find_route(&self, method: Method, path: &str) -> Option<usize> {
+        self.routes
+            .iter()
+            .position(|r| r.method == method && path_matches(&r.pattern, path))
+    }
+
+    // Tenta consumir uma ficha do balde e devolve quantas fichas restam.
+    // Em caso de falha, devolve quantos segundos faltam para a próxima ficha.
+    fn take_token(&self, key: &str, idx: usize, now: Instant) -> Result<f64, u64> {
+        let limit = &self.routes[idx].limit;
+        let capacity = limit.capacity as f64;
+        let mut buckets = self.buckets.lock();
+        let bucket_key = (key.to_string(), idx);
+
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(&bucket_key) {
+            prune_buckets(&mut buckets, &self.routes, now);
+            if buckets.len() >= MAX_BUCKETS {
+                evict_least_recent(&mut buckets);
+            }
+        }
+
+        let bucket = buckets.entry(bucket_key).or_insert(Bucket {
+            tokens: capacity,
+            last: now,
+        });
+
+        // Reabastece o balde proporcionalmente ao tempo que passou
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.refill_per_sec).min(capacity);
+        bucket.last = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(bucket.tokens)
+        } else {
+            Err(secs_until(1.0 - bucket.tokens, limit.refill_per_sec))
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware<AppState> for RateLimiter {
+    async fn handle(&self, req: Request<AppState>, next: Next<'_, AppState>) -> tide::Result {
+        // Rotas sem limite configurado passam direto
+        let path = req.url().path();
+        let route_path = collections::split_path(path).map_or(path, |(_, rest)| rest);
+        let idx = match self.find_route(req.method(), route_path) {
+            Some(idx) => idx,
+            None => return Ok(next.run(req).await),
+        };
+        let limit = self.routes[idx].limit.clone();
+        let key = match auth::valid_key(&req, &self.api_keys, &auth::collection_keys(&req)) {
+            Some(key) => format!("key:{key}"),
+            None => ip_key(&req),
+        };
+        let now = Instant::now();
+
+        let tokens = match self.take_token(&key, idx, now) {
+            Ok(tokens) => tokens,
+            Err(retry_after) => {
+                let mut res = too_many_requests(retry_after, "Rate limit exceeded");
+                set_rate_limit_headers(&mut res, &limit, 0.0);
+                return Ok(res);
+            }
+        };
+
+        let mut res = next.run(req).await;
+        set_rate_limit_headers(&mut res, &limit, tokens);
+        Ok(res)
+    }
+}
+
+// Identifica o cliente, depois do ApiKeyAuth, pela API key validada ou pelo IP
+// de origem. Uma chave enviada sem autenticação configurada (ou numa rota
+// pública) não conta: com ela, cada requisição ganharia um balde novo.
+pub fn client_key<State>(req: &Request<State>) -> String {
+    match req.ext::<ValidApiKey>() {
+        Some(ValidApiKey(key)) => format!("key:{key}"),
+        None => ip_key(req),
+    }
+}
+
+// Identifica o cliente pelo IP de origem
+fn ip_key<State>(req: &Request<State>) -> String {
+    let addr = req.peer_addr().unwrap_or("unknown");
+    match addr.parse::<SocketAddr>() {
+        Ok(socket) => format!("ip:{}", socket.ip()),
+        Err(_) => format!("ip:{addr}"),
+    }
+}
+
+// Segundos (arredondados para cima) até acumular `missing` fichas
+fn secs_until(missing: f64, refill_per_sec: f64) -> u64 {
+    if refill_per_sec <= 0.0 {
+        return MAX_RETRY_AFTER;
+    }
+    (missing / refill_per_sec).ceil().max(1.0) as u64
+}
+
+// Remove baldes que já estariam cheios: eles equivalem a um cliente novo
+fn prune_buckets(
+    buckets: &mut HashMap<(String, usize), Bucket>,
+    routes: &[RouteLimit],
+    now: Instant,
+) {
+    buckets.retain(|(_, idx), bucket| {
+        let limit = &routes[*idx].limit;
+        let missing = limit.capacity as f64 - bucket.tokens;
+        let idle = now.duration_since(bucket.last);
+        idle < Duration::from_secs(secs_until(missing, limit.refill_per_sec))
+    });
+}
+
+// Remove o quarto dos baldes usados há mais tempo (muitos clientes ativos ao
+// mesmo tempo). O cliente removido volta com o balde cheio.
+fn evict_least_recent(buckets: &mut HashMap<(String, usize), Bucket>) {
+    let mut lasts: Vec<Instant> = buckets.values().map(|bucket| bucket.last).collect();
+    let (_, cutoff, _) = lasts.select_nth_unstable(buckets.len() / 4);
+    let cutoff = *cutoff;
+    buckets.retain(|_, bucket| bucket.last > cutoff);
+}
+
+// Monta a resposta 429 com o header Retry-After
+fn too_many_requests(retry_after: u64, message: &str) -> Response {
+    Response::builder(StatusCode::TooManyRequests)
+        .header("Retry-After", retry_after.to_string())
+        .body(message)
+        .build()
+}
+
+// Adiciona os headers RateLimit-* (limite, fichas restantes e segundos até encher o balde)
+fn set_rate_limit_headers(res: &mut Response, limit: &Limit, tokens: f64) {
+    let reset = secs_until(limit.capacity as f64 - tokens, limit.refill_per_sec);
+    res.insert_header("RateLimit-Limit", limit.capacity.to_string());
+    res.insert_header("RateLimit-Remaining", (tokens.floor() as u64).to_string());
+    res.insert_header("RateLimit-Reset", reset.to_string());
+}
@@ -0,0 +1,1255 @@
+// Documento OpenAPI 3.1 que descreve a API CRUD.
+// Ele é servido em /openapi.json e usado pela página /docs.
+use serde_json::{Value, json};
+
+pub fn spec() -> Value {
+    let mut spec = json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "CRUD API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "API CRUD que armazena registros em memória. Toda resposta traz o header X-Request-Id."
+        },
+        "security": [{ "ApiKey": [] }],
+        "paths": {
+            "/data": {
+                "post": {
+                    "summary": "Cria um registro",
+                    "operationId": "createData",
+                    "parameters": [idempotency_key_parameter()],
+                    "requestBody": entry_body("#/components/schemas/DataEntry"),
+                    "responses": {
+                        "200": entry_response("Id do registro criado", "#/components/schemas/CreatedId"),
+                        "409": error_response("Requisição com a mesma Idempotency-Key ainda em andamento"),
+                        "406": { "$ref": "#/components/responses/NotAcceptable" },
+                        "415": { "$ref": "#/components/responses/UnsupportedMediaType" },
+                        "422": { "$ref": "#/components/responses/UnprocessableEntity" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                },
+                "get": {
+                    "summary": "Lê todos os registros",
+                    "operationId": "readAllData",
+                    "responses": {
+                        "200": entry_response("Registros indexados pelo id", "#/components/schemas/DataMap"),
+                        "406": { "$ref": "#/components/responses/NotAcceptable" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/_bulk": {
+                "post": {
+                    "summary": "Executa um lote de operações de criação, atualização e remoção",
+                    "description": "Todas as operações rodam com o estado travado uma única vez. Com atomic=true, qualquer falha desfaz o lote inteiro e a resposta é 409.",
+                    "operationId": "bulkData",
+                    "parameters": [{
+                        "name": "atomic",
+                        "in": "query",
+                        "required": false,
+                        "schema": { "type": "boolean", "default": false }
+                    }],
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": {
+                                    "type": "array",
+                                    "items": { "$ref": "#/components/schemas/BulkOperation" }
+                                }
+                            }
+                        }
+                    },
+                    "responses": {
+                        "200": json_response("Resultado de cada operação", "#/components/schemas/BulkResponse"),
+                        "409": json_response("Lote atômico desfeito por uma falha", "#/components/schemas/BulkResponse"),
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "413": error_response("Lote com operações demais"),
+                        "422": { "$ref": "#/components/responses/UnprocessableEntity" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/_changes": {
+                "get": {
+                    "summary": "Feed de alterações dos registros",
+                    "description": "Stream SSE com um evento por create/update/delete (o nome do evento é a operação e o id é o event_id). Com os headers de upgrade, a conexão vira um WebSocket que envia os mesmos eventos em JSON. Um evento \"reset\" indica que eventos foram perdidos e o cliente deve recarregar GET /data.",
+                    "operationId": "dataChanges",
+                    "parameters": [
+                        {
+                            "name": "id",
+                            "in": "query",
+                            "required": false,
+                            "description": "Ids de registro separados por vírgula",
+                            "schema": { "type": "string", "example": "1,2" }
+                        },
+                        {
+                            "name": "last_event_id",
+                            "in": "query",
+                            "required": false,
+                            "description": "Retoma após este evento (alternativa ao header Last-Event-ID)",
+                            "schema": { "type": "integer", "format": "int64", "minimum": 0 }
+                        },
+                        {
+                            "name": "Last-Event-ID",
+                            "in": "header",
+                            "required": false,
+                            "description": "Último evento recebido, enviado pelo EventSource ao reconectar",
+                            "schema": { "type": "integer", "format": "int64", "minimum": 0 }
+                        }
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": "Stream de eventos",
+                            "content": {
+                                "text/event-stream": { "schema": { "$ref": "#/components/schemas/ChangeEvent" } }
+                            }
+                        },
+                        "101": { "description": "Conexão convertida para WebSocket" },
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/_trash": {
+                "get": {
+                    "summary": "Lista os registros na lixeira",
+                    "operationId": "readTrash",
+                    "responses": {
+                        "200": entry_response("Registros removidos indexados pelo id", "#/components/schemas/TrashMap"),
+                        "406": { "$ref": "#/components/responses/NotAcceptable" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/_export": {
+                "get": {
+                    "summary": "Exporta todos os registros",
+                    "description": "NDJSON (uma linha por registro, aceito de volta por /data/_import) ou CSV com uma visão plana dos registros. O formato vem de ?format ou do header Accept.",
+                    "operationId": "exportData",
+                    "parameters": [{
+                        "name": "format",
+                        "in": "query",
+                        "required": false,
+                        "schema": { "type": "string", "enum": ["ndjson", "csv"], "default": "ndjson" }
+                    }],
+                    "responses": {
+                        "200": {
+                            "description": "Registros ordenados pelo id",
+                            "content": {
+                                "application/x-ndjson": { "schema": { "$ref": "#/components/schemas/ExportRecord" } },
+                                "text/csv": { "schema": { "type": "string" } }
+                            }
+                        },
+                        "400": error_response("Formato inválido"),
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/_import": {
+                "post": {
+                    "summary": "Importa registros em NDJSON mantendo os ids",
+                    "description": "Os registros são gravados em lotes de 500 conforme o body chega; um erro no meio (linha inválida, conflito, limite) desfaz os lotes anteriores no modo fail e os mantém em skip e overwrite.",
+                    "operationId": "importData",
+                    "parameters": [{
+                        "name": "on_conflict",
+                        "in": "query",
+                        "required": false,
+                        "description": "skip mantém o registro atual, overwrite substitui e fail para a importação no primeiro id que já existir, sem gravar nada do arquivo",
+                        "schema": { "type": "string", "enum": ["skip", "overwrite", "fail"], "default": "fail" }
+                    }],
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/x-ndjson": { "schema": { "$ref": "#/components/schemas/ExportRecord" } }
+                        }
+                    },
+                    "responses": {
+                        "200": json_response("Resumo da importação", "#/components/schemas/ImportResult"),
+                        "400": error_response("Linha inválida no NDJSON"),
+                        "409": error_response("Ids já existentes com on_conflict=fail"),
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/_search": {
+                "get": {
+                    "summary": "Busca registros pelo texto de data1",
+                    "description": "Os termos são comparados sem diferenciar maiúsculas, também como prefixo e com tolerância a erros de digitação. O resultado vem ordenado por relevância (BM25).",
+                    "operationId": "searchData",
+                    "parameters": [
+                        {
+                            "name": "q",
+                            "in": "query",
+                            "required": true,
+                            "description": "Texto buscado",
+                            "schema": { "type": "string", "example": "add" }
+                        },
+                        {
+                            "name": "prefix",
+                            "in": "query",
+                            "required": false,
+                            "description": "Termos também casam como prefixo",
+                            "schema": { "type": "boolean", "default": true }
+                        },
+                        {
+                            "name": "fuzzy",
+                            "in": "query",
+                            "required": false,
+                            "description": "Termos com 4 letras ou mais toleram erros de digitação",
+                            "schema": { "type": "boolean", "default": true }
+                        },
+                        {
+                            "name": "limit",
+                            "in": "query",
+                            "required": false,
+                            "schema": { "type": "integer", "minimum": 1, "maximum": 100, "default": 20 }
+                        }
+                    ],
+                    "responses": {
+                        "200": entry_response("Registros encontrados, do mais relevante", "#/components/schemas/SearchResults"),
+                        "400": error_response("Parâmetro q ausente ou inválido"),
+                        "406": { "$ref": "#/components/responses/NotAcceptable" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/_query": {
+                "get": {
+                    "summary": "Consulta registros com uma expressão de filtro",
+                    "description": "Campos: id, data1 (lista), size (bytes de data2) e hash (sha256 de data2, em hex). Operadores: = != < <= > >= contains, combinados com and, or, not e parênteses. Campos listados em [indexes] fields são consultados pelo índice; os demais exigem ler todos os registros.",
+                    "operationId": "queryData",
+                    "parameters": [
+                        {
+                            "name": "q",
+                            "in": "query",
+                            "required": true,
+                            "description": "Expressão de filtro",
+                            "schema": { "type": "string", "example": "data1 contains \"add\" and size < 4096" }
+                        }
+                    ],
+                    "responses": {
+                        "200": entry_response("Registros que satisfazem a expressão", "#/components/schemas/QueryResults"),
+                        "400": error_response("Parâmetro q ausente ou expressão inválida"),
+                        "406": { "$ref": "#/components/responses/NotAcceptable" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/{id}": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Lê um registro",
+                    "operationId": "readData",
+                    "parameters": [{
+                        "name": "rev",
+                        "in": "query",
+                        "required": false,
+                        "description": "Lê o registro como estava nesta revisão (404 se o registro não está ativo, como na lixeira)",
+                        "schema": { "type": "integer", "format": "int32", "minimum": 1 }
+                    }],
+                    "responses": {
+                        "200": {
+                            "description": "Registro encontrado",
+                            "headers": { "X-Revision": revision_header() },
+                            "content": entry_content("#/components/schemas/DataEntry")
+                        },
+                        "406": { "$ref": "#/components/responses/NotAcceptable" },
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "404": { "$ref": "#/components/responses/NotFound" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                },
+                "put": {
+                    "summary": "Atualiza um registro",
+                    "operationId": "updateData",
+                    "requestBody": entry_body("#/components/schemas/DataEntry"),
+                    "responses": {
+                        "200": {
+                            "description": "Registro atualizado",
+                            "headers": { "X-Revision": revision_header() }
+                        },
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "404": { "$ref": "#/components/responses/NotFound" },
+                        "415": { "$ref": "#/components/responses/UnsupportedMediaType" },
+                        "422": { "$ref": "#/components/responses/UnprocessableEntity" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                },
+                "delete": {
+                    "summary": "Move um registro para a lixeira",
+                    "description": "O registro some das leituras e pode ser restaurado até ser apagado de vez pela limpeza da lixeira.",
+                    "operationId": "deleteData",
+                    "responses": {
+                        "204": { "description": "Registro deletado" },
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "404": { "$ref": "#/components/responses/NotFound" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/{id}/history": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Lista as revisões guardadas de um registro",
+                    "description": "Só para registros ativos: um registro na lixeira responde 404 até ser restaurado.",
+                    "operationId": "readHistory",
+                    "responses": {
+                        "200": entry_response("Revisões, da mais antiga para a atual", "#/components/schemas/History"),
+                        "406": { "$ref": "#/components/responses/NotAcceptable" },
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "404": { "$ref": "#/components/responses/NotFound" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/{id}/revert": {
+                "parameters": [id_parameter()],
+                "post": {
+                    "summary": "Volta o registro ao conteúdo de uma revisão anterior",
+                    "description": "Cria uma revisão nova com o conteúdo da revisão pedida; o histórico não é apagado.",
+                    "operationId": "revertData",
+                    "requestBody": json_body("#/components/schemas/RevertRequest"),
+                    "responses": {
+                        "200": {
+                            "description": "Revisão criada pelo revert",
+                            "headers": { "X-Revision": revision_header() },
+                            "content": {
+                                "application/json": { "schema": { "$ref": "#/components/schemas/RevertResult" } }
+                            }
+                        },
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "404": error_response("Registro ou revisão não encontrados"),
+                        "422": { "$ref": "#/components/responses/UnprocessableEntity" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/data/{id}/restore": {
+                "parameters": [id_parameter()],
+                "post": {
+                    "summary": "Tira um registro da lixeira",
+                    "operationId": "restoreData",
+                    "responses": {
+                        "200": json_response("Registro restaurado", "#/components/schemas/CreatedId"),
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "404": error_response("Registro não está na lixeira"),
+                        "409": error_response("Já existe um registro ativo com esse id"),
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/collections": {
+                "post": {
+                    "summary": "Cria uma coleção",
+                    "description": "Cada coleção tem seus próprios registros, lixeira, histórico e sequência de ids, com as mesmas rotas de /data em /collections/{name}/data.",
+                    "operationId": "createCollection",
+                    "requestBody": json_body("#/components/schemas/CollectionCreate"),
+                    "responses": {
+                        "201": json_response("Coleção criada", "#/components/schemas/CollectionName"),
+                        "400": error_response("Nome de coleção inválido"),
+                        "409": error_response("Já existe uma coleção com esse nome"),
+                        "422": { "$ref": "#/components/responses/UnprocessableEntity" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                },
+                "get": {
+                    "summary": "Lista as coleções",
+                    "operationId": "listCollections",
+                    "responses": {
+                        "200": {
+                            "description": "Coleções ordenadas pelo nome",
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "type": "array",
+                                        "items": { "$ref": "#/components/schemas/CollectionInfo" }
+                                    }
+                                }
+                            }
+                        },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/collections/{name}": {
+                "parameters": [name_parameter()],
+                "delete": {
+                    "summary": "Remove uma coleção com todos os registros",
+                    "operationId": "dropCollection",
+                    "responses": {
+                        "204": { "description": "Coleção removida" },
+                        "404": error_response("Coleção não encontrada"),
+                        "409": error_response("A coleção default não pode ser removida"),
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/_tx": {
+                "post": {
+                    "summary": "Executa uma transação de leituras, verificações de versão e escritas",
+                    "description": "As operações rodam em ordem, com a coleção travada, e cada uma enxerga as escritas das anteriores. A versão de um registro é o número da revisão atual (ver /data/{id}/history), ou 0 se ele não existe. Se qualquer operação falhar (versão diferente, registro inexistente, limite da coleção), nada é gravado e a resposta é 409 com a operação que falhou.",
+                    "operationId": "transaction",
+                    "requestBody": {
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": {
+                                    "type": "array",
+                                    "items": { "$ref": "#/components/schemas/TxOperation" }
+                                }
+                            }
+                        }
+                    },
+                    "responses": {
+                        "200": json_response("Transação confirmada", "#/components/schemas/TxCommitted"),
+                        "409": json_response("Transação desfeita por uma falha", "#/components/schemas/TxAborted"),
+                        "400": error_response("Operação inválida"),
+                        "413": error_response("Transação com operações demais"),
+                        "422": { "$ref": "#/components/responses/UnprocessableEntity" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/_webhooks": {
+                "post": {
+                    "summary": "Cria uma assinatura de webhook",
+                    "description": "Cada alteração que casa com os filtros é enviada por POST à url, com o evento em JSON (WebhookPayload) e os headers X-Webhook-Id, X-Webhook-Event, X-Webhook-Attempt, X-Webhook-Timestamp e X-Webhook-Signature (sha256=<hex do HMAC-SHA256 de \"<timestamp>.<body>\" com o segredo). Respostas 2xx confirmam a entrega; falhas de rede, 408, 429 e 5xx são tentadas de novo com espera exponencial, e a última falha leva o evento para /_webhooks/_dead_letters. Coleções com API keys próprias exigem uma delas (ou uma global) em X-Api-Key e só são enviadas às assinaturas que as nomeiam em collections.",
+                    "operationId": "createWebhook",
+                    "requestBody": json_body("#/components/schemas/WebhookCreate"),
+                    "responses": {
+                        "201": json_response("Assinatura criada (com o segredo, mostrado só aqui)", "#/components/schemas/Webhook"),
+                        "400": error_response("URL, coleção ou segredo inválidos"),
+                        "409": error_response("Limite de assinaturas atingido"),
+                        "422": { "$ref": "#/components/responses/UnprocessableEntity" },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                },
+                "get": {
+                    "summary": "Lista as assinaturas de webhook",
+                    "operationId": "listWebhooks",
+                    "responses": {
+                        "200": {
+                            "description": "Assinaturas ordenadas pelo id (sem os segredos)",
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "type": "array",
+                                        "items": { "$ref": "#/components/schemas/Webhook" }
+                                    }
+                                }
+                            }
+                        },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/_webhooks/_dead_letters": {
+                "get": {
+                    "summary": "Eventos que esgotaram as tentativas de entrega",
+                    "operationId": "readDeadLetters",
+                    "responses": {
+                        "200": {
+                            "description": "Eventos não entregues, mais recentes primeiro",
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "type": "array",
+                                        "items": { "$ref": "#/components/schemas/DeadLetter" }
+                                    }
+                                }
+                            }
+                        },
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/_webhooks/_dead_letters/{id}/retry": {
+                "parameters": [{
+                    "name": "id",
+                    "in": "path",
+                    "required": true,
+                    "description": "delivery_id do evento",
+                    "schema": { "type": "integer", "format": "int64", "minimum": 1 }
+                }],
+                "post": {
+                    "summary": "Reenvia um evento da lista de falhas",
+                    "operationId": "retryDeadLetter",
+                    "responses": {
+                        "202": { "description": "Evento fora da lista; as tentativas recomeçam" },
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "404": error_response("Evento não está na lista de falhas"),
+                        "409": error_response("A assinatura do evento foi removida"),
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/_webhooks/{id}": {
+                "parameters": [id_parameter()],
+                "delete": {
+                    "summary": "Remove uma assinatura de webhook",
+                    "operationId": "deleteWebhook",
+                    "responses": {
+                        "204": { "description": "Assinatura removida; as entregas pendentes param" },
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "404": error_response("Assinatura não encontrada"),
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/_webhooks/{id}/deliveries": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Log de entregas de uma assinatura",
+                    "operationId": "readDeliveries",
+                    "responses": {
+                        "200": {
+                            "description": "Últimas tentativas de entrega, mais recentes primeiro",
+                            "content": {
+                                "application/json": {
+                                    "schema": {
+                                        "type": "array",
+                                        "items": { "$ref": "#/components/schemas/DeliveryAttempt" }
+                                    }
+                                }
+                            }
+                        },
+                        "400": { "$ref": "#/components/responses/BadRequest" },
+                        "404": error_response("Assinatura não encontrada"),
+                        "401": { "$ref": "#/components/responses/Unauthorized" },
+                        "429": { "$ref": "#/components/responses/TooManyRequests" }
+                    }
+                }
+            },
+            "/openapi.json": {
+                "get": {
+                    "summary": "Este documento OpenAPI",
+                    "operationId": "openapiSpec",
+                    "security": [],
+                    "responses": {
+                        "200": {
+                            "description": "Documento OpenAPI 3.1",
+                            "content": { "application/json": { "schema": { "type": "object" } } }
+                        }
+                    }
+                }
+            },
+            "/docs": {
+                "get": {
+                    "summary": "Página HTML de documentação",
+                    "operationId": "docsPage",
+                    "security": [],
+                    "responses": {
+                        "200": {
+                            "description": "Página de documentação",
+                            "content": { "text/html": { "schema": { "type": "string" } } }
+                        }
+                    }
+                }
+            }
+        },
+        "components": {
+            "securitySchemes": {
+                "ApiKey": {
+                    "type": "apiKey",
+                    "in": "header",
+                    "name": "X-Api-Key",
+                    "description": "Exigida apenas quando o servidor tem API keys configuradas"
+                }
+            },
+            "schemas": schemas(),
+            "responses": {
+                "BadRequest": error_response("Id inválido"),
+                "NotFound": error_response("Registro não encontrado"),
+                "Unauthorized": error_response("API key ausente ou inválida"),
+                "UnprocessableEntity": error_response("Corpo não corresponde ao schema"),
+                "UnsupportedMediaType": error_response("Content-Type não suportado"),
+                "NotAcceptable": error_response("Nenhum formato do Accept é suportado"),
+                "PayloadTooLarge": error_response("Body maior que o máximo da rota"),
+                "RequestTimeout": error_response("Body não recebido dentro do prazo"),
+                "GatewayTimeout": error_response("Handler não respondeu dentro do prazo da rota"),
+                "ServiceUnavailable": {
+                    "description": "Servidor desligando, com requisições demais em andamento ou, em cluster, sem líder",
+                    "headers": {
+                        "Retry-After": int_header("Segundos até poder tentar de novo")
+                    }
+                },
+                "TooManyRequests": {
+                    "description": "Limite de taxa excedido",
+                    "headers": {
+                        "Retry-After": int_header("Segundos até poder tentar de novo"),
+                        "RateLimit-Limit": int_header("Capacidade do balde de fichas"),
+                        "RateLimit-Remaining": int_header("Fichas restantes"),
+                        "RateLimit-Reset": int_header("Segundos até o balde encher"),
+                        "X-Request-Id": request_id_header()
+                    },
+                    "content": {
+                        "application/json": { "schema": { "$ref": "#/components/schemas/Error" } }
+                    }
+                }
+            }
+        }
+    });
+    spec["paths"]["/_cluster"] = cluster_path();
+    add_limit_responses(&mut spec);
+    add_collection_paths(&mut spec);
+    spec
+}
+
+// Schemas dos corpos de requisição e resposta (components.schemas)
+fn schemas() -> Value {
+    let mut schemas = json!({
+        "DataEntry": {
+            "type": "object",
+            "required": ["data1", "data2"],
+            "properties": {
+                "data1": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Lista de textos"
+                },
+                "data2": bytes_schema("Lista de bytes")
+            }
+        },
+        "DataMap": {
+            "type": "object",
+            "description": "Mapa de id (como texto) para registro",
+            "additionalProperties": { "$ref": "#/components/schemas/DataEntry" }
+        },
+        "Error": {
+            "type": "object",
+            "required": ["error", "request_id"],
+            "properties": {
+                "error": { "type": "string", "description": "Mensagem de erro" },
+                "request_id": {
+                    "type": "string",
+                    "description": "Id da requisição (mesmo valor do header X-Request-Id)"
+                }
+            }
+        },
+        "BulkOperation": {
+            "type": "object",
+            "required": ["op"],
+            "description": "create exige entry; update exige id e entry; delete exige id",
+            "properties": {
+                "op": { "type": "string", "enum": ["create", "update", "delete"] },
+                "id": { "type": "integer", "format": "int32", "minimum": 0 },
+                "entry": { "$ref": "#/components/schemas/DataEntry" }
+            }
+        },
+        "BulkResult": {
+            "type": "object",
+            "required": ["index", "status"],
+            "properties": {
+                "index": { "type": "integer", "description": "Posição da operação no lote" },
+                "op": { "type": "string", "enum": ["create", "update", "delete"] },
+                "id": { "type": "integer", "format": "int32" },
+                "status": { "type": "integer", "description": "Status HTTP equivalente da operação" },
+                "error": { "type": "string" }
+            }
+        },
+        "BulkResponse": {
+            "type": "object",
+            "required": ["committed", "results"],
+            "properties": {
+                "committed": { "type": "boolean", "description": "false quando um lote atômico foi desfeito" },
+                "results": { "type": "array", "items": { "$ref": "#/components/schemas/BulkResult" } }
+            }
+        },
+        "TxOperation": {
+            "type": "object",
+            "required": ["op"],
+            "description": "read e delete exigem id; check exige id e version; create exige entry; update exige id e entry",
+            "properties": {
+                "op": { "type": "string", "enum": ["read", "check", "create", "update", "delete"] },
+                "id": { "type": "integer", "format": "int32", "minimum": 0 },
+                "version": { "type": "integer", "minimum": 0, "description": "Versão esperada (0: o registro não existe)" },
+                "entry": { "$ref": "#/components/schemas/DataEntry" }
+            }
+        },
+        "TxCommitted": {
+            "type": "object",
+            "required": ["committed", "results"],
+            "properties": {
+                "committed": { "type": "boolean", "enum": [true] },
+                "results": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["index", "op", "id", "version"],
+                        "properties": {
+                            "index": { "type": "integer" },
+                            "op": { "type": "string", "enum": ["read", "check", "create", "update", "delete"] },
+                            "id": { "type": "integer", "format": "int32" },
+                            "version": { "type": "integer", "description": "Versão depois da operação (0: o registro não existe)" },
+                            "entry": {
+                                "oneOf": [{ "$ref": "#/components/schemas/DataEntry" }, { "type": "null" }],
+                                "description": "Registro lido (só em read)"
+                            }
+                        }
+                    }
+                }
+            }
+        },
+        "TxAborted": {
+            "type": "object",
+            "required": ["committed", "failed"],
+            "properties": {
+                "committed": { "type": "boolean", "enum": [false] },
+                "failed": {
+                    "type": "object",
+                    "required": ["index", "op", "status", "error"],
+                    "properties": {
+                        "index": { "type": "integer" },
+                        "op": { "type": "string" },
+                        "id": { "type": "integer", "format": "int32" },
+                        "status": { "type": "integer", "description": "Status HTTP equivalente da falha" },
+                        "error": { "type": "string" }
+                    }
+                }
+            }
+        },
+        "ChangeEvent": {
+            "type": "object",
+            "required": ["event_id", "op", "id", "timestamp_ms"],
+            "properties": {
+                "event_id": { "type": "integer", "format": "int64", "minimum": 1 },
+                "op": { "type": "string", "enum": ["create", "update", "delete", "restore"] },
+                "id": { "type": "integer", "format": "int32", "description": "Id do registro alterado" },
+                "entry": {
+                    "$ref": "#/components/schemas/DataEntry",
+                    "description": "Conteúdo atual do registro (ausente no delete)"
+                },
+                "timestamp_ms": { "type": "integer", "format": "int64" }
+            }
+        },
+        "Revision": {
+            "type": "object",
+            "required": ["rev", "timestamp_ms", "entry"],
+            "properties": {
+                "rev": { "type": "integer", "format": "int32", "minimum": 1 },
+                "timestamp_ms": { "type": "integer", "format": "int64" },
+                "entry": { "$ref": "#/components/schemas/DataEntry" },
+                "reverted_from": {
+                    "type": "integer",
+                    "format": "int32",
+                    "description": "Revisão copiada, quando a revisão veio de um revert"
+                }
+            }
+        },
+        "History": {
+            "type": "object",
+            "required": ["id", "revisions"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "revisions": { "type": "array", "items": { "$ref": "#/components/schemas/Revision" } }
+            }
+        },
+        "RevertRequest": {
+            "type": "object",
+            "required": ["rev"],
+            "properties": { "rev": { "type": "integer", "format": "int32", "minimum": 1 } }
+        },
+        "RevertResult": {
+            "type": "object",
+            "required": ["id", "rev"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "rev": { "type": "integer", "format": "int32", "description": "Nova revisão atual" }
+            }
+        },
+        "TrashedEntry": {
+            "type": "object",
+            "required": ["entry", "deleted_at_ms"],
+            "properties": {
+                "entry": { "$ref": "#/components/schemas/DataEntry" },
+                "deleted_at_ms": { "type": "integer", "format": "int64" }
+            }
+        },
+        "TrashMap": {
+            "type": "object",
+            "description": "Mapa de id (como texto) para registro removido",
+            "additionalProperties": { "$ref": "#/components/schemas/TrashedEntry" }
+        },
+        "ExportRecord": {
+            "description": "Uma linha do NDJSON: o id junto com os campos do registro",
+            "allOf": [
+                {
+                    "type": "object",
+                    "required": ["id"],
+                    "properties": { "id": { "type": "integer", "format": "int32" } }
+                },
+                { "$ref": "#/components/schemas/DataEntry" }
+            ]
+        },
+        "SearchResults": {
+            "type": "object",
+            "required": ["total", "results"],
+            "properties": {
+                "total": { "type": "integer", "description": "Quantidade de registros encontrados (antes do limit)" },
+                "results": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "score", "entry", "highlights"],
+                        "properties": {
+                            "id": { "type": "integer", "format": "int32" },
+                            "score": { "type": "number" },
+                            "entry": { "$ref": "#/components/schemas/DataEntry" },
+                            "highlights": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Textos de data1 com os termos encontrados entre <em> e </em> (HTML escapado)"
+                            }
+                        }
+                    }
+                }
+            }
+        },
+        "QueryResults": {
+            "type": "object",
+            "required": ["plan", "scanned", "count", "entries"],
+            "properties": {
+                "plan": { "type": "string", "description": "Plano usado, ex: intersect(index(size < 4096), index(data1 contains \"add\")) ou scan" },
+                "scanned": { "type": "integer", "description": "Registros avaliados depois de consultar os índices" },
+                "count": { "type": "integer" },
+                "entries": {
+                    "type": "object",
+                    "additionalProperties": { "$ref": "#/components/schemas/DataEntry" },
+                    "description": "Registros encontrados, indexados pelo id"
+                }
+            }
+        },
+        "ImportResult": {
+            "type": "object",
+            "required": ["created", "overwritten", "skipped"],
+            "properties": {
+                "created": { "type": "integer" },
+                "overwritten": { "type": "integer" },
+                "skipped": { "type": "integer" }
+            }
+        },
+        "CollectionCreate": {
+            "type": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "type": "string", "pattern": "^[A-Za-z0-9-][A-Za-z0-9_-]{0,63}$" },
+                "max_entries": { "type": "integer", "minimum": 0, "description": "Máximo de registros (fora a lixeira)" },
+                "max_entry_bytes": { "type": "integer", "minimum": 0, "description": "Tamanho máximo do campo de bytes" },
+                "api_keys": { "type": "array", "items": { "type": "string" }, "description": "Chaves aceitas nesta coleção, além das globais" }
+            }
+        },
+        "CollectionName": {
+            "type": "object",
+            "required": ["name"],
+            "properties": { "name": { "type": "string" } }
+        },
+        "CollectionInfo": {
+            "type": "object",
+            "required": ["name", "entries", "trashed", "protected"],
+            "properties": {
+                "name": { "type": "string" },
+                "entries": { "type": "integer" },
+                "trashed": { "type": "integer" },
+                "max_entries": { "type": "integer" },
+                "max_entry_bytes": { "type": "integer" },
+                "protected": { "type": "boolean", "description": "Se a coleção tem API keys próprias" }
+            }
+        },
+        "CreatedId": {
+            "type": "object",
+            "required": ["id"],
+            "properties": { "id": { "type": "integer", "format": "int32", "minimum": 1 } }
+        }
+    });
+    if let (Some(schemas), Value::Object(webhooks)) = (schemas.as_object_mut(), webhook_schemas()) {
+        schemas.extend(webhooks);
+    }
+    if let (Some(schemas), Value::Object(cluster)) = (schemas.as_object_mut(), cluster_schemas()) {
+        schemas.extend(cluster);
+    }
+    schemas
+}
+
+// Schemas das assinaturas e entregas de webhooks (ver src/webhooks.rs)
+fn webhook_schemas() -> Value {
+    json!({
+        "ChangeOp": {
+            "type": "string",
+            "enum": ["create", "update", "delete", "restore"]
+        },
+        "WebhookCreate": {
+            "type": "object",
+            "required": ["url"],
+            "properties": {
+                "url": { "type": "string", "format": "uri", "description": "URL http:// ou https:// que recebe os eventos" },
+                "events": { "type": "array", "items": { "$ref": "#/components/schemas/ChangeOp" }, "description": "Eventos enviados (vazio: todos)" },
+                "collections": { "type": "array", "items": { "type": "string" }, "description": "Coleções observadas (vazio: todas)" },
+                "secret": { "type": "string", "maxLength": 256, "description": "Chave do HMAC; sem ela, uma aleatória é gerada" }
+            }
+        },
+        "Webhook": {
+            "type": "object",
+            "required": ["id", "url", "events", "collections", "created_ms"],
+            "properties": {
+                "id": { "type": "integer", "format": "int32" },
+                "url": { "type": "string" },
+                "events": { "type": "array", "items": { "$ref": "#/components/schemas/ChangeOp" } },
+                "collections": { "type": "array", "items": { "type": "string" } },
+                "created_ms": { "type": "integer" },
+                "secret": { "type": "string", "description": "Só na resposta da criação" }
+            }
+        },
+        "WebhookPayload": {
+            "description": "Corpo enviado às URLs das assinaturas",
+            "allOf": [
+                { "$ref": "#/components/schemas/ChangeEvent" },
+                {
+                    "type": "object",
+                    "required": ["delivery_id", "subscription_id", "collection"],
+                    "properties": {
+                        "delivery_id": { "type": "integer", "description": "Mesmo valor do header X-Webhook-Id; igual em todas as tentativas" },
+                        "subscription_id": { "type": "integer" },
+                        "collection": { "type": "string" }
+                    }
+                }
+            ]
+        },
+        "DeliveryAttempt": {
+            "type": "object",
+            "required": ["delivery_id", "event_id", "collection", "op", "attempt", "outcome", "duration_ms", "timestamp_ms"],
+            "properties": {
+                "delivery_id": { "type": "integer" },
+                "event_id": { "type": "integer" },
+                "collection": { "type": "string" },
+                "op": { "$ref": "#/components/schemas/ChangeOp" },
+                "attempt": { "type": "integer", "minimum": 1 },
+                "outcome": { "type": "string", "enum": ["delivered", "retrying", "failed"] },
+                "status": { "type": "integer", "description": "Status HTTP da resposta (ausente em falhas de rede)" },
+                "error": { "type": "string" },
+                "duration_ms": { "type": "integer" },
+                "timestamp_ms": { "type": "integer" }
+            }
+        },
+        "DeadLetter": {
+            "type": "object",
+            "required": ["delivery_id", "subscription_id", "url", "collection", "event", "attempts", "last_error", "failed_ms"],
+            "properties": {
+                "delivery_id": { "type": "integer" },
+                "subscription_id": { "type": "integer" },
+                "url": { "type": "string" },
+                "collection": { "type": "string" },
+                "event": { "$ref": "#/components/schemas/ChangeEvent" },
+                "attempts": { "type": "integer" },
+                "last_error": { "type": "string" },
+                "failed_ms": { "type": "integer" }
+            }
+        }
+    })
+}
+
+// Estado do nó no cluster Raft (ver src/raft)
+fn cluster_path() -> Value {
+    json!({
+        "get": {
+            "summary": "Estado deste nó no cluster Raft",
+            "operationId": "clusterStatus",
+            "description": "Sempre atendida pelo próprio nó. Em cluster, as escritas e as leituras linearizáveis vão para o líder: um seguidor repassa a requisição ou responde 307 com o endereço dele. O header X-Read-Consistency (linearizable ou stale) escolhe a consistência de cada leitura.",
+            "responses": {
+                "200": {
+                    "description": "Papel, mandato, líder e progresso do log",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/ClusterStatus" } } }
+                },
+                "401": { "$ref": "#/components/responses/Unauthorized" },
+                "404": error_response("Servidor rodando sem [cluster]")
+            }
+        }
+    })
+}
+
+fn cluster_schemas() -> Value {
+    json!({
+        "ClusterStatus": {
+            "type": "object",
+            "required": ["node_id", "role", "term", "commit_index", "last_applied", "last_log_index", "snapshot_index", "peers"],
+            "properties": {
+                "node_id": { "type": "integer" },
+                "role": { "type": "string", "enum": ["follower", "candidate", "leader"] },
+                "term": { "type": "integer" },
+                "leader_id": { "type": ["integer", "null"] },
+                "leader_url": { "type": ["string", "null"], "description": "URL da API do líder" },
+                "commit_index": { "type": "integer" },
+                "last_applied": { "type": "integer" },
+                "last_log_index": { "type": "integer" },
+                "snapshot_index": { "type": "integer", "description": "Última entrada incluída no snapshot; as anteriores já saíram do log" },
+                "peers": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "required": ["id", "api_url"],
+                        "properties": {
+                            "id": { "type": "integer" },
+                            "api_url": { "type": "string" },
+                            "match_index": { "type": ["integer", "null"], "description": "Última entrada replicada no nó (só no líder)" }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+// As rotas de registros também existem dentro de uma coleção nomeada
+// (/collections/{name}/data...): copia cada uma com o parâmetro "name"
+fn add_collection_paths(spec: &mut Value) {
+    let paths = spec["paths"].as_object_mut().unwrap();
+    let scoped: Vec<(String, Value)> = paths
+        .iter()
+        .filter(|(path, _)| path.starts_with("/data") || *path == "/_tx")
+        .map(|(path, item)| {
+            let mut item = item.as_object().unwrap().clone();
+            let mut parameters = vec![name_parameter()];
+            if let Some(Value::Array(existing)) = item.remove("parameters") {
+                parameters.extend(existing);
+            }
+            for operation in item.values_mut() {
+                if let Some(Value::String(id)) = operation.get_mut("operationId") {
+                    id.push_str("InCollection");
+                }
+                if let Some(responses) = operation["responses"].as_object_mut() {
+                    responses
+                        .entry("404")
+                        .or_insert_with(|| error_response("Registro ou coleção não encontrados"));
+                }
+            }
+            item.insert("parameters".to_string(), Value::Array(parameters));
+            (format!("/collections/{{name}}{path}"), Value::Object(item))
+        })
+        .collect();
+    paths.extend(scoped);
+}
+
+// Respostas dos limites de requisição (ver src/middleware/request_limits.rs e
+// src/server.rs), comuns a todas as operações: 503 e 504 em todas, 413 e 408
+// nas que recebem body
+fn add_limit_responses(spec: &mut Value) {
+    let paths = spec["paths"].as_object_mut().unwrap();
+    for operation in paths
+        .values_mut()
+        .flat_map(|item| item.as_object_mut().unwrap().values_mut())
+    {
+        let has_body = operation.get("requestBody").is_some();
+        let Some(responses) = operation
+            .get_mut("responses")
+            .and_then(Value::as_object_mut)
+        else {
+            continue;
+        };
+        let mut add = |status: &str, name: &str| {
+            responses
+                .entry(status)
+                .or_insert_with(|| json!({ "$ref": format!("#/components/responses/{name}") }));
+        };
+        if has_body {
+            add("413", "PayloadTooLarge");
+            add("408", "RequestTimeout");
+        }
+        add("503", "ServiceUnavailable");
+        add("504", "GatewayTimeout");
+    }
+}
+
+fn name_parameter() -> Value {
+    json!({
+        "name": "name",
+        "in": "path",
+        "required": true,
+        "description": "Nome da coleção",
+        "schema": { "type": "string" }
+    })
+}
+
+// Header opcional das rotas POST idempotentes (ver src/middleware/idempotency.rs)
+fn idempotency_key_parameter() -> Value {
+    json!({
+        "name": "Idempotency-Key",
+        "in": "header",
+        "required": false,
+        "description": "Repetições com a mesma chave e o mesmo body recebem a primeira resposta de novo, com o header Idempotent-Replayed: true, sem rodar a operação outra vez. Se todas as chaves guardadas ainda estão em andamento, uma chave nova recebe 503",
+        "schema": { "type": "string", "maxLength": 255 }
+    })
+}
+
+fn id_parameter() -> Value {
+    json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "integer", "format": "int32", "minimum": 0 }
+    })
+}
+
+// Em JSON os bytes vêm como lista de números ou texto base64;
+// em CBOR e MessagePack, como bytes
+fn bytes_schema(description: &str) -> Value {
+    json!({
+        "oneOf": [
+            { "type": "array", "items": { "type": "integer", "minimum": 0, "maximum": 255 } },
+            { "type": "string", "format": "byte" }
+        ],
+        "description": description
+    })
+}
+
+fn json_body(schema_ref: &str) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": { "$ref": schema_ref } } }
+    })
+}
+
+// Corpo com registros, nos formatos negociados por Content-Type/Accept
+fn entry_body(schema_ref: &str) -> Value {
+    json!({ "required": true, "content": entry_content(schema_ref) })
+}
+
+fn entry_response(description: &str, schema_ref: &str) -> Value {
+    json!({ "description": description, "content": entry_content(schema_ref) })
+}
+
+fn entry_content(schema_ref: &str) -> Value {
+    let schema = json!({ "schema": { "$ref": schema_ref } });
+    json!({
+        "application/json": schema,
+        "application/json; bytes=base64": schema,
+        "application/cbor": schema,
+        "application/msgpack": schema
+    })
+}
+
+fn json_response(description: &str, schema_ref: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": { "$ref": schema_ref } } }
+    })
+}
+
+fn error_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "headers": { "X-Request-Id": request_id_header() },
+        "content": {
+            "application/json": { "schema": { "$ref": "#/components/schemas/Error" } }
+        }
+    })
+}
+
+fn revision_header() -> Value {
+    json!({
+        "description": "Revisão do registro (ver GET /data/{id}/history)",
+        "schema": { "type": "integer" }
+    })
+}
+
+fn request_id_header() -> Value {
+    json!({
+        "description": "Id da requisição, recebido do cliente ou gerado pelo servidor",
+        "schema": { "type": "string" }
+    })
+}
+
+fn int_header(description: &str) -> Value {
+    json!({ "description": description, "schema": { "type": "integer" } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::routes;
+    use std::collections::BTreeSet;
+
+    // Converte um caminho do Tide ("/data/:id") para o formato OpenAPI ("/data/{id}")
+    fn openapi_path(path: &str) -> String {
+        path.split('/')
+            .map(|part| match part.strip_prefix(':') {
+                Some(name) => format!("{{{name}}}"),
+                None => part.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    // Garante que o documento descreve exatamente as rotas registradas no app
+    #[test]
+    fn spec_matches_registered_routes() {
+        let registered: BTreeSet<(String, String)> = routes()
+            .iter()
+            .map(|r| (openapi_path(r.path), r.method.to_string().to_lowercase()))
+            .collect();
+
+        let spec = spec();
+        let documented: BTreeSet<(String, String)> = spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| {
+                item.as_object()
+                    .unwrap()
+                    .keys()
+                    .filter(|key| *key != "parameters")
+                    .map(move |method| (path.clone(), method.clone()))
+            })
+            .collect();
+
+        assert_eq!(registered, documented);
+    }
+
+    // Todas as referências "$ref" precisam apontar para componentes existentes
+    #[test]
+    fn spec_refs_resolve() {
+        fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(r)) = map.get("$ref") {
+                        refs.push(r.clone());
+                    }
+                    map.values().for_each(|v| collect_refs(v, refs));
+                }
+                Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+                _ => {}
+            }
+        }
+
+        let spec = spec();
+        let mut refs = Vec::new();
+        collect_refs(&spec, &mut refs);
+        assert!(!refs.is_empty());
+        for r in refs {
+            let pointer = r.trim_start_matches('#');
+            assert!(spec.pointer(pointer).is_some(), "referência quebrada: {r}");
+        }
+    }
+}
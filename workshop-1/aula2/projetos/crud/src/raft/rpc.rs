@@ -0,0 +1,204 @@
+// RPCs entre os nós, em JSON sobre HTTP (porta de `peer_url`, separada da API):
+// - POST /_raft/vote: pedido de voto (RequestVote)
+// - POST /_raft/append: entradas do log e heartbeat (AppendEntries)
+// - POST /_raft/snapshot?term=&leader_id=&last_index=&last_term=: snapshot
+//   inteiro no body, para seguidores atrasados demais (InstallSnapshot)
+// Com `cluster.secret`, as chamadas levam o header X-Raft-Secret.
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::net::TcpListener;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tide::http::{self, Method, Url};
+use tide::{Body, Request};
+
+use super::Node;
+use super::log::Entry;
+use crate::config::PeerConfig;
+use crate::middleware::auth::constant_time_eq;
+
+const SECRET_HEADER: &str = "X-Raft-Secret";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VoteRequest {
+    pub term: u64,
+    pub candidate_id: u64,
+    pub last_log_index: u64,
+    pub last_log_term: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VoteReply {
+    pub term: u64,
+    pub vote_granted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppendRequest {
+    pub term: u64,
+    pub leader_id: u64,
+    pub prev_log_index: u64,
+    pub prev_log_term: u64,
+    pub entries: Vec<Entry>,
+    pub leader_commit: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppendReply {
+    pub term: u64,
+    pub success: bool,
+    // Com sucesso: última entrada igual à do líder. Sem: até onde o líder deve recuar.
+    pub last_log_index: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SnapshotRequest {
+    pub term: u64,
+    pub leader_id: u64,
+    pub last_index: u64,
+    pub last_term: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SnapshotReply {
+    pub term: u64,
+}
+
+// Atende as RPCs dos outros nós até o processo terminar
+pub async fn serve(node: Arc<Node>, listener: TcpListener) {
+    let mut app = tide::with_state(node);
+    app.at("/_raft/vote").post(vote);
+    app.at("/_raft/append").post(append);
+    app.at("/_raft/snapshot").post(snapshot);
+    if let Err(err) = app.listen(listener).await {
+        tracing::error!(error = %err, "servidor de RPCs do Raft parou");
+    }
+}
+
+async fn vote(mut req: Request<Arc<Node>>) -> tide::Result {
+    authorize(&req)?;
+    let body: VoteRequest = req.body_json().await?;
+    let reply = req
+        .state()
+        .handle_vote(body)
+        .await
+        .map_err(storage_failed)?;
+    Ok(Body::from_json(&reply)?.into())
+}
+
+async fn append(mut req: Request<Arc<Node>>) -> tide::Result {
+    authorize(&req)?;
+    let body: AppendRequest = req.body_json().await?;
+    let reply = req
+        .state()
+        .handle_append(body)
+        .await
+        .map_err(storage_failed)?;
+    Ok(Body::from_json(&reply)?.into())
+}
+
+async fn snapshot(mut req: Request<Arc<Node>>) -> tide::Result {
+    authorize(&req)?;
+    let query: SnapshotRequest = req.query()?;
+    let data = req.body_bytes().await?;
+    let reply = req
+        .state()
+        .handle_snapshot(query, data)
+        .await
+        .map_err(storage_failed)?;
+    Ok(Body::from_json(&reply)?.into())
+}
+
+// Sem gravar em disco, o nó não pode responder (o outro nó trata como sem resposta)
+fn storage_failed(err: std::io::Error) -> tide::Error {
+    tracing::error!(error = %err, "falha ao gravar o log do Raft");
+    tide::Error::from_str(500, "Raft storage failed: estado não gravado")
+}
+
+// Recusa as chamadas de quem não tem o segredo e as que chegam depois do shutdown
+fn authorize(req: &Request<Arc<Node>>) -> tide::Result<()> {
+    let node = req.state();
+    if node.is_stopped() {
+        return Err(tide::Error::from_str(503, "Node stopped: nó desligado"));
+    }
+    let secret = &node.config.secret;
+    let given = req.header(SECRET_HEADER).map_or("", |v| v.as_str());
+    if !secret.is_empty() && !constant_time_eq(given.as_bytes(), secret.as_bytes()) {
+        return Err(tide::Error::from_str(
+            401,
+            "Unauthorized: segredo do cluster inválido",
+        ));
+    }
+    Ok(())
+}
+
+pub async fn request_vote(
+    node: &Node,
+    peer: &PeerConfig,
+    req: &VoteRequest,
+) -> Result<VoteReply, String> {
+    call(
+        node,
+        peer,
+        "/_raft/vote",
+        Body::from_json(req).map_err(|e| e.to_string())?,
+        node.rpc_timeout(),
+    )
+    .await
+}
+
+pub async fn append_entries(
+    node: &Node,
+    peer: &PeerConfig,
+    req: &AppendRequest,
+) -> Result<AppendReply, String> {
+    call(
+        node,
+        peer,
+        "/_raft/append",
+        Body::from_json(req).map_err(|e| e.to_string())?,
+        node.rpc_timeout(),
+    )
+    .await
+}
+
+// O snapshot pode ser grande: usa o prazo de commit em vez do prazo das RPCs
+pub async fn install_snapshot(
+    node: &Node,
+    peer: &PeerConfig,
+    req: &SnapshotRequest,
+    data: Arc<Vec<u8>>,
+) -> Result<SnapshotReply, String> {
+    let path = format!(
+        "/_raft/snapshot?term={}&leader_id={}&last_index={}&last_term={}",
+        req.term, req.leader_id, req.last_index, req.last_term
+    );
+    let timeout = node.rpc_timeout().max(node.commit_timeout());
+    call(node, peer, &path, Body::from_bytes(data.to_vec()), timeout).await
+}
+
+async fn call<T: DeserializeOwned>(
+    node: &Node,
+    peer: &PeerConfig,
+    path: &str,
+    body: Body,
+    timeout: Duration,
+) -> Result<T, String> {
+    let url = Url::parse(&peer.peer_url)
+        .and_then(|url| url.join(path))
+        .map_err(|e| e.to_string())?;
+    let mut req = http::Request::new(Method::Post, url);
+    if !node.config.secret.is_empty() {
+        req.insert_header(SECRET_HEADER, node.config.secret.as_str());
+    }
+    req.set_body(body);
+    let mut res = node.client.send(req, timeout).await?;
+    // Recusa do outro nó (ex: segredo diferente): configuração errada, não queda
+    if !res.status().is_success() {
+        let body = res.body_string().await.unwrap_or_default();
+        tracing::warn!(peer = peer.id, path, status = %res.status(), body, "RPC do Raft recusada");
+        return Err(format!("resposta {}", res.status()));
+    }
+    res.body_json().await.map_err(|e| e.to_string())
+}
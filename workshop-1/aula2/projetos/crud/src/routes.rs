@@ -0,0 +1,129 @@
+use crate::handlers::bulk::bulk_data;
+use crate::handlers::changes::changes;
+use crate::handlers::cluster::cluster_status;
+use crate::handlers::collections::{create_collection, drop_collection, list_collections};
+use crate::handlers::create::create_data;
+use crate::handlers::delete::delete_data;
+use crate::handlers::docs::{docs_page, openapi_spec};
+use crate::handlers::export::export_data;
+use crate::handlers::history::{read_history, revert_data};
+use crate::handlers::import::import_data;
+use crate::handlers::query::query_data;
+use crate::handlers::read::{read_all_data, read_data};
+use crate::handlers::search::search_data;
+use crate::handlers::trash::{read_trash, restore_data};
+use crate::handlers::tx::transaction;
+use crate::handlers::update::update_data;
+use crate::handlers::webhooks::{
+    create_webhook, delete_webhook, list_webhooks, read_dead_letters, read_deliveries,
+    retry_dead_letter,
+};
+use crate::state::AppState;
+use tide::http::Method;
+use tide::{Endpoint, Server};
+
+// Uma rota da API: método HTTP, caminho e o handler que a atende
+pub struct RouteDef {
+    pub method: Method,
+    pub path: &'static str,
+    pub endpoint: Box<dyn Endpoint<AppState>>,
+}
+
+// Tabela com todas as rotas do servidor.
+// O documento OpenAPI (src/openapi.rs) precisa descrever exatamente estas rotas.
+pub fn routes() -> Vec<RouteDef> {
+    vec![
+        route(Method::Post, "/data", create_data),         // Cria
+        route(Method::Get, "/data", read_all_data),        // Lê todos
+        route(Method::Post, "/data/_bulk", bulk_data),     // Lote de operações
+        route(Method::Get, "/data/_changes", changes),     // Feed de alterações (SSE/WebSocket)
+        route(Method::Get, "/data/_trash", read_trash),    // Registros na lixeira
+        route(Method::Get, "/data/_export", export_data),  // Exporta tudo (NDJSON/CSV)
+        route(Method::Post, "/data/_import", import_data), // Importa NDJSON
+        route(Method::Get, "/data/_search", search_data),  // Busca textual
+        route(Method::Get, "/data/_query", query_data),    // Consulta com filtros
+        route(Method::Get, "/data/:id", read_data),        // Lê um
+        route(Method::Put, "/data/:id", update_data),      // Atualiza
+        route(Method::Delete, "/data/:id", delete_data),   // Deleta
+        route(Method::Get, "/data/:id/history", read_history), // Revisões de um registro
+        route(Method::Post, "/data/:id/revert", revert_data), // Volta a uma revisão
+        route(Method::Post, "/data/:id/restore", restore_data), // Tira da lixeira
+        route(Method::Post, "/_tx", transaction),          // Transação com várias operações
+        // Gerência de coleções (ver src/collections.rs)
+        route(Method::Post, "/collections", create_collection),
+        route(Method::Get, "/collections", list_collections),
+        route(Method::Delete, "/collections/:name", drop_collection),
+        // Webhooks das alterações (ver src/webhooks.rs)
+        route(Method::Post, "/_webhooks", create_webhook),
+        route(Method::Get, "/_webhooks", list_webhooks),
+        route(Method::Get, "/_webhooks/_dead_letters", read_dead_letters),
+        route(
+            Method::Post,
+            "/_webhooks/_dead_letters/:id/retry",
+            retry_dead_letter,
+        ),
+        route(Method::Delete, "/_webhooks/:id", delete_webhook),
+        route(Method::Get, "/_webhooks/:id/deliveries", read_deliveries),
+        // As mesmas rotas de registros, dentro de uma coleção nomeada
+        route(Method::Post, "/collections/:name/data", create_data),
+        route(Method::Get, "/collections/:name/data", read_all_data),
+        route(Method::Post, "/collections/:name/data/_bulk", bulk_data),
+        route(Method::Get, "/collections/:name/data/_changes", changes),
+        route(Method::Get, "/collections/:name/data/_trash", read_trash),
+        route(Method::Get, "/collections/:name/data/_export", export_data),
+        route(Method::Post, "/collections/:name/data/_import", import_data),
+        route(Method::Get, "/collections/:name/data/_search", search_data),
+        route(Method::Get, "/collections/:name/data/_query", query_data),
+        route(Method::Get, "/collections/:name/data/:id", read_data),
+        route(Method::Put, "/collections/:name/data/:id", update_data),
+        route(Method::Delete, "/collections/:name/data/:id", delete_data),
+        route(
+            Method::Get,
+            "/collections/:name/data/:id/history",
+            read_history,
+        ),
+        route(
+            Method::Post,
+            "/collections/:name/data/:id/revert",
+            revert_data,
+        ),
+        route(
+            Method::Post,
+            "/collections/:name/data/:id/restore",
+            restore_data,
+        ),
+        route(Method::Post, "/collections/:name/_tx", transaction),
+        route(Method::Get, "/openapi.json", openapi_spec), // Documento OpenAPI
+        route(Method::Get, "/docs", docs_page),            // Página de documentação
+        route(Method::Get, "/_cluster", cluster_status),   // Estado do nó no cluster Raft
+    ]
+}
+
+// Registra todas as rotas da tabela no app Tide
+pub fn register(app: &mut Server<AppState>) {
+    for r in routes() {
+        app.at(r.path).method(r.method, r.endpoint);
+    }
+}
+
+// Compara um padrão como "/data/:id" com o caminho da requisição
+pub fn path_matches(pattern: &str, path: &str) -> bool {
+    let mut pattern_parts = pattern.trim_end_matches('/').split('/');
+    let mut path_parts = path.trim_end_matches('/').split('/');
+    loop {
+        match (pattern_parts.next(), path_parts.next()) {
+            (None, None) => return true,
+            (Some(p), Some(s)) if p.starts_with(':') && !s.is_empty() => continue,
+            (Some(p), Some(s)) if p == s => continue,
+            _ => return false,
+        }
+    }
+}
+
+fn route(method: Method, path: &'static str, endpoint: impl Endpoint<AppState>) -> RouteDef {
+    RouteDef {
+        method,
+        path,
+        endpoint: Box::new(endpoint),
+    }
+}
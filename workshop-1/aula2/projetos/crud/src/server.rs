@@ -0,0 +1,352 @@
+// Loop HTTP com desligamento gracioso.
+// Ao receber SIGINT/SIGTERM o servidor para de aceitar conexões, espera as
+// requisições em andamento terminarem (até `drain_timeout`) e só então retorna,
+// para o main salvar o estado antes de sair.
+// Também limita o tempo para o cliente mandar os headers de cada requisição e
+// quantas requisições são atendidas ao mesmo tempo (ver ServerConfig).
+// Com [server.tls] configurado, as conexões falam HTTPS (ver src/tls.rs) e um
+// listener opcional redireciona o HTTP para o HTTPS.
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_io::Timer;
+use async_std::channel::{self, Receiver};
+use async_std::future;
+use async_std::net::{TcpListener, TcpStream};
+use async_std::task;
+use futures_lite::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use tide::Server;
+use tide::http::{Response, StatusCode};
+
+use crate::config::ServerConfig;
+use crate::state::AppState;
+use crate::sync::Mutex;
+use crate::tls::{self, SharedStream};
+
+// Intervalo entre as verificações de requisições pendentes durante o desligamento
+const DRAIN_POLL: Duration = Duration::from_millis(50);
+
+// Contador de requisições em andamento
+#[derive(Default)]
+struct InFlight {
+    count: AtomicUsize,
+    // Máximo de requisições ao mesmo tempo (0: sem limite)
+    max: usize,
+    shutting_down: AtomicBool,
+}
+
+// Decrementa o contador quando a requisição termina (mesmo em caso de pânico)
+struct InFlightGuard(Arc<InFlight>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub async fn serve(app: Server<AppState>, config: &ServerConfig) -> io::Result<()> {
+    let listener = TcpListener::bind(&config.bind).await?;
+    let tls = if config.tls.is_enabled() {
+        let acceptor = Arc::new(tls::Acceptor::new(&config.tls)?);
+        acceptor.spawn_reloader();
+        Some(acceptor)
+    } else {
+        None
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    tracing::info!(
+        "Servidor CRUD rodando em: {scheme}://{}",
+        listener.local_addr()?
+    );
+    if let Some(bind) = &config.tls.redirect_bind {
+        let redirect = TcpListener::bind(bind).await?;
+        tracing::info!(
+            "Redirecionando HTTP para HTTPS em: http://{}",
+            redirect.local_addr()?
+        );
+        task::spawn(redirect_to_https(redirect, listener.local_addr()?.port()));
+    }
+
+    let shutdown = shutdown_signal()?;
+    let in_flight = Arc::new(InFlight {
+        max: config.max_in_flight,
+        ..InFlight::default()
+    });
+    let header_timeout = Duration::from_secs(config.header_timeout_secs);
+
+    loop {
+        // Espera uma nova conexão ou o sinal de desligamento, o que vier primeiro
+        let accepted = futures_lite::future::or(async { Some(listener.accept().await) }, async {
+            let _ = shutdown.recv().await;
+            None
+        })
+        .await;
+
+        match accepted {
+            Some(Ok((stream, _))) => handle_connection(
+                app.clone(),
+                stream,
+                tls.clone(),
+                in_flight.clone(),
+                header_timeout,
+            ),
+            Some(Err(err)) => tracing::warn!(error = %err, "falha ao aceitar conexão"),
+            None => break,
+        }
+    }
+
+    // Para de aceitar conexões e espera as requisições em andamento
+    drop(listener);
+    in_flight.shutting_down.store(true, Ordering::SeqCst);
+    let pending = in_flight.count.load(Ordering::SeqCst);
+    tracing::info!(pending, "desligando: aguardando requisições em andamento");
+
+    let deadline = Instant::now() + Duration::from_secs(config.shutdown_timeout_secs);
+    while in_flight.count.load(Ordering::SeqCst) > 0 {
+        if Instant::now() >= deadline {
+            let pending = in_flight.count.load(Ordering::SeqCst);
+            tracing::warn!(
+                pending,
+                "tempo de desligamento esgotado; abandonando requisições"
+            );
+            break;
+        }
+        task::sleep(DRAIN_POLL).await;
+    }
+    Ok(())
+}
+
+fn handle_connection(
+    app: Server<AppState>,
+    stream: TcpStream,
+    tls: Option<Arc<tls::Acceptor>>,
+    in_flight: Arc<InFlight>,
+    header_timeout: Duration,
+) {
+    task::spawn(async move {
+        let local_addr = stream.local_addr().ok();
+        let peer_addr = stream.peer_addr().ok();
+        let addrs = (local_addr, peer_addr);
+
+        let result = match tls {
+            None => {
+                let stream = TimedStream::new(stream, header_timeout);
+                serve_http(app, stream, in_flight, addrs, false).await
+            }
+            Some(acceptor) => {
+                // O handshake tem o mesmo prazo dos headers
+                let handshake = acceptor.current().accept(stream);
+                match future::timeout(header_timeout, handshake).await {
+                    Ok(Ok(stream)) => {
+                        let mut shared = SharedStream::new(stream);
+                        let stream = TimedStream::new(shared.clone(), header_timeout);
+                        let result = serve_http(app, stream, in_flight, addrs, true).await;
+                        // Avisa o fim da sessão TLS (close_notify): sem ele, o cliente
+                        // não distingue o fim da resposta de uma conexão cortada
+                        let _ = shared.close().await;
+                        result
+                    }
+                    Ok(Err(err)) => {
+                        tracing::debug!(error = %err, "falha no handshake TLS");
+                        return;
+                    }
+                    Err(_) => {
+                        tracing::debug!("handshake TLS não terminou a tempo");
+                        return;
+                    }
+                }
+            }
+        };
+
+        if let Err(err) = result {
+            tracing::debug!(error = %err, "erro na conexão HTTP");
+        }
+    });
+}
+
+// Atende as requisições de uma conexão (já com TLS, se for o caso)
+async fn serve_http<S>(
+    app: Server<AppState>,
+    stream: TimedStream<S>,
+    in_flight: Arc<InFlight>,
+    (local_addr, peer_addr): (Option<SocketAddr>, Option<SocketAddr>),
+    https: bool,
+) -> tide::http::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Clone + Send + Sync + Unpin + 'static,
+{
+    let head = stream.head.clone();
+    async_h1::accept(stream, |mut req| {
+        let app = app.clone();
+        let in_flight = in_flight.clone();
+        let head = head.clone();
+        async move {
+            // Os headers chegaram: o body e o handler têm os próprios prazos
+            head.finish();
+            let count = in_flight.count.fetch_add(1, Ordering::SeqCst) + 1;
+            let _guard = InFlightGuard(in_flight.clone());
+
+            // Conexões keep-alive podem mandar novas requisições durante o desligamento
+            if in_flight.shutting_down.load(Ordering::SeqCst) {
+                let mut res = Response::new(StatusCode::ServiceUnavailable);
+                res.insert_header("Connection", "close");
+                head.restart();
+                return Ok(res);
+            }
+            if in_flight.max > 0 && count > in_flight.max {
+                let mut res = Response::new(StatusCode::ServiceUnavailable);
+                res.insert_header("Retry-After", "1");
+                res.set_body("Server busy: muitas requisições em andamento");
+                head.restart();
+                return Ok(res);
+            }
+
+            req.set_local_addr(local_addr);
+            req.set_peer_addr(peer_addr);
+            if https {
+                let _ = req.url_mut().set_scheme("https");
+            }
+            let res: tide::http::Result<Response> = app.respond(req).await;
+            // Depois de um upgrade (WebSocket) a conexão não volta a ler headers
+            if !matches!(&res, Ok(res) if res.status() == StatusCode::SwitchingProtocols) {
+                head.restart();
+            }
+            res
+        }
+    })
+    .await
+}
+
+// Listener HTTP que responde 308 para a mesma URL em https://, na porta do
+// servidor HTTPS
+async fn redirect_to_https(listener: TcpListener, https_port: u16) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                tracing::warn!(error = %err, "falha ao aceitar conexão");
+                continue;
+            }
+        };
+        task::spawn(async move {
+            let result = async_h1::accept(stream, |req| async move {
+                let mut location = req.url().clone();
+                let _ = location.set_scheme("https");
+                let _ = location.set_port((https_port != 443).then_some(https_port));
+                let mut res = Response::new(StatusCode::PermanentRedirect);
+                res.insert_header("Location", location.as_str());
+                Ok(res)
+            })
+            .await;
+            if let Err(err) = result {
+                tracing::debug!(error = %err, "erro na conexão HTTP");
+            }
+        });
+    }
+}
+
+// Prazo para o cliente mandar os headers da próxima requisição da conexão.
+// Conta a partir da primeira leitura que precisa esperar pelo cliente, para que
+// uma resposta longa (ex: SSE) não gaste o prazo da requisição seguinte.
+struct HeadTimeout {
+    timeout: Duration,
+    waiting: AtomicBool,
+    timer: Mutex<Option<Timer>>,
+}
+
+impl HeadTimeout {
+    fn finish(&self) {
+        self.waiting.store(false, Ordering::SeqCst);
+    }
+
+    fn restart(&self) {
+        *self.timer.lock() = None;
+        self.waiting.store(true, Ordering::SeqCst);
+    }
+}
+
+// Conexão TCP cujas leituras falham com TimedOut quando os headers demoram
+// mais que o prazo; o async-h1 então fecha a conexão
+#[derive(Clone)]
+struct TimedStream<S> {
+    inner: S,
+    head: Arc<HeadTimeout>,
+}
+
+impl<S> TimedStream<S> {
+    fn new(inner: S, timeout: Duration) -> Self {
+        let head = HeadTimeout {
+            timeout,
+            waiting: AtomicBool::new(true),
+            timer: Mutex::new(None),
+        };
+        TimedStream {
+            inner,
+            head: Arc::new(head),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TimedStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if poll.is_pending() && self.head.waiting.load(Ordering::SeqCst) {
+            let mut timer = self.head.timer.lock();
+            let timer = timer.get_or_insert_with(|| Timer::after(self.head.timeout));
+            if Pin::new(timer).poll(cx).is_ready() {
+                let err = io::Error::new(io::ErrorKind::TimedOut, "headers não recebidos a tempo");
+                return Poll::Ready(Err(err));
+            }
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TimedStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+// Escuta SIGINT/SIGTERM em uma thread e avisa o loop assíncrono por um canal.
+// Um segundo sinal encerra o processo na hora, sem esperar o desligamento gracioso.
+fn shutdown_signal() -> io::Result<Receiver<i32>> {
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    let (tx, rx) = channel::bounded(1);
+    std::thread::spawn(move || {
+        let mut signals = signals.forever();
+        if let Some(signal) = signals.next() {
+            tracing::info!(signal, "sinal de desligamento recebido");
+            let _ = tx.send_blocking(signal);
+        }
+        if let Some(signal) = signals.next() {
+            tracing::warn!(signal, "segundo sinal recebido; saindo imediatamente");
+            std::process::exit(128 + signal);
+        }
+    });
+    Ok(rx)
+}
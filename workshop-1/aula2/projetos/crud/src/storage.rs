@@ -0,0 +1,215 @@
+// Persistência do estado no backend configurado em [storage].
+// No backend "file", o estado é salvo como JSON (snapshot) ao desligar o
+// servidor e restaurado ao iniciar, junto com a lixeira e o histórico de revisões.
+// Entre um snapshot e outro, as escritas ficam no WAL (ver src/wal.rs).
+// A coleção "default" fica no topo do arquivo (formato anterior às coleções);
+// as outras ficam em "collections" e as assinaturas de webhooks em "webhooks".
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use async_std::task;
+use serde::{Deserialize, Serialize};
+
+use crate::collections::{CollectionSettings, DEFAULT_COLLECTION};
+use crate::config::{StorageBackend, StorageConfig};
+use crate::history::Revision;
+use crate::models::DataEntry;
+use crate::state::AppState;
+use crate::trash::TrashedEntry;
+use crate::webhooks::Subscription;
+
+// Conteúdo do arquivo de dados
+#[derive(Deserialize, Default)]
+pub struct Snapshot {
+    pub entries: HashMap<u32, DataEntry>,
+    #[serde(default)]
+    pub trash: HashMap<u32, TrashedEntry>,
+    #[serde(default)]
+    pub history: HashMap<u32, Vec<Revision>>,
+    // Próximo id da coleção default (ausente nos snapshots antigos)
+    #[serde(default)]
+    pub next_id: Option<u32>,
+    #[serde(default)]
+    pub collections: HashMap<String, CollectionSnapshot>,
+    // Assinaturas de webhooks (as entregas pendentes não são salvas)
+    #[serde(default)]
+    pub webhooks: Vec<Subscription>,
+    // Último registro do WAL já contido no snapshot (ver src/wal.rs)
+    #[serde(default)]
+    pub wal_seq: u64,
+}
+
+// Conteúdo salvo de uma coleção
+#[derive(Deserialize, Default)]
+pub struct CollectionSnapshot {
+    #[serde(default)]
+    pub settings: CollectionSettings,
+    pub entries: HashMap<u32, DataEntry>,
+    #[serde(default)]
+    pub trash: HashMap<u32, TrashedEntry>,
+    #[serde(default)]
+    pub history: HashMap<u32, Vec<Revision>>,
+    #[serde(default)]
+    pub next_id: Option<u32>,
+}
+
+// Mesmo formato do Snapshot, sem copiar o estado para salvar
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    #[serde(flatten)]
+    default: CollectionRef<'a>,
+    collections: HashMap<&'a str, CollectionRef<'a>>,
+    webhooks: Vec<Subscription>,
+    wal_seq: u64,
+}
+
+#[derive(Serialize)]
+struct CollectionRef<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    settings: Option<&'a CollectionSettings>,
+    entries: &'a HashMap<u32, DataEntry>,
+    trash: &'a HashMap<u32, TrashedEntry>,
+    history: &'a HashMap<u32, VecDeque<Revision>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_id: Option<u32>,
+}
+
+// Lê o snapshot salvo. Sem snapshot (ou no backend "memory"), começa vazio.
+pub fn load_snapshot(config: &StorageConfig) -> io::Result<Snapshot> {
+    let path = match (config.backend, &config.path) {
+        (StorageBackend::File, Some(path)) => path,
+        _ => return Ok(Snapshot::default()),
+    };
+    if !path.exists() {
+        return Ok(Snapshot::default());
+    }
+    decode_snapshot(&fs::read(path)?)
+}
+
+// Lê um snapshot em JSON (arquivo do backend "file" ou snapshot do cluster)
+pub fn decode_snapshot(bytes: &[u8]) -> io::Result<Snapshot> {
+    match serde_json::from_slice::<Snapshot>(bytes) {
+        Ok(snapshot) => Ok(snapshot),
+        // Arquivos antigos guardavam apenas o mapa de registros
+        Err(err) => match serde_json::from_slice::<HashMap<u32, DataEntry>>(bytes) {
+            Ok(entries) => Ok(Snapshot {
+                entries,
+                ..Snapshot::default()
+            }),
+            Err(_) => Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        },
+    }
+}
+
+// Salva o estado atual no backend configurado.
+// Devolve quantos registros foram gravados (None no backend "memory").
+pub async fn save_snapshot(config: &StorageConfig, state: &AppState) -> io::Result<Option<Saved>> {
+    let path = match (config.backend, &config.path) {
+        (StorageBackend::File, Some(path)) => path.clone(),
+        _ => return Ok(None),
+    };
+    let encoded = encode_snapshot(state).await?;
+    let bytes = encoded.bytes;
+    // A escrita em disco fica fora das threads do executor
+    task::spawn_blocking(move || write_atomic(&path, &bytes)).await?;
+    Ok(Some(Saved {
+        entries: encoded.entries,
+        wal_seq: encoded.wal_seq,
+    }))
+}
+
+// Snapshot em JSON, com quantos registros ele tem e o último registro do WAL incluído
+pub struct Encoded {
+    pub bytes: Vec<u8>,
+    pub entries: usize,
+    pub wal_seq: u64,
+}
+
+pub struct Saved {
+    pub entries: usize,
+    pub wal_seq: u64,
+}
+
+pub async fn encode_snapshot(state: &AppState) -> io::Result<Encoded> {
+    // Sem coleções ou assinaturas mudando no meio (ver AppState::lock_admin)
+    let _admin = state.lock_admin().await;
+    // Trava cada coleção inteira enquanto serializa (ordem: `data` de todas, pelo
+    // nome, e depois `trash` e `history`)
+    let collections = state.all_collections();
+    let mut maps = Vec::with_capacity(collections.len());
+    for (_, collection) in &collections {
+        maps.push(collection.data.read().await);
+    }
+    let locked: Vec<_> = collections
+        .iter()
+        .zip(maps)
+        .map(|((name, collection), map)| {
+            let trash = collection.trash.read();
+            let history = collection.history.read();
+            let next_id = collection.next_id();
+            (
+                name.as_str(),
+                &collection.settings,
+                map,
+                trash,
+                history,
+                next_id,
+            )
+        })
+        .collect();
+
+    let mut default = None;
+    let mut named = HashMap::new();
+    for (name, settings, map, trash, history, next_id) in &locked {
+        let collection = CollectionRef {
+            settings: Some(*settings),
+            entries: map,
+            trash: trash.all(),
+            history: history.all(),
+            next_id: *next_id,
+        };
+        if *name == DEFAULT_COLLECTION {
+            default = Some(CollectionRef {
+                settings: None,
+                ..collection
+            });
+        } else {
+            named.insert(*name, collection);
+        }
+    }
+    let snapshot = SnapshotRef {
+        default: default.expect("a coleção default sempre existe"),
+        collections: named,
+        webhooks: state.webhooks.subscriptions(),
+        // Com todas as coleções travadas, toda escrita já gravada no WAL está no snapshot
+        wal_seq: state.wal().map_or(0, |wal| wal.last_seq()),
+    };
+    let entries = locked.iter().map(|(_, _, map, _, _, _)| map.len()).sum();
+    let bytes = serde_json::to_vec(&snapshot)?;
+    Ok(Encoded {
+        bytes,
+        entries,
+        wal_seq: snapshot.wal_seq,
+    })
+}
+
+// Escreve em um arquivo temporário e renomeia por cima do original,
+// para nunca deixar um snapshot pela metade se o processo cair no meio.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = Path::new(&tmp);
+
+    let mut file = File::create(tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(tmp, path)?;
+
+    // Garante que o rename também chegou ao disco
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,134 @@
+// Benchmark de carga: várias threads fazem GET /data/:id no mesmo app (em
+// processo, sem rede) e medimos as requisições por segundo com 1, 2, 4... threads
+// até o número de núcleos. As leituras não se bloqueiam (ver src/sync.rs), então a
+// vazão deve crescer junto com as threads.
+//
+//     cargo bench --bench load
+//     LOAD_SECS=5 LOAD_THREADS=16 LOAD_WRITE_PERCENT=10 cargo bench --bench load
+//
+// LOAD_SECS: duração de cada rodada (padrão 2)
+// LOAD_RECORDS: registros na coleção (padrão 10000)
+// LOAD_THREADS: quantidade máxima de threads (padrão: núcleos disponíveis)
+// LOAD_WRITE_PERCENT: porcentagem de PUT /data/:id misturados às leituras (padrão 0)
+use std::collections::HashMap;
+use std::env;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crud_e::build_app;
+use crud_e::config::Config;
+use crud_e::models::DataEntry;
+use crud_e::state::{self, AppState};
+use crud_e::storage::Snapshot;
+use crud_e::testing::TestClient;
+use serde_json::json;
+use tide::Server;
+
+fn main() {
+    let secs = env_or("LOAD_SECS", 2);
+    let records = env_or("LOAD_RECORDS", 10_000).max(1);
+    let cores = thread::available_parallelism().map_or(1, |n| n.get() as u64);
+    let max_threads = env_or("LOAD_THREADS", cores).max(1);
+    let write_percent = env_or("LOAD_WRITE_PERCENT", 0).min(100);
+
+    let app = new_app(records as u32);
+    println!(
+        "{records} registros, {write_percent}% de escritas, {secs}s por rodada ({cores} núcleos)"
+    );
+    println!("{:>8} {:>14} {:>10}", "threads", "req/s", "speedup");
+
+    let mut threads = 1;
+    let mut baseline = None;
+    loop {
+        let rate = run(
+            &app,
+            threads,
+            Duration::from_secs(secs),
+            records,
+            write_percent,
+        );
+        let baseline = *baseline.get_or_insert(rate);
+        println!("{threads:>8} {rate:>14.0} {:>9.2}x", rate / baseline);
+        if threads >= max_threads {
+            break;
+        }
+        threads = (threads * 2).min(max_threads);
+    }
+}
+
+// App sem limites de taxa nem API keys, com `records` registros na coleção default
+fn new_app(records: u32) -> Server<AppState> {
+    let mut config = Config::default();
+    config.limits.rate_limits.clear();
+    let entries: HashMap<u32, DataEntry> = (1..=records)
+        .map(|id| {
+            let entry = DataEntry {
+                func_names: vec![format!("fn_{id}")],
+                bytecode: vec![0, 97, 115, 109],
+            };
+            (id, entry)
+        })
+        .collect();
+    let snapshot = Snapshot {
+        entries,
+        ..Snapshot::default()
+    };
+    build_app(state::from_snapshot(snapshot, &config), &config)
+}
+
+// Roda `threads` clientes ao mesmo tempo por `duration` e devolve as requisições/s
+fn run(
+    app: &Server<AppState>,
+    threads: u64,
+    duration: Duration,
+    records: u64,
+    write_percent: u64,
+) -> f64 {
+    let start = Instant::now();
+    let deadline = start + duration;
+    let workers: Vec<_> = (0..threads)
+        .map(|n| {
+            let client = TestClient::new(app.clone());
+            thread::spawn(move || {
+                async_std::task::block_on(async move {
+                    let mut rng = Rng(0x9E37_79B9_7F4A_7C15 ^ (n + 1));
+                    let mut count = 0u64;
+                    while Instant::now() < deadline {
+                        let id = rng.next() % records + 1;
+                        let path = format!("/data/{id}");
+                        let res = if rng.next() % 100 < write_percent {
+                            let entry = json!({ "func_names": ["upd"], "bytecode": [1, 2] });
+                            client.put_json(&path, &entry).await
+                        } else {
+                            client.get(&path).await
+                        };
+                        assert!(res.status().is_success(), "{path}: {}", res.status());
+                        count += 1;
+                    }
+                    count
+                })
+            })
+        })
+        .collect();
+    let total: u64 = workers.into_iter().map(|w| w.join().unwrap()).sum();
+    total as f64 / start.elapsed().as_secs_f64()
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+// Gerador xorshift, só para espalhar os ids lidos
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
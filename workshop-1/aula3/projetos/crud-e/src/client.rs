@@ -0,0 +1,90 @@
+// Cliente HTTP de saída, sobre o async-h1: entregas de webhooks, requisições
+// encaminhadas ao líder do cluster e RPCs do Raft. Cada requisição abre uma
+// conexão nova (http:// ou https://, com as CAs públicas e as de `ca_path`).
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_std::future;
+use async_std::net::TcpStream;
+use futures_rustls::TlsConnector;
+use futures_rustls::rustls::pki_types::pem::PemObject;
+use futures_rustls::rustls::pki_types::{CertificateDer, ServerName};
+use futures_rustls::rustls::{ClientConfig, RootCertStore};
+use tide::http::url::Host;
+use tide::http::{Request, Response};
+
+pub struct Client {
+    tls: TlsConnector,
+}
+
+impl Client {
+    pub fn new(ca_path: Option<&Path>) -> Self {
+        Client {
+            tls: connector(ca_path),
+        }
+    }
+
+    // Envia a requisição e lê a resposta inteira (o body fica em memória)
+    // dentro de `timeout`
+    pub async fn send(&self, req: Request, timeout: Duration) -> Result<Response, String> {
+        future::timeout(timeout, self.exchange(req))
+            .await
+            .map_err(|_| format!("sem resposta em {}ms", timeout.as_millis()))?
+    }
+
+    async fn exchange(&self, req: Request) -> Result<Response, String> {
+        let url = req.url().clone();
+        let host = match url.host() {
+            Some(Host::Ipv6(ip)) => ip.to_string(),
+            Some(host) => host.to_string(),
+            None => return Err("url sem host".to_string()),
+        };
+        let port = url.port_or_known_default().unwrap_or(80);
+        let stream = TcpStream::connect((host.as_str(), port))
+            .await
+            .map_err(|e| format!("falha ao conectar: {e}"))?;
+
+        let res = if url.scheme() == "https" {
+            let name = ServerName::try_from(host).map_err(|e| e.to_string())?;
+            let stream = self
+                .tls
+                .connect(name, stream)
+                .await
+                .map_err(|e| format!("falha no TLS: {e}"))?;
+            async_h1::connect(stream, req).await
+        } else {
+            async_h1::connect(stream, req).await
+        };
+        let mut res = res.map_err(|e| e.to_string())?;
+        let body = res.body_bytes().await.map_err(|e| e.to_string())?;
+        res.set_body(body);
+        Ok(res)
+    }
+}
+
+// Cliente TLS com as CAs públicas e as de `ca_path`
+fn connector(ca_path: Option<&Path>) -> TlsConnector {
+    let mut roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    if let Some(path) = ca_path {
+        let certs = CertificateDer::pem_file_iter(path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>());
+        match certs {
+            Ok(certs) => {
+                let (_, ignored) = roots.add_parsable_certificates(certs);
+                if ignored > 0 {
+                    tracing::warn!(ignored, path = %path.display(), "certificados de CA ignorados");
+                }
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, path = %path.display(), "falha ao ler os certificados de CA")
+            }
+        }
+    }
+    let tls = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(tls))
+}
@@ -0,0 +1,216 @@
+// Negociação de formato dos corpos com registros.
+// O cliente escolhe o formato do corpo enviado pelo Content-Type e o da resposta
+// pelo Accept:
+//   application/json               bytes como lista de números (padrão)
+//   application/json; bytes=base64 bytes como texto base64
+//   application/cbor               CBOR, com os bytes em binário
+//   application/msgpack            MessagePack, com os bytes em binário
+// Em JSON, os bytes são aceitos tanto como lista de números quanto como base64.
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tide::http::headers::{ACCEPT, CONTENT_TYPE};
+use tide::{Body, Request, Response, StatusCode};
+
+use crate::models::BYTES_FIELD;
+
+pub const CBOR: &str = "application/cbor";
+pub const MSGPACK: &str = "application/msgpack";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    JsonBase64,
+    Cbor,
+    MessagePack,
+}
+
+impl Format {
+    // Reconhece um tipo de mídia ("application/json; bytes=base64", ...)
+    fn from_media_type(media_type: &str) -> Option<Format> {
+        let mut parts = media_type.split(';').map(str::trim);
+        let essence = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let base64 = parts.any(|p| p.eq_ignore_ascii_case("bytes=base64"));
+        match essence.as_str() {
+            "application/json" | "application/*" | "*/*" if base64 => Some(Format::JsonBase64),
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            "application/cbor" => Some(Format::Cbor),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Format::MessagePack)
+            }
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::JsonBase64 => "application/json; bytes=base64",
+            Format::Cbor => CBOR,
+            Format::MessagePack => MSGPACK,
+        }
+    }
+}
+
+// Formato do corpo recebido, pelo Content-Type (JSON quando ausente)
+fn request_format<State>(req: &Request<State>) -> tide::Result<Format> {
+    match req.header(CONTENT_TYPE) {
+        None => Ok(Format::Json),
+        Some(value) => Format::from_media_type(value.as_str()).ok_or_else(|| {
+            tide::Error::from_str(
+                StatusCode::UnsupportedMediaType,
+                format!("Unsupported Content-Type: {}", value.as_str()),
+            )
+        }),
+    }
+}
+
+// Formato da resposta pelo Accept, respeitando os pesos (q=).
+// Sem Accept, responde JSON; sem nenhum formato suportado, 406.
+fn response_format<State>(req: &Request<State>) -> tide::Result<Format> {
+    let Some(values) = req.header(ACCEPT) else {
+        return Ok(Format::Json);
+    };
+    let mut candidates: Vec<(f32, Format)> = values
+        .iter()
+        .flat_map(|value| value.as_str().split(','))
+        .filter_map(|item| {
+            let quality = item
+                .split(';')
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let format = Format::from_media_type(item)?;
+            (quality > 0.0).then_some((quality, format))
+        })
+        .collect();
+    // Ordenação estável: com o mesmo peso vale a ordem do header
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+    candidates
+        .first()
+        .map(|(_, format)| *format)
+        .ok_or_else(|| tide::Error::from_str(StatusCode::NotAcceptable, "Not acceptable"))
+}
+
+// Lê o corpo no formato indicado pelo Content-Type
+pub async fn read_body<T: DeserializeOwned, State>(req: &mut Request<State>) -> tide::Result<T> {
+    let format = request_format(req)?;
+    let bytes = req.body_bytes().await?;
+    let parsed = match format {
+        Format::Json | Format::JsonBase64 => {
+            serde_json::from_slice(&bytes).map_err(|e| e.to_string())
+        }
+        Format::Cbor => ciborium::from_reader(bytes.as_slice()).map_err(|e| e.to_string()),
+        Format::MessagePack => rmp_serde::from_slice(&bytes).map_err(|e| e.to_string()),
+    };
+    parsed.map_err(|e| tide::Error::from_str(StatusCode::UnprocessableEntity, e))
+}
+
+// Monta a resposta no formato pedido pelo Accept
+pub fn response<T: Serialize, State>(req: &Request<State>, value: &T) -> tide::Result<Response> {
+    let format = response_format(req)?;
+    let bytes = match format {
+        Format::Json => serde_json::to_vec(value)?,
+        Format::JsonBase64 => {
+            let mut json = serde_json::to_value(value)?;
+            bytes_to_base64(&mut json);
+            serde_json::to_vec(&json)?
+        }
+        Format::Cbor => {
+            let mut buf = Vec::new();
+            ciborium::into_writer(value, &mut buf)?;
+            buf
+        }
+        Format::MessagePack => rmp_serde::to_vec_named(value)?,
+    };
+    let mut res = Response::new(StatusCode::Ok);
+    res.set_body(Body::from_bytes(bytes));
+    res.insert_header(CONTENT_TYPE, format.content_type());
+    res.insert_header("Vary", "Accept");
+    Ok(res)
+}
+
+// Troca as listas de números do campo de bytes por texto base64, em qualquer
+// profundidade (registro, mapa de registros, histórico, ...)
+fn bytes_to_base64(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if key == BYTES_FIELD
+                    && let Some(bytes) = as_bytes(field)
+                {
+                    *field = Value::String(BASE64.encode(bytes));
+                    continue;
+                }
+                bytes_to_base64(field);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(bytes_to_base64),
+        _ => {}
+    }
+}
+
+fn as_bytes(value: &Value) -> Option<Vec<u8>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|n| n.as_u64().and_then(|n| u8::try_from(n).ok()))
+        .collect()
+}
+
+// (De)serialização do campo de bytes do DataEntry.
+// Formatos de texto (JSON) usam lista de números; formatos binários (CBOR,
+// MessagePack) usam bytes. Na leitura aceita também texto base64.
+pub mod bytes {
+    use super::BASE64;
+    use base64::Engine;
+    use serde::de::{self, SeqAccess, Visitor};
+    use serde::{Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_seq(bytes)
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        deserializer.deserialize_any(BytesVisitor)
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("uma lista de bytes ou um texto base64")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1 << 20));
+            while let Some(byte) = seq.next_element::<u8>()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(v)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+            BASE64
+                .decode(v)
+                .map_err(|e| E::custom(format!("base64 inválido: {e}")))
+        }
+    }
+}
@@ -0,0 +1,222 @@
+// Coleções nomeadas: cada uma tem seus próprios registros, lixeira, histórico,
+// feed de alterações e sequência de ids, além de limites e API keys próprios.
+// As rotas /collections/:name/data... usam a coleção do caminho; as rotas
+// /data..., /_tx (e /execute/:id) continuam funcionando sobre a coleção "default".
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tide::{Request, StatusCode};
+
+use crate::changes::{ChangeFeed, ChangeKind};
+use crate::config::HistoryConfig;
+use crate::history::History;
+use crate::indexes::SecondaryIndexes;
+use crate::models::DataEntry;
+use crate::query::Field;
+use crate::search::SearchIndex;
+use crate::state::AppState;
+use crate::storage::CollectionSnapshot;
+use crate::sync::{AsyncRwLock, Mutex, RwLock};
+use crate::trash::Trash;
+use crate::webhooks::Webhooks;
+
+// Coleção usada pelas rotas sem /collections/:name
+pub const DEFAULT_COLLECTION: &str = "default";
+
+// Tamanho máximo do nome de uma coleção
+const MAX_NAME_LEN: usize = 64;
+
+// Limites e permissões de uma coleção (definidos ao criá-la)
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct CollectionSettings {
+    // Quantidade máxima de registros (sem contar a lixeira)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_entries: Option<usize>,
+    // Tamanho máximo, em bytes, do bytecode de cada registro
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_entry_bytes: Option<usize>,
+    // API keys com acesso à coleção, além das globais de [auth]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub api_keys: Vec<String>,
+}
+
+// Estado de uma coleção.
+// Quem precisa de mais de um travamento segue a ordem `data`, `trash`, `history`,
+// `search`, `indexes`, `next_id`. Só `data` é uma trava assíncrona (ver src/sync.rs): as
+// outras são curtas e nunca ficam travadas durante um `.await`.
+pub struct Collection {
+    // Nome da coleção, enviado nos eventos dos webhooks
+    name: String,
+    pub settings: CollectionSettings,
+    // Registros indexados pelo id
+    pub data: AsyncRwLock<HashMap<u32, DataEntry>>,
+    // Registros removidos, ainda restauráveis (ver src/trash.rs)
+    pub trash: RwLock<Trash>,
+    // Revisões de cada registro (ver src/history.rs)
+    pub history: RwLock<History>,
+    // Índice da busca textual (ver src/search.rs)
+    pub search: RwLock<SearchIndex>,
+    // Índices secundários de GET .../data/_query (ver src/indexes.rs)
+    pub indexes: RwLock<SecondaryIndexes>,
+    // Próximo id das criações (ver `next_id`); só avança com `data` travado
+    // para escrita
+    next_id: Mutex<Option<u32>>,
+    // Alterações publicadas para os assinantes de .../data/_changes
+    pub changes: ChangeFeed,
+    // Assinaturas de webhooks, avisadas de cada alteração (ver src/webhooks.rs)
+    webhooks: Arc<Webhooks>,
+    // Coleção já removida: quem ainda a tem em mãos não pode mais escrever nela
+    dropped: AtomicBool,
+}
+
+impl Collection {
+    pub fn new(
+        name: &str,
+        snapshot: CollectionSnapshot,
+        config: &HistoryConfig,
+        indexed: &[Field],
+        webhooks: Arc<Webhooks>,
+    ) -> Self {
+        let history = History::new(config, &snapshot.entries, snapshot.history);
+        let search = SearchIndex::new(&snapshot.entries);
+        let indexes = SecondaryIndexes::new(indexed, &snapshot.entries);
+        let trash = Trash::new(snapshot.trash);
+        // Snapshots antigos não guardam a sequência: continua depois do maior id
+        let next_id = snapshot
+            .next_id
+            .or_else(|| first_free_id(&snapshot.entries, &trash));
+        Collection {
+            name: name.to_string(),
+            settings: snapshot.settings,
+            data: AsyncRwLock::new(snapshot.entries),
+            trash: RwLock::new(trash),
+            history: RwLock::new(history),
+            search: RwLock::new(search),
+            indexes: RwLock::new(indexes),
+            next_id: Mutex::new(next_id),
+            changes: ChangeFeed::new(),
+            webhooks,
+            dropped: AtomicBool::new(false),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_dropped(&self) -> bool {
+        self.dropped.load(Ordering::SeqCst)
+    }
+
+    // Chamado com `data` travado para escrita, ao remover a coleção
+    pub fn mark_dropped(&self) {
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+
+    // Próximo id livre. A sequência nunca volta: ids apagados de vez (lixeira
+    // esvaziada, importação desfeita) não são reaproveitados.
+    // None quando os ids acabaram: dar a volta para 0 sobrescreveria registros.
+    pub fn next_id(&self) -> Option<u32> {
+        *self.next_id.lock()
+    }
+
+    // Registra uma alteração já aplicada em `data`: atualiza os índices de busca
+    // e secundários, avança a sequência de ids e avisa os assinantes do feed e
+    // dos webhooks. Chamado com `data` ainda travado.
+    pub fn publish(&self, op: ChangeKind, id: u32, entry: Option<DataEntry>) {
+        let mut search = self.search.write();
+        let mut indexes = self.indexes.write();
+        match (op, &entry) {
+            (ChangeKind::Delete, _) => {
+                search.remove(id);
+                indexes.remove(id);
+            }
+            (_, Some(entry)) => {
+                search.insert(id, entry);
+                indexes.insert(id, entry);
+            }
+            (_, None) => {}
+        }
+        drop(indexes);
+        drop(search);
+        // Todo id gravado (criação, importação, replay do WAL) fica para trás
+        let mut next_id = self.next_id.lock();
+        if next_id.is_some_and(|next| id >= next) {
+            *next_id = id.checked_add(1);
+        }
+        drop(next_id);
+        let event = self.changes.publish(op, id, entry);
+        let protected = !self.settings.api_keys.is_empty();
+        self.webhooks.notify(&self.name, protected, &event);
+    }
+
+    // Confere o tamanho do registro antes de gravá-lo
+    pub fn check_size(&self, entry: &DataEntry) -> tide::Result<()> {
+        match self.settings.max_entry_bytes {
+            Some(max) if entry.bytecode.len() > max => Err(tide::Error::from_str(
+                StatusCode::PayloadTooLarge,
+                format!("Entry too large: máximo de {max} bytes nesta coleção"),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // Confere se cabem mais `added` registros além dos `live` atuais
+    pub fn check_capacity(&self, live: usize, added: usize) -> tide::Result<()> {
+        match self.settings.max_entries {
+            Some(max) if live + added > max => Err(tide::Error::from_str(
+                StatusCode::InsufficientStorage,
+                format!("Collection full: máximo de {max} registros"),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Coleção da requisição: o parâmetro :name da rota ou, sem ele, a "default"
+pub fn from_request(req: &Request<AppState>) -> tide::Result<Arc<Collection>> {
+    let name = req.param("name").unwrap_or(DEFAULT_COLLECTION);
+    req.state()
+        .collection(name)
+        .ok_or_else(|| tide::Error::from_str(404, "Collection not found"))
+}
+
+// Separa um caminho na coleção e no caminho equivalente sem o prefixo:
+// "/collections/x/data/1" -> ("x", "/data/1"), "/data/1" -> ("default", "/data/1").
+// Caminhos que não tratam de registros (gerência de coleções, docs) -> None.
+pub fn split_path(path: &str) -> Option<(&str, &str)> {
+    if let Some(rest) = path.strip_prefix("/collections/") {
+        let (name, rest) = rest.split_once('/')?;
+        return (!rest.is_empty()).then(|| (name, &path[path.len() - rest.len() - 1..]));
+    }
+    let scoped = path == "/data"
+        || path.starts_with("/data/")
+        || path == "/_tx"
+        || path.starts_with("/execute/");
+    scoped.then_some((DEFAULT_COLLECTION, path))
+}
+
+// Nomes aceitos: letras, números, "-" e "_", sem começar com "_"
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let valid_chars = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if name.is_empty() || name.len() > MAX_NAME_LEN || !valid_chars || name.starts_with('_') {
+        return Err(format!(
+            "Invalid collection name: use até {MAX_NAME_LEN} letras, números, \"-\" ou \"_\""
+        ));
+    }
+    Ok(())
+}
+
+// Um a mais que o maior id em uso, contando a lixeira
+// (usar `map.len() + 1` sobrescreveria registros depois de um delete)
+fn first_free_id(map: &HashMap<u32, DataEntry>, trash: &Trash) -> Option<u32> {
+    match map.keys().max().copied().max(trash.max_id()) {
+        Some(id) => id.checked_add(1),
+        None => Some(1),
+    }
+}
@@ -0,0 +1,1218 @@
+// Configuração do servidor em camadas:
+// valores padrão -> arquivo TOML -> variáveis de ambiente -> flags da linha de comando.
+// Cada camada sobrescreve apenas o que define.
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use tide::http::{Method, Url};
+use tracing_subscriber::EnvFilter;
+
+use crate::query::Field;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub auth: AuthConfig,
+    pub cors: CorsConfig,
+    pub limits: LimitsConfig,
+    pub idempotency: IdempotencyConfig,
+    pub webhooks: WebhooksConfig,
+    pub cluster: ClusterConfig,
+    pub history: HistoryConfig,
+    pub trash: TrashConfig,
+    pub indexes: IndexesConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    // Endereço em que o servidor escuta (ex: "0.0.0.0:8080")
+    pub bind: String,
+    // Tempo máximo (em segundos) esperando requisições em andamento ao desligar
+    pub shutdown_timeout_secs: u64,
+    // Tempo máximo (em segundos) para o cliente mandar a linha e os headers de
+    // uma requisição. O async-h1 já fecha a conexão depois de 60s, então esse é
+    // o maior valor aceito.
+    pub header_timeout_secs: u64,
+    // Requisições atendidas ao mesmo tempo; acima disso a resposta é 503.
+    // 0 desliga o limite.
+    pub max_in_flight: usize,
+    // HTTPS (ver src/tls.rs)
+    pub tls: TlsConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: "0.0.0.0:8080".to_string(),
+            shutdown_timeout_secs: 30,
+            header_timeout_secs: 30,
+            max_in_flight: 0,
+            tls: TlsConfig::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    // Cadeia de certificados e chave privada em PEM. Sem os dois, o servidor
+    // fala HTTP puro.
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+    // CAs (PEM) dos certificados de cliente: liga o TLS mútuo
+    pub client_ca_path: Option<PathBuf>,
+    // Com `client_ca_path`, recusa clientes sem certificado
+    // (false: o certificado só é validado quando o cliente manda um)
+    pub require_client_cert: bool,
+    // Intervalo (em segundos) entre as verificações dos arquivos; quando algum
+    // muda, os certificados são recarregados sem reiniciar. 0 desliga.
+    pub reload_interval_secs: u64,
+    // Endereço de um listener HTTP que redireciona tudo para o HTTPS
+    pub redirect_bind: Option<String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            cert_path: None,
+            key_path: None,
+            client_ca_path: None,
+            require_client_cert: true,
+            reload_interval_secs: 10,
+            redirect_bind: None,
+        }
+    }
+}
+
+impl TlsConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.cert_path.is_some()
+    }
+}
+
+#[derive(Serialize, Deserialize, ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    // Dados apenas em memória (perdidos ao reiniciar)
+    #[default]
+    Memory,
+    // Dados salvos em um arquivo no disco
+    File,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    // Caminho do arquivo de dados (obrigatório para o backend "file")
+    pub path: Option<PathBuf>,
+    // Write-ahead log das escritas entre um snapshot e outro (ver src/wal.rs)
+    pub wal: WalConfig,
+}
+
+impl StorageConfig {
+    // Arquivo do WAL, quando ele está ligado (backend "file")
+    pub fn wal_path(&self) -> Option<PathBuf> {
+        match (self.backend, &self.path) {
+            (StorageBackend::File, Some(path)) if self.wal.enabled => {
+                Some(self.wal.path.clone().unwrap_or_else(|| {
+                    let mut wal = path.as_os_str().to_owned();
+                    wal.push(".wal");
+                    PathBuf::from(wal)
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct WalConfig {
+    // Grava cada mudança do estado antes de responder (backend "file")
+    pub enabled: bool,
+    // Arquivo do log (padrão: o de storage.path com ".wal" no fim)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    // Quando os registros são forçados ao disco (fsync)
+    pub fsync: FsyncPolicy,
+    // Intervalo (em milissegundos) entre os fsyncs com a política "interval"
+    pub fsync_interval_ms: u64,
+    // Salva um snapshot (checkpoint) e esvazia o log a cada N segundos. 0 desliga.
+    pub checkpoint_interval_secs: u64,
+    // ... ou quando o log passa de N registros. 0 desliga.
+    pub checkpoint_records: u64,
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        WalConfig {
+            enabled: true,
+            path: None,
+            fsync: FsyncPolicy::default(),
+            fsync_interval_ms: 1000,
+            checkpoint_interval_secs: 300,
+            checkpoint_records: 10000,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncPolicy {
+    // A cada registro, antes de responder: nada confirmado se perde
+    #[default]
+    Always,
+    // A cada `fsync_interval_ms`: uma queda da máquina perde no máximo esse intervalo
+    Interval,
+    // Fica com o sistema operacional; só uma queda do processo é garantida
+    Never,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    // API keys aceitas no header X-Api-Key. Lista vazia desliga a autenticação.
+    pub api_keys: Vec<String>,
+    // Caminhos que não exigem API key
+    pub public_paths: Vec<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            api_keys: Vec::new(),
+            public_paths: vec!["/docs".to_string(), "/openapi.json".to_string()],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    // Origens aceitas (ex: "https://app.exemplo.com"); "*" aceita qualquer uma.
+    // Lista vazia desliga o CORS.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    // Headers que o navegador pode mandar; "*" aceita qualquer um
+    pub allowed_headers: Vec<String>,
+    // Headers da resposta que o JavaScript da página pode ler
+    pub exposed_headers: Vec<String>,
+    // Aceita cookies e autenticação do navegador (não combina com a origem "*")
+    pub allow_credentials: bool,
+    // Segundos que o navegador guarda a resposta do preflight
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        let strings = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+        CorsConfig {
+            allowed_origins: Vec::new(),
+            allowed_methods: strings(&["GET", "POST", "PUT", "DELETE"]),
+            allowed_headers: strings(&[
+                "Accept",
+                "Content-Type",
+                "Idempotency-Key",
+                "Last-Event-ID",
+                "X-Api-Key",
+                "X-Request-Id",
+            ]),
+            exposed_headers: strings(&[
+                "Idempotent-Replayed",
+                "Location",
+                "Retry-After",
+                "RateLimit-Limit",
+                "RateLimit-Remaining",
+                "RateLimit-Reset",
+                "X-Quota-Limit",
+                "X-Quota-Remaining",
+                "X-Request-Id",
+                "X-Revision",
+            ]),
+            allow_credentials: false,
+            max_age_secs: 600,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    // Limites de taxa por rota (ver src/middleware/rate_limit.rs)
+    pub rate_limits: Vec<RouteLimitConfig>,
+    // Tamanho máximo (em bytes) do body das requisições
+    pub max_body_bytes: u64,
+    // Tempo máximo (em segundos) para receber o body. 0 desliga o limite.
+    pub body_timeout_secs: u64,
+    // Tempo máximo (em segundos) para o handler responder. 0 desliga o limite.
+    pub handler_timeout_secs: u64,
+    // Valores próprios de algumas rotas (ver src/middleware/request_limits.rs)
+    pub request_limits: Vec<RequestLimitConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RouteLimitConfig {
+    pub method: String,
+    pub path: String,
+    pub capacity: u32,
+    pub refill_per_sec: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_quota: Option<u32>,
+}
+
+impl RouteLimitConfig {
+    fn new(method: &str, path: &str, capacity: u32, refill_per_sec: f64) -> Self {
+        RouteLimitConfig {
+            method: method.to_string(),
+            path: path.to_string(),
+            capacity,
+            refill_per_sec,
+            daily_quota: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RequestLimitConfig {
+    pub method: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_body_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+impl RequestLimitConfig {
+    fn new(method: &str, path: &str, max_body_bytes: u64, timeout_secs: u64) -> Self {
+        RequestLimitConfig {
+            method: method.to_string(),
+            path: path.to_string(),
+            max_body_bytes: Some(max_body_bytes),
+            timeout_secs: Some(timeout_secs),
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            rate_limits: vec![
+                RouteLimitConfig::new("POST", "/data", 20, 5.0),
+                RouteLimitConfig::new("GET", "/data", 100, 50.0),
+                RouteLimitConfig::new("POST", "/data/_bulk", 5, 1.0),
+                RouteLimitConfig::new("GET", "/data/_changes", 10, 1.0),
+                RouteLimitConfig::new("GET", "/data/_trash", 100, 50.0),
+                RouteLimitConfig::new("GET", "/data/_export", 5, 0.5),
+                RouteLimitConfig::new("POST", "/data/_import", 5, 0.5),
+                RouteLimitConfig::new("GET", "/data/_search", 50, 20.0),
+                RouteLimitConfig::new("GET", "/data/_query", 50, 20.0),
+                RouteLimitConfig::new("GET", "/data/:id", 100, 50.0),
+                RouteLimitConfig::new("PUT", "/data/:id", 20, 5.0),
+                RouteLimitConfig::new("DELETE", "/data/:id", 20, 5.0),
+                RouteLimitConfig::new("GET", "/data/:id/history", 100, 50.0),
+                RouteLimitConfig::new("POST", "/data/:id/revert", 20, 5.0),
+                RouteLimitConfig::new("POST", "/data/:id/restore", 20, 5.0),
+                RouteLimitConfig::new("POST", "/_tx", 10, 2.0),
+                RouteLimitConfig::new("POST", "/collections", 5, 0.5),
+                RouteLimitConfig::new("GET", "/collections", 100, 50.0),
+                RouteLimitConfig::new("DELETE", "/collections/:name", 5, 0.5),
+                RouteLimitConfig {
+                    daily_quota: Some(1000),
+                    ..RouteLimitConfig::new("POST", "/execute/:id", 10, 2.0)
+                },
+            ],
+            max_body_bytes: 1024 * 1024,
+            body_timeout_secs: 30,
+            handler_timeout_secs: 30,
+            request_limits: vec![
+                RequestLimitConfig::new("POST", "/data/_bulk", 16 * 1024 * 1024, 60),
+                RequestLimitConfig::new("POST", "/data/_import", 64 * 1024 * 1024, 120),
+                RequestLimitConfig::new("POST", "/_tx", 4 * 1024 * 1024, 30),
+                RequestLimitConfig::new("POST", "/execute/:id", 4096, 5),
+            ],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdempotencyConfig {
+    // Rotas POST que aceitam o header Idempotency-Key (ver src/middleware/idempotency.rs)
+    pub paths: Vec<String>,
+    // Tempo (em segundos) que a primeira resposta de cada chave fica guardada
+    pub ttl_secs: u64,
+    // Quantidade máxima de chaves guardadas; acima dela, as mais antigas saem
+    pub max_keys: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        IdempotencyConfig {
+            paths: vec!["/data".to_string(), "/execute/:id".to_string()],
+            ttl_secs: 24 * 60 * 60,
+            max_keys: 10_000,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhooksConfig {
+    // Tentativas de entrega de cada evento, contando a primeira; depois disso o
+    // evento vai para a lista de falhas (dead letters)
+    pub max_attempts: u32,
+    // Espera (em milissegundos) antes da segunda tentativa; dobra a cada nova falha
+    pub initial_backoff_ms: u64,
+    // Maior espera (em milissegundos) entre duas tentativas
+    pub max_backoff_ms: u64,
+    // Tempo máximo (em segundos) de cada tentativa, da conexão à resposta
+    pub timeout_secs: u64,
+    // Quantidade máxima de assinaturas
+    pub max_subscriptions: usize,
+    // Tentativas guardadas no log de entregas de cada assinatura
+    pub log_size: usize,
+    // Eventos guardados na lista de falhas; acima disso, os mais antigos saem
+    pub dead_letter_size: usize,
+    // Certificados de CA (PEM) aceitos nas URLs https://, além das CAs públicas
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_path: Option<PathBuf>,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        WebhooksConfig {
+            max_attempts: 6,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 60_000,
+            timeout_secs: 10,
+            max_subscriptions: 100,
+            log_size: 100,
+            dead_letter_size: 1000,
+            ca_path: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClusterConfig {
+    // Id deste nó na lista `peers`. Sem ele o servidor roda sozinho, sem Raft.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<u64>,
+    // Diretório com o log, o estado e o snapshot do Raft; cada nó usa o
+    // subdiretório node-<id>
+    pub data_dir: PathBuf,
+    // Segredo exigido nas RPCs entre os nós (header X-Raft-Secret). Vazio: sem verificação.
+    pub secret: String,
+    // Intervalo (em milissegundos) entre os heartbeats do líder
+    pub heartbeat_interval_ms: u64,
+    // Sem notícias do líder por um tempo sorteado nesta faixa (em milissegundos),
+    // o seguidor começa uma eleição
+    pub election_timeout_min_ms: u64,
+    pub election_timeout_max_ms: u64,
+    // Tempo máximo (em milissegundos) de cada RPC entre os nós
+    pub rpc_timeout_ms: u64,
+    // Entradas do log enviadas por AppendEntries
+    pub max_entries_per_append: usize,
+    // Entradas aplicadas desde o último snapshot que disparam um novo snapshot
+    // e a compactação do log
+    pub snapshot_threshold: u64,
+    // Tempo máximo (em milissegundos) esperando uma escrita ser confirmada e aplicada
+    pub commit_timeout_ms: u64,
+    // O que um seguidor faz com escritas e leituras linearizáveis
+    pub follower_writes: FollowerWrites,
+    // Consistência das leituras sem o header X-Read-Consistency
+    pub read_consistency: ReadConsistency,
+    // Rotas sempre atendidas pelo próprio nó, sem passar pelo log (ex: feeds,
+    // execução de funções, documentação e o status do cluster)
+    pub local_paths: Vec<String>,
+    // Todos os nós do cluster, inclusive este
+    pub peers: Vec<PeerConfig>,
+}
+
+impl ClusterConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.node_id.is_some()
+    }
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        ClusterConfig {
+            node_id: None,
+            data_dir: PathBuf::from("raft"),
+            secret: String::new(),
+            heartbeat_interval_ms: 100,
+            election_timeout_min_ms: 500,
+            election_timeout_max_ms: 1000,
+            rpc_timeout_ms: 1000,
+            max_entries_per_append: 256,
+            snapshot_threshold: 1000,
+            commit_timeout_ms: 5000,
+            follower_writes: FollowerWrites::default(),
+            read_consistency: ReadConsistency::default(),
+            local_paths: [
+                "/_cluster",
+                "/data/_changes",
+                "/execute/:id",
+                "/metrics",
+                "/openapi.json",
+                "/docs",
+            ]
+            .map(String::from)
+            .to_vec(),
+            peers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PeerConfig {
+    pub id: u64,
+    // URL das RPCs do Raft (http://); o nó escuta no host:porta da própria
+    pub peer_url: String,
+    // URL da API, para onde os seguidores encaminham ou redirecionam os clientes
+    pub api_url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FollowerWrites {
+    // O seguidor repassa a requisição ao líder e devolve a resposta dele
+    #[default]
+    Forward,
+    // O seguidor responde 307 com o endereço do líder
+    Redirect,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadConsistency {
+    // Lê no líder depois de confirmar a liderança com a maioria (ReadIndex)
+    #[default]
+    Linearizable,
+    // Lê no próprio nó, que pode estar atrasado
+    Stale,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    // Quantidade máxima de revisões guardadas por registro (inclui a atual)
+    pub max_revisions: usize,
+    // Idade máxima (em segundos) das revisões antigas. 0 desliga o limite por idade.
+    pub max_age_secs: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            max_revisions: 100,
+            max_age_secs: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashConfig {
+    // Tempo (em segundos) que um registro removido fica restaurável
+    pub retention_secs: u64,
+    // Intervalo (em segundos) entre as limpezas da lixeira (no cluster, a limpeza
+    // acompanha as escritas replicadas)
+    pub purge_interval_secs: u64,
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        TrashConfig {
+            retention_secs: 7 * 24 * 60 * 60,
+            purge_interval_secs: 60,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexesConfig {
+    // Campos com índice secundário em todas as coleções (ver src/indexes.rs):
+    // "func_names", "size" e/ou "hash"
+    pub fields: Vec<String>,
+}
+
+impl IndexesConfig {
+    // Campos válidos da lista (os inválidos são barrados em Config::validate)
+    pub fn fields(&self) -> Vec<Field> {
+        self.fields.iter().filter_map(|f| Field::parse(f)).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    // Filtro no formato do tracing (ex: "info" ou "debug,tide=warn")
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "info".to_string(),
+            format: LogFormat::Text,
+        }
+    }
+}
+
+// Flags da linha de comando. Cada flag também pode vir de uma variável de ambiente.
+#[derive(Parser, Debug)]
+#[command(version, about = "Servidor CRUD-E")]
+pub struct Cli {
+    /// Arquivo de configuração TOML
+    #[arg(long, env = "CRUD_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Endereço em que o servidor escuta
+    #[arg(long, env = "CRUD_BIND")]
+    pub bind: Option<String>,
+
+    /// Segundos esperando requisições em andamento ao desligar
+    #[arg(long, env = "CRUD_SHUTDOWN_TIMEOUT")]
+    pub shutdown_timeout: Option<u64>,
+
+    /// Segundos para o cliente mandar os headers de uma requisição
+    #[arg(long, env = "CRUD_HEADER_TIMEOUT")]
+    pub header_timeout: Option<u64>,
+
+    /// Requisições atendidas ao mesmo tempo (0 = sem limite)
+    #[arg(long, env = "CRUD_MAX_IN_FLIGHT")]
+    pub max_in_flight: Option<usize>,
+
+    /// Tamanho máximo do body das requisições, em bytes
+    #[arg(long, env = "CRUD_MAX_BODY_BYTES")]
+    pub max_body_bytes: Option<u64>,
+
+    /// Certificado TLS (PEM); liga o HTTPS junto com --tls-key
+    #[arg(long, env = "CRUD_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// Chave privada TLS (PEM)
+    #[arg(long, env = "CRUD_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Endereço do listener HTTP que redireciona para o HTTPS
+    #[arg(long, env = "CRUD_TLS_REDIRECT_BIND")]
+    pub tls_redirect_bind: Option<String>,
+
+    /// Backend de armazenamento
+    #[arg(long, env = "CRUD_STORAGE_BACKEND")]
+    pub storage_backend: Option<StorageBackend>,
+
+    /// Arquivo de dados do backend "file"
+    #[arg(long, env = "CRUD_STORAGE_PATH")]
+    pub storage_path: Option<PathBuf>,
+
+    /// Quando o WAL do backend "file" força os registros ao disco
+    #[arg(long, env = "CRUD_WAL_FSYNC")]
+    pub wal_fsync: Option<FsyncPolicy>,
+
+    /// API key aceita (pode repetir a flag; no ambiente, separe por vírgula)
+    #[arg(long = "api-key", env = "CRUD_API_KEYS", value_delimiter = ',')]
+    pub api_keys: Vec<String>,
+
+    /// Origem aceita pelo CORS (pode repetir a flag; no ambiente, separe por vírgula)
+    #[arg(long = "cors-origin", env = "CRUD_CORS_ORIGINS", value_delimiter = ',')]
+    pub cors_origins: Vec<String>,
+
+    /// Id deste nó no cluster ([cluster] peers); liga o modo cluster
+    #[arg(long, env = "CRUD_NODE_ID")]
+    pub node_id: Option<u64>,
+
+    /// Filtro de nível dos logs
+    #[arg(long, env = "CRUD_LOG_LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Formato dos logs
+    #[arg(long, env = "CRUD_LOG_FORMAT")]
+    pub log_format: Option<LogFormat>,
+
+    /// Mostra a configuração final e sai
+    #[arg(long)]
+    pub print_config: bool,
+}
+
+impl Config {
+    // Monta a configuração final a partir das flags (e variáveis de ambiente)
+    pub fn load(cli: &Cli) -> Result<Config, String> {
+        let mut config = match &cli.config {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+        config.apply_cli(cli);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &PathBuf) -> Result<Config, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("não foi possível ler {}: {e}", path.display()))?;
+        toml::from_str(&text).map_err(|e| format!("erro em {}: {e}", path.display()))
+    }
+
+    fn apply_cli(&mut self, cli: &Cli) {
+        if let Some(bind) = &cli.bind {
+            self.server.bind = bind.clone();
+        }
+        if let Some(timeout) = cli.shutdown_timeout {
+            self.server.shutdown_timeout_secs = timeout;
+        }
+        if let Some(timeout) = cli.header_timeout {
+            self.server.header_timeout_secs = timeout;
+        }
+        if let Some(max) = cli.max_in_flight {
+            self.server.max_in_flight = max;
+        }
+        if let Some(max) = cli.max_body_bytes {
+            self.limits.max_body_bytes = max;
+        }
+        if let Some(path) = &cli.tls_cert {
+            self.server.tls.cert_path = Some(path.clone());
+        }
+        if let Some(path) = &cli.tls_key {
+            self.server.tls.key_path = Some(path.clone());
+        }
+        if let Some(bind) = &cli.tls_redirect_bind {
+            self.server.tls.redirect_bind = Some(bind.clone());
+        }
+        if let Some(backend) = cli.storage_backend {
+            self.storage.backend = backend;
+        }
+        if let Some(path) = &cli.storage_path {
+            self.storage.path = Some(path.clone());
+        }
+        if let Some(fsync) = cli.wal_fsync {
+            self.storage.wal.fsync = fsync;
+        }
+        if !cli.api_keys.is_empty() {
+            self.auth.api_keys = cli.api_keys.clone();
+        }
+        if !cli.cors_origins.is_empty() {
+            self.cors.allowed_origins = cli.cors_origins.clone();
+        }
+        if let Some(id) = cli.node_id {
+            self.cluster.node_id = Some(id);
+        }
+        if let Some(level) = &cli.log_level {
+            self.log.level = level.clone();
+        }
+        if let Some(format) = cli.log_format {
+            self.log.format = format;
+        }
+    }
+
+    // Confere a configuração e devolve todos os problemas encontrados de uma vez
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        if self.server.bind.parse::<SocketAddr>().is_err() {
+            errors.push(format!("server.bind inválido: {:?}", self.server.bind));
+        }
+        if !(1..=60).contains(&self.server.header_timeout_secs) {
+            errors.push("server.header_timeout_secs deve estar entre 1 e 60".to_string());
+        }
+
+        let tls = &self.server.tls;
+        if tls.cert_path.is_some() != tls.key_path.is_some() {
+            errors.push("server.tls.cert_path e server.tls.key_path vêm juntos".to_string());
+        }
+        if !tls.is_enabled() && (tls.client_ca_path.is_some() || tls.redirect_bind.is_some()) {
+            errors.push(
+                "server.tls.client_ca_path e server.tls.redirect_bind exigem cert_path".to_string(),
+            );
+        }
+        if let Some(bind) = &tls.redirect_bind
+            && bind.parse::<SocketAddr>().is_err()
+        {
+            errors.push(format!("server.tls.redirect_bind inválido: {bind:?}"));
+        }
+
+        if self.storage.backend == StorageBackend::File && self.storage.path.is_none() {
+            errors.push("storage.path é obrigatório com o backend \"file\"".to_string());
+        }
+        if self.storage.wal.fsync == FsyncPolicy::Interval
+            && self.storage.wal.fsync_interval_ms == 0
+        {
+            errors.push("storage.wal.fsync_interval_ms deve ser maior que zero".to_string());
+        }
+
+        if self.auth.api_keys.iter().any(|key| key.trim().is_empty()) {
+            errors.push("auth.api_keys não pode ter chaves vazias".to_string());
+        }
+        for path in &self.auth.public_paths {
+            if !path.starts_with('/') {
+                errors.push(format!("auth.public_paths: {path:?} deve começar com '/'"));
+            }
+        }
+
+        for origin in &self.cors.allowed_origins {
+            let valid = origin == "*"
+                || ((origin.starts_with("http://") || origin.starts_with("https://"))
+                    && !origin.ends_with('/'));
+            if !valid {
+                errors.push(format!(
+                    "cors.allowed_origins: {origin:?} deve ser \"*\" ou esquema://host[:porta]"
+                ));
+            }
+        }
+        if self.cors.allow_credentials && self.cors.allowed_origins.iter().any(|o| o == "*") {
+            errors.push("cors.allow_credentials não pode ser usado com a origem \"*\"".to_string());
+        }
+        for method in &self.cors.allowed_methods {
+            if Method::from_str(method).is_err() {
+                errors.push(format!("cors.allowed_methods: método inválido {method:?}"));
+            }
+        }
+
+        for (i, limit) in self.limits.rate_limits.iter().enumerate() {
+            let prefix = format!("limits.rate_limits[{i}]");
+            if Method::from_str(&limit.method).is_err() {
+                errors.push(format!("{prefix}.method inválido: {:?}", limit.method));
+            }
+            if !limit.path.starts_with('/') {
+                errors.push(format!("{prefix}.path deve começar com '/'"));
+            }
+            if limit.capacity == 0 {
+                errors.push(format!("{prefix}.capacity deve ser maior que zero"));
+            }
+            if !limit.refill_per_sec.is_finite() || limit.refill_per_sec < 0.0 {
+                errors.push(format!("{prefix}.refill_per_sec deve ser >= 0"));
+            }
+        }
+        if self.limits.max_body_bytes == 0 {
+            errors.push("limits.max_body_bytes deve ser maior que zero".to_string());
+        }
+        for (i, limit) in self.limits.request_limits.iter().enumerate() {
+            let prefix = format!("limits.request_limits[{i}]");
+            if Method::from_str(&limit.method).is_err() {
+                errors.push(format!("{prefix}.method inválido: {:?}", limit.method));
+            }
+            if !limit.path.starts_with('/') {
+                errors.push(format!("{prefix}.path deve começar com '/'"));
+            }
+            if limit.max_body_bytes == Some(0) {
+                errors.push(format!("{prefix}.max_body_bytes deve ser maior que zero"));
+            }
+        }
+
+        for path in &self.idempotency.paths {
+            if !path.starts_with('/') {
+                errors.push(format!("idempotency.paths: {path:?} deve começar com '/'"));
+            }
+        }
+        if self.idempotency.ttl_secs == 0 || self.idempotency.max_keys == 0 {
+            errors.push(
+                "idempotency.ttl_secs e idempotency.max_keys devem ser maiores que zero"
+                    .to_string(),
+            );
+        }
+
+        let webhooks = &self.webhooks;
+        if webhooks.max_attempts == 0 {
+            errors.push("webhooks.max_attempts deve ser maior que zero".to_string());
+        }
+        if webhooks.initial_backoff_ms == 0 || webhooks.max_backoff_ms < webhooks.initial_backoff_ms
+        {
+            errors.push(
+                "webhooks.initial_backoff_ms deve ser maior que zero e até webhooks.max_backoff_ms"
+                    .to_string(),
+            );
+        }
+        if webhooks.timeout_secs == 0 {
+            errors.push("webhooks.timeout_secs deve ser maior que zero".to_string());
+        }
+        if webhooks.log_size == 0 || webhooks.dead_letter_size == 0 {
+            errors.push(
+                "webhooks.log_size e webhooks.dead_letter_size devem ser maiores que zero"
+                    .to_string(),
+            );
+        }
+        if let Some(path) = &webhooks.ca_path
+            && !path.exists()
+        {
+            errors.push(format!("webhooks.ca_path não existe: {}", path.display()));
+        }
+
+        let cluster = &self.cluster;
+        if let Some(id) = cluster.node_id {
+            if !cluster.peers.iter().any(|peer| peer.id == id) {
+                errors.push(format!("cluster.node_id {id} não está em cluster.peers"));
+            }
+            if self.storage.backend != StorageBackend::Memory {
+                errors.push(
+                    "cluster: use storage.backend = \"memory\"; os dados ficam em cluster.data_dir"
+                        .to_string(),
+                );
+            }
+        }
+        for (i, peer) in cluster.peers.iter().enumerate() {
+            let prefix = format!("cluster.peers[{i}]");
+            if cluster.peers[..i].iter().any(|other| other.id == peer.id) {
+                errors.push(format!("{prefix}.id repetido: {}", peer.id));
+            }
+            if !Url::parse(&peer.peer_url).is_ok_and(|url| {
+                url.scheme() == "http"
+                    && url.port_or_known_default().is_some()
+                    && url.host().is_some()
+            }) {
+                errors.push(format!(
+                    "{prefix}.peer_url deve ser uma URL http:// com host"
+                ));
+            }
+            if !Url::parse(&peer.api_url)
+                .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())
+            {
+                errors.push(format!(
+                    "{prefix}.api_url deve ser uma URL http:// ou https://"
+                ));
+            }
+        }
+        if cluster.heartbeat_interval_ms == 0
+            || cluster.election_timeout_min_ms <= cluster.heartbeat_interval_ms
+            || cluster.election_timeout_max_ms < cluster.election_timeout_min_ms
+        {
+            errors.push(
+                "cluster: heartbeat_interval_ms > 0 e heartbeat_interval_ms < election_timeout_min_ms <= election_timeout_max_ms"
+                    .to_string(),
+            );
+        }
+        if cluster.rpc_timeout_ms == 0
+            || cluster.commit_timeout_ms == 0
+            || cluster.max_entries_per_append == 0
+            || cluster.snapshot_threshold == 0
+        {
+            errors.push(
+                "cluster.rpc_timeout_ms, commit_timeout_ms, max_entries_per_append e snapshot_threshold devem ser maiores que zero"
+                    .to_string(),
+            );
+        }
+        for path in &cluster.local_paths {
+            if !path.starts_with('/') {
+                errors.push(format!(
+                    "cluster.local_paths: {path:?} deve começar com '/'"
+                ));
+            }
+        }
+
+        if self.history.max_revisions == 0 {
+            errors.push("history.max_revisions deve ser maior que zero".to_string());
+        }
+
+        if self.trash.purge_interval_secs == 0 {
+            errors.push("trash.purge_interval_secs deve ser maior que zero".to_string());
+        }
+
+        for field in &self.indexes.fields {
+            if !Field::parse(field).is_some_and(|f| Field::INDEXABLE.contains(&f)) {
+                errors.push(format!(
+                    "indexes.fields: {field:?} inválido (use func_names, size ou hash)"
+                ));
+            }
+        }
+
+        if EnvFilter::try_new(&self.log.level).is_err() {
+            errors.push(format!("log.level inválido: {:?}", self.log.level));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "configuração inválida:\n  - {}",
+                errors.join("\n  - ")
+            ))
+        }
+    }
+
+    // Configuração em TOML, com as API keys escondidas
+    pub fn to_redacted_toml(&self) -> String {
+        let mut config = self.clone();
+        for key in &mut config.auth.api_keys {
+            *key = "***".to_string();
+        }
+        if !config.cluster.secret.is_empty() {
+            config.cluster.secret = "***".to_string();
+        }
+        toml::to_string_pretty(&config).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mudança na configuração padrão e o trecho esperado na mensagem de erro
+    type Case = (fn(&mut Config), &'static str);
+
+    fn peer(id: u64) -> PeerConfig {
+        PeerConfig {
+            id,
+            peer_url: format!("http://127.0.0.1:{}", 7000 + id),
+            api_url: format!("http://127.0.0.1:{}", 8000 + id),
+        }
+    }
+
+    // Cada camada sobrescreve só o que define. Único teste que mexe nas variáveis
+    // de ambiente: os outros montam a Config sem passar pelo clap.
+    #[test]
+    fn layers_override_in_order() {
+        let path =
+            std::env::temp_dir().join(format!("crud-e-config-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"
+            [server]
+            bind = "127.0.0.1:1000"
+            shutdown_timeout_secs = 5
+            header_timeout_secs = 10
+
+            [log]
+            level = "warn"
+            "#,
+        )
+        .unwrap();
+
+        // SAFETY: nenhum outro teste lê ou altera o ambiente
+        unsafe {
+            std::env::set_var("CRUD_BIND", "127.0.0.1:2000");
+            std::env::set_var("CRUD_SHUTDOWN_TIMEOUT", "7");
+            std::env::set_var("CRUD_API_KEYS", "chave-a,chave-b");
+        }
+        let cli = Cli::try_parse_from([
+            "crud-e",
+            "--config",
+            path.to_str().unwrap(),
+            "--bind",
+            "127.0.0.1:3000",
+        ]);
+        unsafe {
+            std::env::remove_var("CRUD_BIND");
+            std::env::remove_var("CRUD_SHUTDOWN_TIMEOUT");
+            std::env::remove_var("CRUD_API_KEYS");
+        }
+        let config = Config::load(&cli.unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Flag > ambiente > TOML > padrão
+        assert_eq!(config.server.bind, "127.0.0.1:3000");
+        assert_eq!(config.server.shutdown_timeout_secs, 7);
+        assert_eq!(config.auth.api_keys, ["chave-a", "chave-b"]);
+        assert_eq!(config.server.header_timeout_secs, 10);
+        assert_eq!(config.log.level, "warn");
+        assert_eq!(config.server.max_in_flight, 0);
+        assert_eq!(config.history.max_revisions, 100);
+    }
+
+    #[test]
+    fn rejects_unknown_toml_keys() {
+        let err = toml::from_str::<Config>("[server]\nport = 8080\n").unwrap_err();
+        assert!(err.to_string().contains("port"));
+    }
+
+    #[test]
+    fn reports_every_invalid_setting() {
+        assert!(Config::default().validate().is_ok());
+
+        let cases: Vec<Case> = vec![
+            (
+                |c| c.server.bind = "localhost".into(),
+                "server.bind inválido",
+            ),
+            (
+                |c| c.server.header_timeout_secs = 61,
+                "server.header_timeout_secs",
+            ),
+            (
+                |c| c.server.tls.cert_path = Some("cert.pem".into()),
+                "cert_path e server.tls.key_path vêm juntos",
+            ),
+            (
+                |c| c.server.tls.client_ca_path = Some("ca.pem".into()),
+                "exigem cert_path",
+            ),
+            (
+                |c| c.server.tls.redirect_bind = Some("http".into()),
+                "server.tls.redirect_bind inválido",
+            ),
+            (
+                |c| c.storage.backend = StorageBackend::File,
+                "storage.path é obrigatório",
+            ),
+            (
+                |c| {
+                    c.storage.wal.fsync = FsyncPolicy::Interval;
+                    c.storage.wal.fsync_interval_ms = 0;
+                },
+                "storage.wal.fsync_interval_ms",
+            ),
+            (|c| c.auth.api_keys = vec![" ".into()], "chaves vazias"),
+            (
+                |c| c.auth.public_paths = vec!["docs".into()],
+                "auth.public_paths",
+            ),
+            (
+                |c| c.cors.allowed_origins = vec!["https://a.com/".into()],
+                "cors.allowed_origins",
+            ),
+            (
+                |c| {
+                    c.cors.allowed_origins = vec!["*".into()];
+                    c.cors.allow_credentials = true;
+                },
+                "cors.allow_credentials",
+            ),
+            (
+                |c| c.cors.allowed_methods = vec!["FETCH".into()],
+                "cors.allowed_methods",
+            ),
+            (
+                |c| c.limits.rate_limits[0].method = "FETCH".into(),
+                "limits.rate_limits[0].method",
+            ),
+            (
+                |c| c.limits.rate_limits[0].path = "data".into(),
+                "limits.rate_limits[0].path",
+            ),
+            (
+                |c| c.limits.rate_limits[0].capacity = 0,
+                "limits.rate_limits[0].capacity",
+            ),
+            (
+                |c| c.limits.rate_limits[0].refill_per_sec = -1.0,
+                "limits.rate_limits[0].refill_per_sec",
+            ),
+            (|c| c.limits.max_body_bytes = 0, "limits.max_body_bytes"),
+            (
+                |c| c.limits.request_limits[0].method = "FETCH".into(),
+                "limits.request_limits[0].method",
+            ),
+            (
+                |c| c.limits.request_limits[0].path = "data".into(),
+                "limits.request_limits[0].path",
+            ),
+            (
+                |c| c.limits.request_limits[0].max_body_bytes = Some(0),
+                "limits.request_limits[0].max_body_bytes",
+            ),
+            (
+                |c| c.idempotency.paths = vec!["data".into()],
+                "idempotency.paths",
+            ),
+            (|c| c.idempotency.ttl_secs = 0, "idempotency.ttl_secs"),
+            (|c| c.webhooks.max_attempts = 0, "webhooks.max_attempts"),
+            (
+                |c| c.webhooks.max_backoff_ms = 0,
+                "webhooks.initial_backoff_ms",
+            ),
+            (|c| c.webhooks.timeout_secs = 0, "webhooks.timeout_secs"),
+            (|c| c.webhooks.log_size = 0, "webhooks.log_size"),
+            (
+                |c| c.webhooks.ca_path = Some("/nao/existe.pem".into()),
+                "webhooks.ca_path não existe",
+            ),
+            (
+                |c| c.cluster.node_id = Some(1),
+                "cluster.node_id 1 não está",
+            ),
+            (
+                |c| {
+                    c.cluster.node_id = Some(1);
+                    c.cluster.peers = vec![peer(1)];
+                    c.storage.backend = StorageBackend::File;
+                    c.storage.path = Some("dados.json".into());
+                },
+                "use storage.backend = \"memory\"",
+            ),
+            (
+                |c| c.cluster.peers = vec![peer(1), peer(1)],
+                "cluster.peers[1].id repetido",
+            ),
+            (
+                |c| {
+                    c.cluster.peers = vec![PeerConfig {
+                        peer_url: "https://a".into(),
+                        ..peer(1)
+                    }]
+                },
+                "cluster.peers[0].peer_url",
+            ),
+            (
+                |c| {
+                    c.cluster.peers = vec![PeerConfig {
+                        api_url: "ftp://a".into(),
+                        ..peer(1)
+                    }]
+                },
+                "cluster.peers[0].api_url",
+            ),
+            (
+                |c| c.cluster.election_timeout_min_ms = c.cluster.heartbeat_interval_ms,
+                "heartbeat_interval_ms < election_timeout_min_ms",
+            ),
+            (|c| c.cluster.rpc_timeout_ms = 0, "cluster.rpc_timeout_ms"),
+            (
+                |c| c.cluster.local_paths = vec!["status".into()],
+                "cluster.local_paths",
+            ),
+            (|c| c.history.max_revisions = 0, "history.max_revisions"),
+            (
+                |c| c.trash.purge_interval_secs = 0,
+                "trash.purge_interval_secs",
+            ),
+            (
+                |c| c.indexes.fields = vec!["bytecode".into()],
+                "indexes.fields",
+            ),
+            (
+                |c| c.log.level = "muito=alto=demais".into(),
+                "log.level inválido",
+            ),
+        ];
+        for (change, expected) in cases {
+            let mut config = Config::default();
+            change(&mut config);
+            let err = config.validate().unwrap_err();
+            assert!(err.contains(expected), "esperava {expected:?} em:\n{err}");
+        }
+    }
+
+    // Todos os problemas aparecem juntos, um por linha
+    #[test]
+    fn lists_all_errors_at_once() {
+        let mut config = Config::default();
+        config.server.bind = "localhost".into();
+        config.history.max_revisions = 0;
+        config.trash.purge_interval_secs = 0;
+        let err = config.validate().unwrap_err();
+        assert_eq!(err.lines().count(), 4);
+        assert!(err.starts_with("configuração inválida:"));
+    }
+}
@@ -0,0 +1,13 @@
+use crate::state::AppState;
+use tide::Request;
+
+// Papel, mandato, líder e progresso do log deste nó (ver src/raft)
+pub async fn cluster_status(req: Request<AppState>) -> tide::Result {
+    let Some(node) = req.state().cluster() else {
+        return Err(tide::Error::from_str(
+            404,
+            "Cluster disabled: o servidor roda sem [cluster]",
+        ));
+    };
+    Ok(tide::Body::from_json(&node.status())?.into())
+}
@@ -1,21 +1,43 @@
+use crate::changes::ChangeKind;
+use crate::codec;
+use crate::collections;
+use crate::middleware::request_log;
 use crate::models::DataEntry;
-use crate::state::AppState;
+use crate::state::{self, AppState};
+use crate::wal::Op;
 use tide::Request;
 
 pub async fn create_data(mut req: Request<AppState>) -> tide::Result {
-    // Lê o corpo da requisição como JSON
-    let entry: DataEntry = req.body_json().await?;
+    // Lê o corpo da requisição (JSON, CBOR ou MessagePack, ver src/codec.rs)
+    let entry: DataEntry = codec::read_body(&mut req).await?;
 
-    // Pega o estado global (HashMap protegido por Mutex)
-    let state = req.state();
-    let mut map = state.lock().unwrap();
+    // Pega a coleção da requisição (registros atrás de uma trava de escrita)
+    let collection = collections::from_request(&req)?;
+    let mut map = collection.data.write().await;
 
-    // Gera um novo id simples
-    let new_id = map.len() as u32 + 1;
+    // Confere os limites da coleção
+    collection.check_size(&entry)?;
+    collection.check_capacity(map.len(), 1)?;
 
-    // Insere o novo registro
+    // Gera um novo id
+    let new_id = collection.next_id().ok_or_else(state::ids_exhausted)?;
+
+    // Enfileira no WAL na mesma ordem em que muda a memória (sem WAL, não faz nada)
+    let pending = req
+        .state()
+        .log_ops(&collection, vec![Op::put(new_id, entry.clone())])?;
+
+    // Insere o novo registro, guarda a revisão 1 e avisa os assinantes do feed
+    collection
+        .history
+        .write()
+        .record(new_id, entry.clone(), None);
+    collection.publish(ChangeKind::Create, new_id, Some(entry.clone()));
     map.insert(new_id, entry);
+    drop(map);
+    request_log::record_id(new_id);
+    pending.durable().await?;
 
-    // Retorna o id criado como JSON
-    Ok(tide::Body::from_json(&serde_json::json!({ "id": new_id }))?.into())
+    // Retorna o id criado no formato pedido pelo Accept
+    codec::response(&req, &serde_json::json!({ "id": new_id }))
 }
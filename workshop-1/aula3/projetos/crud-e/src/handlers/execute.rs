@@ -1,8 +1,24 @@
+use crate::collections;
+use crate::metrics::Metrics;
+use crate::middleware::request_limits::Deadline;
+use crate::middleware::request_log;
 use crate::state::AppState;
+use async_std::task;
 use serde::Deserialize;
 use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tide::{Request, Response, StatusCode};
-use wasmi::{Engine, Instance, Module, Store, TypedFunc};
+use wasmi::core::TrapCode;
+use wasmi::{Config, Engine, Instance, Module, Store, TypedFunc};
+
+// Combustível (instruções wasm, aproximadamente) por segundo de prazo. Fica bem
+// abaixo do que o wasmi executa por segundo, para a função parar antes de o
+// prazo da rota responder 504 e não seguir ocupando a thread depois dele.
+const FUEL_PER_SEC: u64 = 10_000_000;
+
+// Prazo considerado quando a rota não tem um (handler_timeout_secs = 0)
+const DEFAULT_BUDGET: Duration = Duration::from_secs(30);
 
 #[derive(Deserialize)]
 struct ExecRequest {
@@ -24,22 +40,65 @@ pub async fn execute_fn(mut req: Request<AppState>) -> tide::Result {
             .map_err(|_| tide::Error::from_str(400, "Invalid id"))?,
         Err(_) => return Err(tide::Error::from_str(400, "Missing id")),
     };
-    let map = req.state().lock().unwrap();
-    let entry = match map.get(&id) {
-        Some(e) => e,
+    request_log::record_id(id);
+    let collection = collections::from_request(&req)?;
+    let bytecode = match collection.data.read().await.get(&id) {
+        Some(e) => e.bytecode.clone(),
         None => return Err(tide::Error::from_str(404, "Not found")),
     };
-    let wasm_bytes = &entry.bytecode;
 
-    // Carrega e instancia o wasm (wasmi 0.47.0)
-    let engine = Engine::default();
-    let module = Module::new(&engine, wasm_bytes)
-        .map_err(|e| tide::Error::from_str(StatusCode::BadRequest, format!("Invalid wasm: {e}")))?;
+    // Executa a função fora do executor async, para que o prazo do handler
+    // (ver src/middleware/request_limits.rs) consiga responder 504 a tempo,
+    // com combustível só para o que resta do prazo, e registra a execução nas
+    // métricas
+    let started = Instant::now();
+    let budget = req
+        .ext::<Deadline>()
+        .map_or(DEFAULT_BUDGET, |Deadline(deadline)| {
+            deadline.saturating_duration_since(started)
+        });
+    let fuel = (budget.as_secs_f64() * FUEL_PER_SEC as f64) as u64;
+    let outcome = task::spawn_blocking(move || run_wasm(&bytecode, &exec_req, fuel)).await;
+    if let Some(metrics) = req.ext::<Arc<Metrics>>() {
+        let failure = outcome.as_ref().err().map(|(reason, _)| *reason);
+        metrics.observe_execution(collection.name(), id, started.elapsed(), failure);
+    }
+    let result = outcome.map_err(|(_, err)| err)?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .body(serde_json::to_string(&json!({ "result": result }))?)
+        .content_type(tide::http::mime::JSON)
+        .build())
+}
+
+// Carrega o módulo, instancia e chama a função pedida com `fuel` de combustível.
+// Em caso de erro, devolve também o motivo usado como label nas métricas.
+fn run_wasm(
+    wasm_bytes: &[u8],
+    exec_req: &ExecRequest,
+    fuel: u64,
+) -> Result<i32, (&'static str, tide::Error)> {
+    // Carrega e instancia o wasm (wasmi 0.47.0), contando o combustível gasto
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, wasm_bytes).map_err(|e| {
+        (
+            "invalid_module",
+            tide::Error::from_str(StatusCode::BadRequest, format!("Invalid wasm: {e}")),
+        )
+    })?;
     let mut store = Store::new(&engine, ());
+    store
+        .set_fuel(fuel)
+        .expect("consumo de combustível ligado no Config");
     let instance = Instance::new(&mut store, &module, &[]).map_err(|e| {
-        tide::Error::from_str(
-            StatusCode::InternalServerError,
-            format!("Wasm instantiation error: {e}"),
+        (
+            "instantiation",
+            tide::Error::from_str(
+                StatusCode::InternalServerError,
+                format!("Wasm instantiation error: {e}"),
+            ),
         )
     })?;
 
@@ -47,24 +106,37 @@ pub async fn execute_fn(mut req: Request<AppState>) -> tide::Result {
     let func = instance
         .get_func(&mut store, &exec_req.func)
         .ok_or_else(|| {
-            tide::Error::from_str(
-                StatusCode::BadRequest,
-                format!("Function not found: {}", exec_req.func),
+            (
+                "function_not_found",
+                tide::Error::from_str(
+                    StatusCode::BadRequest,
+                    format!("Function not found: {}", exec_req.func),
+                ),
             )
         })?;
     let typed: TypedFunc<(i32, i32), i32> = func.typed(&store).map_err(|e| {
-        tide::Error::from_str(StatusCode::BadRequest, format!("Signature error: {e}"))
+        (
+            "signature",
+            tide::Error::from_str(StatusCode::BadRequest, format!("Signature error: {e}")),
+        )
     })?;
 
-    // Executa a função
-    let result = typed
+    // Executa a função; sem combustível ela para com um trap
+    typed
         .call(&mut store, (exec_req.arg[0], exec_req.arg[1]))
         .map_err(|e| {
-            tide::Error::from_str(StatusCode::InternalServerError, format!("Call error: {e}"))
-        })?;
-
-    Ok(Response::builder(StatusCode::Ok)
-        .body(serde_json::to_string(&json!({ "result": result }))?)
-        .content_type(tide::http::mime::JSON)
-        .build())
+            if e.as_trap_code() == Some(TrapCode::OutOfFuel) {
+                return (
+                    "out_of_fuel",
+                    tide::Error::from_str(
+                        StatusCode::GatewayTimeout,
+                        "Execution timeout: a função esgotou o combustível do prazo da rota",
+                    ),
+                );
+            }
+            (
+                "trap",
+                tide::Error::from_str(StatusCode::InternalServerError, format!("Call error: {e}")),
+            )
+        })
 }
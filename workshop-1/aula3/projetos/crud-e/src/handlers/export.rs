@@ -0,0 +1,152 @@
+use crate::collections;
+use crate::models::DataEntry;
+use crate::state::AppState;
+use async_std::io::{BufReader, Read};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tide::http::headers::ACCEPT;
+use tide::{Body, Request, Response, StatusCode};
+
+// Uma linha do NDJSON: o id do registro junto com os campos dele.
+// É o mesmo formato aceito por POST /data/_import.
+#[derive(Serialize, Deserialize)]
+pub struct ExportRecord {
+    pub id: u32,
+    #[serde(flatten)]
+    pub entry: DataEntry,
+}
+
+#[derive(Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    Ndjson,
+    Csv,
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    format: Option<Format>,
+}
+
+pub async fn export_data(req: Request<AppState>) -> tide::Result {
+    // Formato pelo ?format=ndjson|csv ou, sem ele, pelo header Accept
+    let query: ExportQuery = req
+        .query()
+        .map_err(|_| tide::Error::from_str(400, "Invalid format: esperado ndjson ou csv"))?;
+    let wants_csv = req
+        .header(ACCEPT)
+        .is_some_and(|v| v.as_str().contains("text/csv"));
+    let format = query.format.unwrap_or(if wants_csv {
+        Format::Csv
+    } else {
+        Format::Ndjson
+    });
+
+    // Copia os registros e solta o travamento antes de mandar a resposta
+    let collection = collections::from_request(&req)?;
+    let mut records: Vec<(u32, DataEntry)> = {
+        let map = collection.data.read().await;
+        map.iter().map(|(id, entry)| (*id, entry.clone())).collect()
+    };
+    records.sort_by_key(|(id, _)| *id);
+
+    let (content_type, file_name) = match format {
+        Format::Ndjson => ("application/x-ndjson", "data.ndjson"),
+        Format::Csv => ("text/csv; charset=utf-8", "data.csv"),
+    };
+    let reader = LineReader::new(records, format);
+    Ok(Response::builder(StatusCode::Ok)
+        .content_type(content_type)
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{file_name}\""),
+        )
+        .body(Body::from_reader(BufReader::new(reader), None))
+        .build())
+}
+
+// Gera o corpo uma linha por vez conforme o cliente lê
+struct LineReader {
+    records: std::vec::IntoIter<(u32, DataEntry)>,
+    format: Format,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl LineReader {
+    fn new(records: Vec<(u32, DataEntry)>, format: Format) -> Self {
+        // O CSV começa com o cabeçalho
+        let line = match format {
+            Format::Csv => b"id,func_names,bytecode_len,bytecode_hex\n".to_vec(),
+            Format::Ndjson => Vec::new(),
+        };
+        LineReader {
+            records: records.into_iter(),
+            format,
+            line,
+            pos: 0,
+        }
+    }
+
+    fn next_line(&mut self) -> io::Result<bool> {
+        let Some((id, entry)) = self.records.next() else {
+            return Ok(false);
+        };
+        self.line.clear();
+        self.pos = 0;
+        match self.format {
+            Format::Ndjson => {
+                serde_json::to_writer(&mut self.line, &ExportRecord { id, entry })?;
+                self.line.push(b'\n');
+            }
+            Format::Csv => self.line.extend_from_slice(csv_row(id, &entry).as_bytes()),
+        }
+        Ok(true)
+    }
+}
+
+impl Read for LineReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        while this.pos == this.line.len() {
+            match this.next_line() {
+                Ok(true) => {}
+                Ok(false) => return Poll::Ready(Ok(0)),
+                Err(err) => return Poll::Ready(Err(err)),
+            }
+        }
+        let n = buf.len().min(this.line.len() - this.pos);
+        buf[..n].copy_from_slice(&this.line[this.pos..this.pos + n]);
+        this.pos += n;
+        Poll::Ready(Ok(n))
+    }
+}
+
+// Visão plana do registro: nomes separados por "|" e o bytecode em hexadecimal
+fn csv_row(id: u32, entry: &DataEntry) -> String {
+    let mut hex = String::with_capacity(entry.bytecode.len() * 2);
+    for byte in &entry.bytecode {
+        let _ = write!(hex, "{byte:02x}");
+    }
+    format!(
+        "{id},{},{},{hex}\n",
+        csv_field(&entry.func_names.join("|")),
+        entry.bytecode.len()
+    )
+}
+
+// Coloca o campo entre aspas quando necessário (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
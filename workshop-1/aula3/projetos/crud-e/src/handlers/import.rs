@@ -0,0 +1,304 @@
+use crate::changes::ChangeKind;
+use crate::collections::{self, Collection};
+use crate::handlers::export::ExportRecord;
+use crate::middleware::request_limits::body_error;
+use crate::models::DataEntry;
+use crate::state::{self, AppState};
+use crate::trash;
+use crate::wal::{Op, Pending};
+use async_std::io::prelude::BufReadExt;
+use async_std::stream::StreamExt;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use tide::Request;
+
+// Quantidade máxima de ids listados na mensagem de conflito
+const MAX_LISTED_CONFLICTS: usize = 10;
+
+// Registros gravados de cada vez enquanto o NDJSON chega: a memória usada não
+// cresce com o arquivo e as escritas na coleção só esperam um lote, não a
+// leitura do body inteiro
+const IMPORT_BATCH: usize = 500;
+
+// O que fazer quando o id importado já existe (nos dados ou na lixeira)
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum OnConflict {
+    // Mantém o registro atual
+    Skip,
+    // Substitui pelo importado (vira uma nova revisão)
+    Overwrite,
+    // Para a importação no primeiro conflito, sem gravar nada do arquivo
+    #[default]
+    Fail,
+}
+
+#[derive(Deserialize)]
+struct ImportQuery {
+    #[serde(default)]
+    on_conflict: OnConflict,
+}
+
+pub async fn import_data(mut req: Request<AppState>) -> tide::Result {
+    let query: ImportQuery = req.query().map_err(|_| {
+        tide::Error::from_str(400, "Invalid on_conflict: esperado skip, overwrite ou fail")
+    })?;
+    let collection = collections::from_request(&req)?;
+    let mut import = Import::new(query.on_conflict);
+
+    // Lê o NDJSON linha por linha (o mesmo formato de GET /data/_export) e grava
+    // um lote sempre que ele enche
+    let mut batch = Vec::with_capacity(IMPORT_BATCH);
+    let mut lines = req.take_body().lines();
+    let mut line_number = 0;
+    while let Some(line) = lines.next().await {
+        line_number += 1;
+        let record = match parse_line(line, line_number) {
+            Ok(Some(record)) => record,
+            Ok(None) => continue,
+            Err(err) => return Err(import.abort(req.state(), &collection, err).await),
+        };
+        batch.push(record);
+        if batch.len() == IMPORT_BATCH {
+            import.write(req.state(), &collection, &mut batch).await?;
+        }
+    }
+    import.write(req.state(), &collection, &mut batch).await?;
+
+    let Import {
+        created,
+        overwritten,
+        skipped,
+        ..
+    } = import;
+    tracing::info!(created, overwritten, skipped, "importação concluída");
+    Ok(tide::Body::from_json(&serde_json::json!({
+        "created": created,
+        "overwritten": overwritten,
+        "skipped": skipped,
+    }))?
+    .into())
+}
+
+// Lê um registro do NDJSON (`None` para linhas em branco)
+fn parse_line(
+    line: std::io::Result<String>,
+    line_number: usize,
+) -> tide::Result<Option<ExportRecord>> {
+    let line = line.map_err(body_error)?;
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    let record: ExportRecord = serde_json::from_str(&line).map_err(|e| {
+        tide::Error::from_str(400, format!("Invalid record on line {line_number}: {e}"))
+    })?;
+    // O id seguinte precisa caber em u32 para as criações continuarem
+    if record.id > state::MAX_ID {
+        return Err(tide::Error::from_str(
+            400,
+            format!(
+                "Invalid record on line {line_number}: id maior que {}",
+                state::MAX_ID
+            ),
+        ));
+    }
+    Ok(Some(record))
+}
+
+// Andamento da importação: contagens e ids já vistos no arquivo (um id
+// repetido conta como conflito)
+struct Import {
+    on_conflict: OnConflict,
+    created: usize,
+    overwritten: usize,
+    skipped: usize,
+    seen: HashSet<u32>,
+    // Ids criados pelos lotes já gravados no modo "fail", desfeitos se a
+    // importação parar no meio
+    written: Vec<u32>,
+}
+
+impl Import {
+    fn new(on_conflict: OnConflict) -> Self {
+        Import {
+            on_conflict,
+            created: 0,
+            overwritten: 0,
+            skipped: 0,
+            seen: HashSet::new(),
+            written: Vec::new(),
+        }
+    }
+
+    // Grava o lote (se houver) e o esvazia
+    async fn write(
+        &mut self,
+        state: &AppState,
+        collection: &Collection,
+        batch: &mut Vec<ExportRecord>,
+    ) -> tide::Result<()> {
+        match self.write_batch(state, collection, batch).await {
+            Ok(()) => Ok(()),
+            Err(err) => Err(self.abort(state, collection, err).await),
+        }
+    }
+
+    async fn write_batch(
+        &mut self,
+        state: &AppState,
+        collection: &Collection,
+        batch: &mut Vec<ExportRecord>,
+    ) -> tide::Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let records = std::mem::take(batch);
+        let mut map = collection.data.write().await;
+        let pending = self.apply(state, collection, &mut map, records)?;
+        drop(map);
+        pending.durable().await
+    }
+
+    // Erro no meio da importação. No modo "fail" nada do arquivo fica: os lotes
+    // anteriores são desfeitos; nos outros modos eles continuam gravados.
+    async fn abort(
+        &mut self,
+        state: &AppState,
+        collection: &Collection,
+        err: tide::Error,
+    ) -> tide::Error {
+        if self.on_conflict == OnConflict::Fail {
+            if let Err(undo) = self.rollback(state, collection).await {
+                tracing::error!(error = %undo, "falha ao desfazer a importação");
+                return undo;
+            }
+            return err;
+        }
+        let done = self.created + self.overwritten + self.skipped;
+        if done == 0 {
+            return err;
+        }
+        tide::Error::from_str(
+            err.status(),
+            format!("{err} ({done} registros anteriores já processados)"),
+        )
+    }
+
+    // Apaga de vez os registros criados pelos lotes anteriores (e o histórico
+    // deles), como um DELETE seguido de purge da lixeira
+    async fn rollback(&mut self, state: &AppState, collection: &Collection) -> tide::Result<()> {
+        let ids = std::mem::take(&mut self.written);
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let mut map = collection.data.write().await;
+        let ops = ids
+            .iter()
+            .map(|&id| Op::Delete { id })
+            .chain([Op::Purge { ids: ids.clone() }])
+            .collect();
+        let pending = state.log_ops(collection, ops)?;
+        for &id in &ids {
+            if let Some(entry) = map.remove(&id) {
+                collection.trash.write().insert(id, entry);
+                collection.publish(ChangeKind::Delete, id, None);
+            }
+        }
+        trash::purge(collection, &ids);
+        drop(map);
+        self.created = 0;
+        pending.durable().await
+    }
+
+    // Grava um lote de registros importados. Chamado com `data` travado para
+    // escrita (ordem: `data`, `trash`, `history`).
+    fn apply(
+        &mut self,
+        state: &AppState,
+        collection: &Collection,
+        map: &mut HashMap<u32, DataEntry>,
+        records: Vec<ExportRecord>,
+    ) -> tide::Result<Pending> {
+        let on_conflict = self.on_conflict;
+        let mut trash = collection.trash.write();
+        let mut history = collection.history.write();
+
+        // No modo "fail", confere todos os ids do lote antes de alterar qualquer coisa
+        if on_conflict == OnConflict::Fail {
+            let mut in_batch = HashSet::new();
+            let conflicts: Vec<String> = records
+                .iter()
+                .filter(|r| {
+                    map.contains_key(&r.id)
+                        || trash.contains(r.id)
+                        || self.seen.contains(&r.id)
+                        || !in_batch.insert(r.id)
+                })
+                .map(|r| r.id.to_string())
+                .collect();
+            if !conflicts.is_empty() {
+                let listed: Vec<&str> = conflicts
+                    .iter()
+                    .take(MAX_LISTED_CONFLICTS)
+                    .map(String::as_str)
+                    .collect();
+                return Err(tide::Error::from_str(
+                    409,
+                    format!("{} conflicting ids: {}", conflicts.len(), listed.join(", ")),
+                ));
+            }
+        }
+
+        // Confere os limites da coleção antes de alterar qualquer coisa
+        for record in &records {
+            collection.check_size(&record.entry)?;
+        }
+        let added: HashSet<u32> = records
+            .iter()
+            .map(|r| r.id)
+            .filter(|id| !map.contains_key(id))
+            .filter(|id| !(trash.contains(*id) && on_conflict == OnConflict::Skip))
+            .collect();
+        collection.check_capacity(map.len(), added.len())?;
+
+        let mut imported = Vec::with_capacity(records.len());
+        for ExportRecord { id, entry } in records {
+            let repeated = !self.seen.insert(id);
+            let exists = repeated || map.contains_key(&id) || trash.contains(id);
+            if exists && on_conflict == OnConflict::Skip {
+                self.skipped += 1;
+                continue;
+            }
+
+            if exists {
+                self.overwritten += 1;
+            } else {
+                self.created += 1;
+            }
+            imported.push((id, entry));
+        }
+        if on_conflict == OnConflict::Fail {
+            self.written.extend(imported.iter().map(|(id, _)| *id));
+        }
+
+        // Enfileira no WAL na mesma ordem em que muda a memória (sem WAL, não faz nada)
+        let ops = imported
+            .iter()
+            .map(|(id, entry)| Op::put(*id, entry.clone()))
+            .collect();
+        let pending = state.log_ops(collection, ops)?;
+        for (id, entry) in imported {
+            // Importar por cima de um registro na lixeira o tira de lá
+            trash.take(id);
+            let op = if map.contains_key(&id) {
+                ChangeKind::Update
+            } else {
+                ChangeKind::Create
+            };
+            history.record(id, entry.clone(), None);
+            collection.publish(op, id, Some(entry.clone()));
+            map.insert(id, entry);
+        }
+        Ok(pending)
+    }
+}
@@ -0,0 +1,22 @@
+use crate::metrics::Metrics;
+use crate::state::AppState;
+use std::sync::Arc;
+use tide::{Request, Response, StatusCode};
+
+pub async fn metrics(req: Request<AppState>) -> tide::Result {
+    // As métricas são colocadas na requisição pelo MetricsMiddleware
+    let metrics = req
+        .ext::<Arc<Metrics>>()
+        .ok_or_else(|| tide::Error::from_str(500, "Metrics middleware not installed"))?;
+
+    // Quantidade de registros somando todas as coleções
+    let mut store_size = 0;
+    for (_, collection) in req.state().all_collections() {
+        store_size += collection.data.read().await.len();
+    }
+
+    Ok(Response::builder(StatusCode::Ok)
+        .body(metrics.render(store_size))
+        .content_type("text/plain; version=0.0.4")
+        .build())
+}
@@ -1,5 +1,19 @@
+pub mod bulk;
+pub mod changes;
+pub mod cluster;
+pub mod collections;
 pub mod create;
 pub mod delete;
+pub mod docs;
 pub mod execute;
+pub mod export;
+pub mod history;
+pub mod import;
+pub mod metrics;
+pub mod query;
 pub mod read;
+pub mod search;
+pub mod trash;
+pub mod tx;
 pub mod update;
+pub mod webhooks;
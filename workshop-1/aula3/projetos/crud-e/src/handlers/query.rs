@@ -0,0 +1,64 @@
+use crate::codec;
+use crate::collections;
+use crate::models::DataEntry;
+use crate::query::{self, Expr};
+use crate::state::AppState;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tide::Request;
+
+#[derive(Deserialize)]
+struct QueryParams {
+    // Expressão de filtro (ver src/query.rs)
+    q: Option<String>,
+}
+
+#[derive(Serialize)]
+struct QueryResponse<'a> {
+    // Plano usado (ex: "intersect(index(func_names contains \"add\"), index(size < 4096))")
+    plan: String,
+    // Registros avaliados depois de consultar os índices
+    scanned: usize,
+    count: usize,
+    entries: BTreeMap<u32, &'a DataEntry>,
+}
+
+pub async fn query_data(req: Request<AppState>) -> tide::Result {
+    let params: QueryParams = req
+        .query()
+        .map_err(|_| tide::Error::from_str(400, "Invalid query"))?;
+    let text = params.q.as_deref().map(str::trim).unwrap_or_default();
+    if text.is_empty() {
+        return Err(tide::Error::from_str(400, "Missing q"));
+    }
+    let expr =
+        Expr::parse(text).map_err(|e| tide::Error::from_str(400, format!("Invalid query: {e}")))?;
+
+    // Pega a coleção da requisição (ordem: `data` antes de `indexes`)
+    let collection = collections::from_request(&req)?;
+    let map = collection.data.read().await;
+    let indexes = collection.indexes.read();
+    let plan = query::plan(&expr, &indexes);
+    let candidates: Vec<u32> = match plan.candidates(&indexes) {
+        Some(ids) => ids.into_iter().collect(),
+        None => map.keys().copied().collect(),
+    };
+    drop(indexes);
+
+    // O plano só reduz os candidatos: a expressão inteira é conferida em cada um
+    let entries: BTreeMap<u32, &DataEntry> = candidates
+        .iter()
+        .filter_map(|id| map.get(id).map(|entry| (*id, entry)))
+        .filter(|(id, entry)| expr.matches(*id, entry))
+        .collect();
+
+    codec::response(
+        &req,
+        &QueryResponse {
+            plan: plan.to_string(),
+            scanned: candidates.len(),
+            count: entries.len(),
+            entries,
+        },
+    )
+}
@@ -0,0 +1,90 @@
+use crate::codec;
+use crate::collections;
+use crate::models::DataEntry;
+use crate::search::{self, MatchOptions};
+use crate::state::AppState;
+use serde::{Deserialize, Serialize};
+use tide::Request;
+
+// Quantidade de resultados devolvidos por padrão e no máximo
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    // Texto buscado nos nomes de função
+    q: Option<String>,
+    // Termos da busca também casam como prefixo ("ad" encontra "add")
+    #[serde(default = "enabled")]
+    prefix: bool,
+    // Termos da busca toleram erros de digitação
+    #[serde(default = "enabled")]
+    fuzzy: bool,
+    limit: Option<usize>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Serialize)]
+struct SearchResponse<'a> {
+    total: usize,
+    results: Vec<SearchResult<'a>>,
+}
+
+#[derive(Serialize)]
+struct SearchResult<'a> {
+    id: u32,
+    score: f64,
+    entry: &'a DataEntry,
+    // Nomes de função com os termos encontrados marcados com <em>
+    highlights: Vec<String>,
+}
+
+pub async fn search_data(req: Request<AppState>) -> tide::Result {
+    let query: SearchQuery = req
+        .query()
+        .map_err(|_| tide::Error::from_str(400, "Invalid query"))?;
+    let text = query.q.as_deref().map(str::trim).unwrap_or_default();
+    if text.is_empty() {
+        return Err(tide::Error::from_str(400, "Missing q"));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let options = MatchOptions {
+        prefix: query.prefix,
+        fuzzy: query.fuzzy,
+    };
+
+    // Pega a coleção da requisição (ordem: `data` antes de `search`)
+    let collection = collections::from_request(&req)?;
+    let map = collection.data.read().await;
+    let hits = collection.search.read().search(text, options);
+
+    let results = hits
+        .iter()
+        .take(limit)
+        .filter_map(|hit| {
+            let entry = map.get(&hit.id)?;
+            let highlights = entry
+                .func_names
+                .iter()
+                .filter_map(|name| search::highlight(name, &hit.terms))
+                .collect();
+            Some(SearchResult {
+                id: hit.id,
+                score: (hit.score * 1000.0).round() / 1000.0,
+                entry,
+                highlights,
+            })
+        })
+        .collect();
+
+    codec::response(
+        &req,
+        &SearchResponse {
+            total: hits.len(),
+            results,
+        },
+    )
+}
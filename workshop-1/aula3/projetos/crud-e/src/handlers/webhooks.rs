@@ -0,0 +1,98 @@
+use crate::changes::ChangeKind;
+use crate::state::AppState;
+use crate::webhooks::{NewSubscription, Subscription};
+use serde::Serialize;
+use tide::{Request, Response, StatusCode};
+
+// Uma assinatura nas respostas (o segredo só aparece na criação)
+#[derive(Serialize)]
+struct SubscriptionInfo<'a> {
+    id: u32,
+    url: &'a str,
+    events: &'a [ChangeKind],
+    collections: &'a [String],
+    created_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<&'a str>,
+}
+
+impl<'a> SubscriptionInfo<'a> {
+    fn new(subscription: &'a Subscription) -> Self {
+        SubscriptionInfo {
+            id: subscription.id,
+            url: &subscription.url,
+            events: &subscription.events,
+            collections: &subscription.collections,
+            created_ms: subscription.created_ms,
+            secret: None,
+        }
+    }
+}
+
+pub async fn create_webhook(mut req: Request<AppState>) -> tide::Result {
+    let body: NewSubscription = req.body_json().await?;
+    body.validate().map_err(|e| tide::Error::from_str(400, e))?;
+
+    let Some(subscription) = req.state().subscribe_webhook(body).await? else {
+        return Err(tide::Error::from_str(
+            409,
+            "Too many webhooks: limite de assinaturas atingido",
+        ));
+    };
+    tracing::info!(webhook = subscription.id, url = %subscription.url, "webhook criado");
+
+    let info = SubscriptionInfo {
+        secret: Some(&subscription.secret),
+        ..SubscriptionInfo::new(&subscription)
+    };
+    Ok(Response::builder(StatusCode::Created)
+        .header("Location", format!("/_webhooks/{}", subscription.id))
+        .body(tide::Body::from_json(&info)?)
+        .build())
+}
+
+pub async fn list_webhooks(req: Request<AppState>) -> tide::Result {
+    let subscriptions = req.state().webhooks.subscriptions();
+    let infos: Vec<_> = subscriptions.iter().map(SubscriptionInfo::new).collect();
+    Ok(tide::Body::from_json(&infos)?.into())
+}
+
+pub async fn delete_webhook(req: Request<AppState>) -> tide::Result {
+    let id = webhook_id(&req)?;
+    if !req.state().unsubscribe_webhook(id).await? {
+        return Err(tide::Error::from_str(404, "Webhook not found"));
+    }
+    tracing::info!(webhook = id, "webhook removido");
+    Ok(Response::new(StatusCode::NoContent))
+}
+
+// Últimas tentativas de entrega da assinatura, mais recentes primeiro
+pub async fn read_deliveries(req: Request<AppState>) -> tide::Result {
+    let id = webhook_id(&req)?;
+    let Some(deliveries) = req.state().webhooks.deliveries(id) else {
+        return Err(tide::Error::from_str(404, "Webhook not found"));
+    };
+    Ok(tide::Body::from_json(&deliveries)?.into())
+}
+
+// Eventos que esgotaram as tentativas, mais recentes primeiro
+pub async fn read_dead_letters(req: Request<AppState>) -> tide::Result {
+    Ok(tide::Body::from_json(&req.state().webhooks.dead_letters())?.into())
+}
+
+// Reenvia um evento da lista de falhas (as tentativas recomeçam do zero)
+pub async fn retry_dead_letter(req: Request<AppState>) -> tide::Result {
+    let id: u64 = match req.param("id")?.parse() {
+        Ok(val) => val,
+        Err(_) => return Err(tide::Error::from_str(400, "Invalid id")),
+    };
+    req.state().webhooks.retry_dead_letter(id)?;
+    Ok(Response::new(StatusCode::Accepted))
+}
+
+fn webhook_id(req: &Request<AppState>) -> tide::Result<u32> {
+    match req.param("id")?.parse() {
+        Ok(val) => Ok(val),
+        Err(_) => Err(tide::Error::from_str(400, "Invalid id")),
+    }
+}
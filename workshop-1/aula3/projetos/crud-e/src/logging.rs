@@ -0,0 +1,31 @@
+// Configuração dos logs estruturados (tracing), a partir da seção [log] da configuração.
+use crate::config::{LogConfig, LogFormat};
+use tracing::Dispatch;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+
+// O log de requisições do próprio Tide fica desligado porque
+// o RequestLogger já registra cada requisição com mais detalhes.
+const TIDE_LOG_FILTER: &str = "tide::log::middleware=off";
+
+pub fn init(config: &LogConfig) {
+    dispatch(config, std::io::stdout).init();
+}
+
+// Coletor dos logs no formato configurado, escrevendo em `writer`
+// (o servidor usa a saída padrão; os testes, um buffer)
+pub fn dispatch<W>(config: &LogConfig, writer: W) -> Dispatch
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::new(format!("{},{TIDE_LOG_FILTER}", config.level));
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    match config.format {
+        LogFormat::Json => builder.json().with_current_span(true).finish().into(),
+        LogFormat::Text => builder.finish().into(),
+    }
+}
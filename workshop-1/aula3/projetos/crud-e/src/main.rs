@@ -1,51 +1,102 @@
-mod handlers;
-mod models;
-mod state;
+use clap::Parser;
+use crud_e::config::{Cli, Config};
+use crud_e::raft::Node;
+use crud_e::{build_app, logging, server, state, storage, trash, wal};
 
-use handlers::create::create_data;
-use handlers::delete::delete_data;
-use handlers::execute::execute_fn;
-use handlers::read::{read_all_data, read_data};
-use handlers::update::update_data;
+#[async_std::main]
+async fn main() -> tide::Result<()> {
+    // Lê a configuração (padrões, arquivo TOML, variáveis de ambiente e flags)
+    let cli = Cli::parse();
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    if cli.print_config {
+        print!("{}", config.to_redacted_toml());
+        return Ok(());
+    }
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+    // Inicia os logs estruturados
+    logging::init(&config.log);
 
+    // Em cluster, o estado vem do snapshot e do log do Raft (ver src/raft)
+    if config.cluster.is_enabled() {
+        return serve_cluster(&config).await;
+    }
 
-// Cria um contador global de chamadas
-static CALL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+    // Cria o estado global da aplicação, restaurando o snapshot salvo (backend "file")
+    let snapshot = match storage::load_snapshot(&config.storage) {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            tracing::error!(error = %err, "não foi possível restaurar o snapshot");
+            std::process::exit(1);
+        }
+    };
+    tracing::info!(
+        storage = ?config.storage.backend,
+        entries = snapshot.entries.len(),
+        "estado carregado"
+    );
+    let checkpoint_seq = snapshot.wal_seq;
+    let state = state::from_snapshot(snapshot, &config);
 
-#[async_std::main]
-async fn main() -> tide::Result<()> {
-    // Cria o estado global da aplicação
-    let state = state::new_state();
-
-    // Cria o app Tide e associa o estado
-    let mut app = tide::with_state(state);
-
-    // Adiciona um middleware para logar a rota chamada e o contador
-    app.with(tide::utils::Before(|req: tide::Request<_>| async move {
-        let count = CALL_COUNTER.fetch_add(1, Ordering::SeqCst) + 1;
-        println!(
-            "Rota chamada: {} {} | Total de chamadas: {}",
-            req.method(),
-            req.url().path(),
-            count
-        );
-        req
-    }));
-
-    // Define as rotas CRUD
-    app.at("/data").post(create_data); // Cria
-    app.at("/data").get(read_all_data); // Lê todos
-    app.at("/data/:id").get(read_data); // Lê um
-    app.at("/data/:id").put(update_data); // Atualiza
-    app.at("/data/:id").delete(delete_data); // Deleta
-    app.at("/execute/:id").post(execute_fn); // Executa funções wasm
-
-    let addr = "0.0.0.0:8080";
-    println!("Servidor CRUD rodando em: http://{addr}");
-
-    // Inicia o servidor
-    app.listen(addr).await?;
+    // Reaplica as escritas gravadas no WAL depois do último checkpoint (ver src/wal.rs)
+    let wal = match wal::open(&config.storage, checkpoint_seq, &state).await {
+        Ok(wal) => wal,
+        Err(err) => {
+            tracing::error!(error = %err, "não foi possível abrir o WAL");
+            std::process::exit(1);
+        }
+    };
+    if let Some(wal) = &wal {
+        state.set_wal(wal.clone());
+        wal::spawn_checkpointer(state.clone(), config.storage.clone(), wal.clone());
+    }
+
+    // Apaga de vez os registros que passaram do tempo na lixeira
+    trash::spawn_purger(state.clone(), &config.trash);
+
+    // Cria o app Tide com middlewares e rotas (ver src/lib.rs)
+    let app = build_app(state.clone(), &config);
+
+    // Inicia o servidor e espera o sinal de desligamento (SIGINT/SIGTERM)
+    server::serve(app, &config.server).await?;
+
+    // Salva o estado antes de sair (com WAL, como um último checkpoint)
+    let saved = match &wal {
+        Some(wal) => wal.checkpoint(&config.storage, &state).await,
+        None => storage::save_snapshot(&config.storage, &state).await,
+    };
+    match saved {
+        Ok(Some(saved)) => tracing::info!(entries = saved.entries, "snapshot salvo"),
+        Ok(None) => tracing::info!("backend em memória: nada para salvar"),
+        Err(err) => {
+            tracing::error!(error = %err, "falha ao salvar o snapshot");
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+// Sobe o nó do cluster e a API sobre o estado replicado. Não há snapshot do
+// backend "file" para salvar na saída: o nó guarda o próprio log e snapshots.
+// Também não há limpeza periódica da lixeira: ela expira junto com as entradas
+// do log (ver src/raft).
+async fn serve_cluster(config: &Config) -> tide::Result<()> {
+    let state = state::from_snapshot(Default::default(), config);
+    let node = match Node::start(&config.cluster, state.clone()).await {
+        Ok(node) => node,
+        Err(err) => {
+            tracing::error!(error = %err, "não foi possível iniciar o nó do cluster");
+            std::process::exit(1);
+        }
+    };
+
+    let app = build_app(state, config);
+    server::serve(app, &config.server).await?;
+    node.shutdown();
     Ok(())
 }
@@ -0,0 +1,202 @@
+// Métricas da aplicação no formato texto do Prometheus.
+// São coletadas pelo middleware de métricas e pelo handler de execução,
+// e expostas na rota /metrics.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::sync::Mutex;
+
+// Limites (em segundos) dos buckets dos histogramas de latência
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// Histograma cumulativo no estilo Prometheus
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    counts: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        for (i, limit) in BUCKETS.iter().enumerate() {
+            if secs <= *limit {
+                self.counts[i] += 1;
+            }
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+}
+
+// Chave das métricas HTTP: método, rota e status
+type RequestKey = (String, String, u16);
+
+// Chave das métricas wasm: coleção e módulo (id do registro na coleção)
+type ModuleKey = (String, u32);
+
+#[derive(Debug, Default)]
+struct Inner {
+    requests: BTreeMap<RequestKey, u64>,
+    request_duration: BTreeMap<RequestKey, Histogram>,
+    executions: BTreeMap<ModuleKey, u64>,
+    execution_duration: BTreeMap<ModuleKey, Histogram>,
+    execution_failures: BTreeMap<(ModuleKey, &'static str), u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registra uma requisição HTTP finalizada
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let key = (method.to_string(), route.to_string(), status);
+        let mut inner = self.inner.lock();
+        *inner.requests.entry(key.clone()).or_default() += 1;
+        inner
+            .request_duration
+            .entry(key)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    // Registra uma execução wasm do módulo `module` (id do registro) da
+    // coleção `collection`: o mesmo id existe em várias coleções.
+    // `failure` indica o motivo quando a execução falhou.
+    pub fn observe_execution(
+        &self,
+        collection: &str,
+        module: u32,
+        elapsed: Duration,
+        failure: Option<&'static str>,
+    ) {
+        let key = (collection.to_string(), module);
+        let mut inner = self.inner.lock();
+        *inner.executions.entry(key.clone()).or_default() += 1;
+        inner
+            .execution_duration
+            .entry(key.clone())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+        if let Some(reason) = failure {
+            *inner.execution_failures.entry((key, reason)).or_default() += 1;
+        }
+    }
+
+    // Gera o texto no formato de exposição do Prometheus
+    pub fn render(&self, store_size: usize) -> String {
+        let inner = self.inner.lock();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "http_requests_total",
+            "counter",
+            "Total de requisições HTTP",
+        );
+        for ((method, route, status), count) in &inner.requests {
+            let labels = request_labels(method, route, *status);
+            let _ = writeln!(out, "http_requests_total{{{labels}}} {count}");
+        }
+
+        header(
+            &mut out,
+            "http_request_duration_seconds",
+            "histogram",
+            "Latência das requisições HTTP",
+        );
+        for ((method, route, status), hist) in &inner.request_duration {
+            let labels = request_labels(method, route, *status);
+            write_histogram(&mut out, "http_request_duration_seconds", &labels, hist);
+        }
+
+        header(
+            &mut out,
+            "crud_store_entries",
+            "gauge",
+            "Registros armazenados",
+        );
+        let _ = writeln!(out, "crud_store_entries {store_size}");
+
+        header(
+            &mut out,
+            "wasm_executions_total",
+            "counter",
+            "Execuções wasm por coleção e módulo",
+        );
+        for (key, count) in &inner.executions {
+            let labels = module_labels(key);
+            let _ = writeln!(out, "wasm_executions_total{{{labels}}} {count}");
+        }
+
+        header(
+            &mut out,
+            "wasm_execution_duration_seconds",
+            "histogram",
+            "Duração das execuções wasm por coleção e módulo",
+        );
+        for (key, hist) in &inner.execution_duration {
+            let labels = module_labels(key);
+            write_histogram(&mut out, "wasm_execution_duration_seconds", &labels, hist);
+        }
+
+        header(
+            &mut out,
+            "wasm_execution_failures_total",
+            "counter",
+            "Execuções wasm que falharam, por coleção, módulo e motivo",
+        );
+        for ((key, reason), count) in &inner.execution_failures {
+            let labels = module_labels(key);
+            let _ = writeln!(
+                out,
+                "wasm_execution_failures_total{{{labels},reason=\"{reason}\"}} {count}"
+            );
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn request_labels(method: &str, route: &str, status: u16) -> String {
+    format!(
+        "method=\"{}\",route=\"{}\",status=\"{status}\"",
+        escape(method),
+        escape(route)
+    )
+}
+
+fn module_labels((collection, module): &ModuleKey) -> String {
+    format!("collection=\"{}\",module=\"{module}\"", escape(collection))
+}
+
+fn write_histogram(out: &mut String, name: &str, labels: &str, hist: &Histogram) {
+    for (limit, count) in BUCKETS.iter().zip(hist.counts.iter()) {
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{limit}\"}} {count}");
+    }
+    let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", hist.count);
+    let _ = writeln!(out, "{name}_sum{{{labels}}} {}", hist.sum);
+    let _ = writeln!(out, "{name}_count{{{labels}}} {}", hist.count);
+}
+
+// Escapa os caracteres especiais dos valores de label
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,167 @@
+use crate::collections;
+use crate::config::AuthConfig;
+use crate::middleware::request_limits::{read_error, replace_body};
+use crate::routes::path_matches;
+use crate::state::AppState;
+use serde::Deserialize;
+use tide::http::Method;
+use tide::utils::async_trait;
+use tide::{Middleware, Next, Request, Response, StatusCode};
+
+// Rota que cria assinaturas de webhooks (ver WebhookAccess)
+const WEBHOOKS_PATH: &str = "/_webhooks";
+
+// Chave que o ApiKeyAuth conferiu, guardada na requisição para os middlewares
+// seguintes (ver rate_limit::client_key)
+#[derive(Clone, Debug)]
+pub struct ValidApiKey(pub String);
+
+// Middleware de autenticação por API key (header `X-Api-Key`).
+// Nas rotas de uma coleção valem as chaves globais e as da própria coleção;
+// sem nenhuma chave configurada, todas as requisições passam.
+#[derive(Debug)]
+pub struct ApiKeyAuth {
+    api_keys: Vec<String>,
+    public_paths: Vec<String>,
+}
+
+impl ApiKeyAuth {
+    pub fn new(config: &AuthConfig) -> Self {
+        ApiKeyAuth {
+            api_keys: config.api_keys.clone(),
+            public_paths: config.public_paths.clone(),
+        }
+    }
+
+    fn is_public(&self, path: &str) -> bool {
+        self.public_paths
+            .iter()
+            .any(|pattern| path_matches(pattern, path))
+    }
+}
+
+#[async_trait]
+impl Middleware<AppState> for ApiKeyAuth {
+    async fn handle(&self, mut req: Request<AppState>, next: Next<'_, AppState>) -> tide::Result {
+        let collection_keys = collection_keys(&req);
+        if (self.api_keys.is_empty() && collection_keys.is_empty())
+            || self.is_public(req.url().path())
+        {
+            return Ok(next.run(req).await);
+        }
+
+        let Some(key) = valid_key(&req, &self.api_keys, &collection_keys) else {
+            return Ok(unauthorized("Missing or invalid API key".to_string()));
+        };
+
+        req.set_ext(ValidApiKey(key));
+        Ok(next.run(req).await)
+    }
+}
+
+// Chaves próprias da coleção do caminho (se houver), inclusive no
+// DELETE /collections/:name
+pub fn collection_keys(req: &Request<AppState>) -> Vec<String> {
+    let path = req.url().path();
+    collections::split_path(path)
+        .map(|(name, _)| name)
+        .or_else(|| path.strip_prefix("/collections/"))
+        .and_then(|name| req.state().collection(name.trim_end_matches('/')))
+        .map(|collection| collection.settings.api_keys.clone())
+        .unwrap_or_default()
+}
+
+// Chave do header `X-Api-Key`, se for uma das globais ou das da coleção
+pub fn valid_key(
+    req: &Request<AppState>,
+    api_keys: &[String],
+    collection_keys: &[String],
+) -> Option<String> {
+    let key = req.header("X-Api-Key")?.last().as_str();
+    api_keys
+        .iter()
+        .chain(collection_keys)
+        .any(|valid| constant_time_eq(valid.as_bytes(), key.as_bytes()))
+        .then(|| key.to_string())
+}
+
+// Confere o acesso às coleções nomeadas em POST /_webhooks: cada uma com API
+// keys próprias exige uma delas (ou uma global), como as rotas da coleção.
+// Fica depois do RequestLimits, que limita a leitura do body, e antes do
+// Cluster, que tira as credenciais das entradas do log.
+#[derive(Debug)]
+pub struct WebhookAccess {
+    api_keys: Vec<String>,
+}
+
+// Parte do corpo de POST /_webhooks lida aqui; o resto fica com o handler
+#[derive(Deserialize)]
+struct WebhookCollections {
+    #[serde(default)]
+    collections: Vec<String>,
+}
+
+impl WebhookAccess {
+    pub fn new(config: &AuthConfig) -> Self {
+        WebhookAccess {
+            api_keys: config.api_keys.clone(),
+        }
+    }
+
+    // Primeira coleção nomeada cuja API key não veio na requisição
+    fn forbidden<'a>(&self, req: &Request<AppState>, names: &'a [String]) -> Option<&'a str> {
+        let key = req.header("X-Api-Key").map(|values| values.last().as_str());
+        let matches = |keys: &[String]| {
+            key.is_some_and(|key| {
+                keys.iter()
+                    .any(|valid| constant_time_eq(valid.as_bytes(), key.as_bytes()))
+            })
+        };
+        if matches(&self.api_keys) {
+            return None;
+        }
+        names.iter().map(String::as_str).find(|name| {
+            req.state().collection(name).is_some_and(|collection| {
+                let keys = &collection.settings.api_keys;
+                !keys.is_empty() && !matches(keys)
+            })
+        })
+    }
+}
+
+#[async_trait]
+impl Middleware<AppState> for WebhookAccess {
+    async fn handle(&self, mut req: Request<AppState>, next: Next<'_, AppState>) -> tide::Result {
+        if req.method() != Method::Post || req.url().path() != WEBHOOKS_PATH {
+            return Ok(next.run(req).await);
+        }
+        let body = req.body_bytes().await.map_err(read_error)?;
+        // Corpo inválido: o handler responde o erro
+        let names = serde_json::from_slice::<WebhookCollections>(&body)
+            .map(|parsed| parsed.collections)
+            .unwrap_or_default();
+        if let Some(name) = self.forbidden(&req, &names) {
+            return Ok(unauthorized(format!(
+                "Missing or invalid API key: a coleção {name} exige a própria API key"
+            )));
+        }
+        replace_body(&mut req, body);
+        Ok(next.run(req).await)
+    }
+}
+
+fn unauthorized(message: String) -> Response {
+    Response::builder(StatusCode::Unauthorized)
+        .header("WWW-Authenticate", "ApiKey header=\"X-Api-Key\"")
+        .body(message)
+        .build()
+}
+
+// Compara as chaves sem parar no primeiro byte diferente,
+// para o tempo de resposta não revelar quanto da chave está certo
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
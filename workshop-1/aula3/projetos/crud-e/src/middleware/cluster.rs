@@ -0,0 +1,204 @@
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use tide::http::Method;
+use tide::utils::async_trait;
+use tide::{Middleware, Next, Request, Response, StatusCode};
+
+use crate::client::Client;
+use crate::collections;
+use crate::config::{ClusterConfig, FollowerWrites, PeerConfig, ReadConsistency};
+use crate::middleware::request_limits::read_error;
+use crate::raft::{Refusal, WriteRequest, build_request};
+use crate::routes::path_matches;
+use crate::state::{AppState, new_uuid, now_ms};
+
+const READ_CONSISTENCY_HEADER: &str = "X-Read-Consistency";
+// Marca as requisições repassadas por um seguidor, para nunca repassar de novo
+const FORWARDED_HEADER: &str = "X-Raft-Forwarded";
+
+// Headers da conexão, que nunca são copiados
+const CONNECTION_HEADERS: [&str; 4] = ["host", "connection", "content-length", "transfer-encoding"];
+// Credenciais: vão para o líder (que autentica de novo), mas não para o log
+const CREDENTIAL_HEADERS: [&str; 2] = ["authorization", "x-api-key"];
+
+// Encaminha as requisições da API pelo Raft (ver src/raft):
+// - escritas (métodos que não são GET/HEAD/OPTIONS) viram entradas do log no
+//   líder e respondem depois de confirmadas pela maioria e aplicadas
+// - leituras linearizáveis (padrão) passam pelo ReadIndex no líder; com
+//   `X-Read-Consistency: stale` o nó responde com o que já aplicou
+// - num seguidor, as duas primeiras são repassadas ao líder ou respondem 307
+//   (`follower_writes`); sem líder conhecido, 503 com Retry-After
+// As rotas de `local_paths` são sempre atendidas pelo próprio nó.
+pub struct Cluster {
+    local_paths: Vec<String>,
+    follower_writes: FollowerWrites,
+    read_consistency: ReadConsistency,
+    client: Client,
+    forward_timeout: Duration,
+}
+
+impl Cluster {
+    pub fn from_config(config: &ClusterConfig) -> Self {
+        Cluster {
+            local_paths: config.local_paths.clone(),
+            follower_writes: config.follower_writes,
+            read_consistency: config.read_consistency,
+            client: Client::new(None),
+            forward_timeout: Duration::from_millis(
+                config.commit_timeout_ms + config.rpc_timeout_ms,
+            ),
+        }
+    }
+
+    fn is_local(&self, path: &str) -> bool {
+        let rest = collections::split_path(path).map_or(path, |(_, rest)| rest);
+        self.local_paths
+            .iter()
+            .any(|pattern| path_matches(pattern, path) || path_matches(pattern, rest))
+    }
+
+    fn consistency(&self, req: &Request<AppState>) -> tide::Result<ReadConsistency> {
+        match req
+            .header(READ_CONSISTENCY_HEADER)
+            .map(|v| v.last().as_str())
+        {
+            None => Ok(self.read_consistency),
+            Some("linearizable") => Ok(ReadConsistency::Linearizable),
+            Some("stale") => Ok(ReadConsistency::Stale),
+            Some(_) => Err(tide::Error::from_str(
+                StatusCode::BadRequest,
+                "Invalid X-Read-Consistency: use linearizable ou stale",
+            )),
+        }
+    }
+
+    // Resposta quando este nó não pôde atender: repassa ao líder, redireciona ou 503
+    async fn refuse(
+        &self,
+        req: &Request<AppState>,
+        body: Vec<u8>,
+        refusal: Refusal,
+    ) -> tide::Result {
+        let leader = match refusal {
+            Refusal::Unavailable(message) => return Ok(unavailable(message)),
+            Refusal::NotLeader(None) => {
+                return Ok(unavailable("No leader: eleição em andamento"));
+            }
+            // Repassada por um seguidor que achava que este nó era o líder
+            Refusal::NotLeader(Some(_)) if req.header(FORWARDED_HEADER).is_some() => {
+                return Ok(unavailable("Not leader: a liderança mudou"));
+            }
+            Refusal::NotLeader(Some(leader)) => leader,
+        };
+        let location = format!(
+            "{}{}",
+            leader.api_url.trim_end_matches('/'),
+            path_and_query(req)
+        );
+        match self.follower_writes {
+            FollowerWrites::Redirect => Ok(Response::builder(StatusCode::TemporaryRedirect)
+                .header("Location", location)
+                .build()),
+            FollowerWrites::Forward => self.forward(req, body, &leader, &location).await,
+        }
+    }
+
+    async fn forward(
+        &self,
+        req: &Request<AppState>,
+        body: Vec<u8>,
+        leader: &PeerConfig,
+        url: &str,
+    ) -> tide::Result {
+        let url = url
+            .parse()
+            .map_err(|_| tide::Error::from_str(500, "Invalid leader URL"))?;
+        let mut headers = copy_headers(req, &[]);
+        headers.push((FORWARDED_HEADER.to_string(), "true".to_string()));
+        let forwarded = build_request(req.method(), url, headers, body);
+        let mut res = self
+            .client
+            .send(forwarded, self.forward_timeout)
+            .await
+            .map_err(|err| {
+                tide::Error::from_str(
+                    StatusCode::BadGateway,
+                    format!("Leader unreachable: nó {} não respondeu ({err})", leader.id),
+                )
+            })?;
+        for name in ["connection", "transfer-encoding", "content-length"] {
+            res.remove_header(name);
+        }
+        Ok(res.into())
+    }
+}
+
+#[async_trait]
+impl Middleware<AppState> for Cluster {
+    async fn handle(&self, mut req: Request<AppState>, next: Next<'_, AppState>) -> tide::Result {
+        let Some(node) = req.state().cluster() else {
+            return Ok(next.run(req).await);
+        };
+        if self.is_local(req.url().path()) {
+            return Ok(next.run(req).await);
+        }
+
+        if matches!(req.method(), Method::Get | Method::Head | Method::Options) {
+            if self.consistency(&req)? == ReadConsistency::Stale {
+                return Ok(next.run(req).await);
+            }
+            return match node.read_barrier().await {
+                Ok(()) => Ok(next.run(req).await),
+                Err(refusal) => self.refuse(&req, Vec::new(), refusal).await,
+            };
+        }
+
+        let body = req.body_bytes().await.map_err(read_error)?;
+        let write = WriteRequest {
+            method: req.method().to_string(),
+            path: path_and_query(&req),
+            headers: copy_headers(&req, &CREDENTIAL_HEADERS),
+            body: BASE64.encode(&body),
+            timestamp_ms: now_ms(),
+            seed: new_uuid().as_u128(),
+        };
+        match node.propose(write).await {
+            Ok(result) => result,
+            Err(refusal) => self.refuse(&req, body, refusal).await,
+        }
+    }
+}
+
+fn path_and_query(req: &Request<AppState>) -> String {
+    let url = req.url();
+    match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    }
+}
+
+fn copy_headers(req: &Request<AppState>, skip: &[&str]) -> Vec<(String, String)> {
+    req.iter()
+        .filter(|(name, _)| {
+            !CONNECTION_HEADERS.contains(&name.as_str()) && !skip.contains(&name.as_str())
+        })
+        .flat_map(|(name, values)| {
+            values
+                .iter()
+                .map(|value| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn unavailable(message: &str) -> Response {
+    let mut res = Response::new(StatusCode::ServiceUnavailable);
+    res.insert_header("Retry-After", "1");
+    res.set_error(tide::Error::from_str(
+        StatusCode::ServiceUnavailable,
+        message.to_string(),
+    ));
+    res
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use tide::http::Method;
+use tide::http::headers::{CONTENT_TYPE, HeaderName, HeaderValues};
+use tide::utils::async_trait;
+use tide::{Body, Middleware, Next, Request, Response, StatusCode};
+
+use crate::collections;
+use crate::config::IdempotencyConfig;
+use crate::middleware::rate_limit::client_key;
+use crate::middleware::request_limits::{read_error, replace_body};
+use crate::routes::path_matches;
+use crate::sync::Mutex;
+
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+// Tamanho máximo do valor do header Idempotency-Key
+const MAX_KEY_LEN: usize = 255;
+
+// Chave guardada: cliente (API key ou IP), caminho e o valor do header
+type Key = (String, String, String);
+
+// Primeira resposta de uma chave, repetida nas tentativas seguintes
+#[derive(Clone, Debug)]
+struct Stored {
+    status: StatusCode,
+    headers: Vec<(HeaderName, HeaderValues)>,
+    body: Vec<u8>,
+    // Mensagem do erro devolvido pelo handler; o corpo JSON do erro é montado
+    // pelo RequestLogger (ver src/middleware/request_log.rs)
+    error: Option<String>,
+}
+
+impl Stored {
+    fn replay(&self) -> Response {
+        let mut res = Response::new(self.status);
+        for (name, values) in &self.headers {
+            for value in values {
+                res.append_header(name.clone(), value.clone());
+            }
+        }
+        set_body(&mut res, self.body.clone());
+        if let Some(message) = &self.error {
+            res.set_error(tide::Error::from_str(self.status, message.clone()));
+        }
+        res.insert_header(REPLAYED_HEADER, "true");
+        res
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    // sha256 do body da primeira requisição
+    fingerprint: [u8; 32],
+    created: Instant,
+    // `None` enquanto a primeira requisição ainda está em andamento
+    response: Option<Stored>,
+}
+
+// Middleware de idempotência para as rotas POST configuradas.
+// Com o header `Idempotency-Key`, a primeira resposta de cada chave fica
+// guardada por `ttl` e é devolvida de novo (com `Idempotent-Replayed: true`)
+// quando o cliente repete a requisição, sem rodar o handler outra vez.
+// - mesma chave com outro body: 422
+// - mesma chave enquanto a primeira ainda roda: 409
+// - respostas 5xx não são guardadas, para o cliente poder tentar de novo
+// - `max_keys` chaves ainda em andamento: 503 para as chaves novas
+// As chaves são separadas por cliente e por caminho (e portanto por coleção).
+#[derive(Debug)]
+pub struct Idempotency {
+    paths: Vec<String>,
+    ttl: Duration,
+    max_keys: usize,
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+impl Idempotency {
+    pub fn from_config(config: &IdempotencyConfig) -> Self {
+        Idempotency {
+            paths: config.paths.clone(),
+            ttl: Duration::from_secs(config.ttl_secs),
+            max_keys: config.max_keys,
+            entries: Mutex::default(),
+        }
+    }
+
+    fn applies_to(&self, path: &str) -> bool {
+        let path = collections::split_path(path).map_or(path, |(_, rest)| rest);
+        self.paths.iter().any(|pattern| path_matches(pattern, path))
+    }
+
+    // Registra a chave como em andamento (`None`), ou devolve a resposta da
+    // chave já usada: a primeira resposta de novo ou um erro
+    fn begin(&self, key: &Key, fingerprint: [u8; 32]) -> Option<tide::Result> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock();
+        match entries.get(key) {
+            Some(entry) if now.duration_since(entry.created) >= self.ttl => {}
+            Some(entry) if entry.fingerprint != fingerprint => {
+                return Some(Err(tide::Error::from_str(
+                    StatusCode::UnprocessableEntity,
+                    "Idempotency-Key reused: a chave já foi usada com outro body",
+                )));
+            }
+            Some(Entry {
+                response: Some(stored),
+                ..
+            }) => return Some(Ok(stored.replay())),
+            Some(_) => {
+                return Some(Err(tide::Error::from_str(
+                    StatusCode::Conflict,
+                    "Idempotency-Key in progress: a primeira requisição com a chave ainda não terminou",
+                )));
+            }
+            None => {}
+        }
+
+        if entries.len() >= self.max_keys {
+            entries.retain(|_, entry| now.duration_since(entry.created) < self.ttl);
+        }
+        if entries.len() >= self.max_keys {
+            // Descarta a resposta guardada mais antiga; as chaves em andamento
+            // ficam, senão uma repetição rodaria o handler uma segunda vez
+            let Some(oldest) = entries
+                .iter()
+                .filter(|(_, entry)| entry.response.is_some())
+                .min_by_key(|(_, entry)| entry.created)
+                .map(|(key, _)| key.clone())
+            else {
+                return Some(Err(tide::Error::from_str(
+                    StatusCode::ServiceUnavailable,
+                    "Idempotency keys exhausted: todas as chaves guardadas ainda estão em andamento",
+                )));
+            };
+            entries.remove(&oldest);
+        }
+        entries.insert(
+            key.clone(),
+            Entry {
+                fingerprint,
+                created: now,
+                response: None,
+            },
+        );
+        None
+    }
+}
+
+// Tira a chave em andamento se a requisição não chegar ao fim (ex: cancelada
+// pelo prazo do handler), para o cliente poder tentar de novo
+struct Pending<'a> {
+    entries: &'a Mutex<HashMap<Key, Entry>>,
+    key: Option<Key>,
+}
+
+impl Pending<'_> {
+    fn finish(mut self, stored: Option<Stored>) {
+        let Some(key) = self.key.take() else {
+            return;
+        };
+        let mut entries = self.entries.lock();
+        match stored {
+            Some(stored) => {
+                if let Some(entry) = entries.get_mut(&key) {
+                    entry.response = Some(stored);
+                }
+            }
+            None => {
+                entries.remove(&key);
+            }
+        }
+    }
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.entries.lock().remove(&key);
+        }
+    }
+}
+
+#[async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Idempotency {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        if req.method() != Method::Post || !self.applies_to(req.url().path()) {
+            return Ok(next.run(req).await);
+        }
+        let Some(value) = req
+            .header(IDEMPOTENCY_KEY_HEADER)
+            .map(|v| v.last().to_string())
+        else {
+            return Ok(next.run(req).await);
+        };
+        if value.is_empty()
+            || value.len() > MAX_KEY_LEN
+            || !value.bytes().all(|b| b.is_ascii_graphic())
+        {
+            return Err(tide::Error::from_str(
+                StatusCode::BadRequest,
+                format!("Invalid Idempotency-Key: use até {MAX_KEY_LEN} caracteres ASCII visíveis"),
+            ));
+        }
+
+        let body = req.body_bytes().await.map_err(read_error)?;
+        let fingerprint = Sha256::digest(&body).into();
+        replace_body(&mut req, body);
+        let key = (client_key(&req), req.url().path().to_string(), value);
+        if let Some(result) = self.begin(&key, fingerprint) {
+            return result;
+        }
+        let pending = Pending {
+            entries: &self.entries,
+            key: Some(key),
+        };
+
+        let mut res = next.run(req).await;
+        if res.status().is_server_error() {
+            pending.finish(None);
+            return Ok(res);
+        }
+        let body = res.take_body().into_bytes().await?;
+        set_body(&mut res, body.clone());
+        pending.finish(Some(Stored {
+            status: res.status(),
+            headers: res
+                .iter()
+                .map(|(name, values)| (name.clone(), values.clone()))
+                .collect(),
+            body,
+            error: res.error().map(|err| err.to_string()),
+        }));
+        Ok(res)
+    }
+}
+
+// Como `replace_body`, sem inventar um Content-Type que o handler não definiu
+fn set_body(res: &mut Response, bytes: Vec<u8>) {
+    let had_content_type = res.header(CONTENT_TYPE).is_some();
+    res.set_body(Body::from_bytes(bytes));
+    if !had_content_type {
+        res.remove_header(CONTENT_TYPE);
+    }
+}
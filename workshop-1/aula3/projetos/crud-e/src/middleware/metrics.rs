@@ -0,0 +1,48 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::metrics::Metrics;
+use crate::routes::path_matches;
+use tide::utils::async_trait;
+use tide::{Middleware, Next, Request};
+
+// Middleware que mede cada requisição (contagem e latência por rota e status).
+// Também deixa as métricas disponíveis para os handlers via `req.ext::<Arc<Metrics>>()`.
+#[derive(Debug)]
+pub struct MetricsMiddleware {
+    metrics: Arc<Metrics>,
+    patterns: Vec<&'static str>,
+}
+
+impl MetricsMiddleware {
+    // `patterns` são os caminhos das rotas registradas (ex: "/data/:id"),
+    // usados como label para não criar uma série por id.
+    pub fn new(metrics: Arc<Metrics>, patterns: Vec<&'static str>) -> Self {
+        MetricsMiddleware { metrics, patterns }
+    }
+
+    fn route_label(&self, path: &str) -> &'static str {
+        self.patterns
+            .iter()
+            .find(|pattern| path_matches(pattern, path))
+            .copied()
+            .unwrap_or("unmatched")
+    }
+}
+
+#[async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for MetricsMiddleware {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let method = req.method().to_string();
+        let route = self.route_label(req.url().path());
+        req.set_ext(self.metrics.clone());
+
+        let started = Instant::now();
+        let res = next.run(req).await;
+
+        let status: u16 = res.status().into();
+        self.metrics
+            .observe_request(&method, route, status, started.elapsed());
+        Ok(res)
+    }
+}
@@ -0,0 +1,8 @@
+pub mod auth;
+pub mod cluster;
+pub mod cors;
+pub mod idempotency;
+pub mod metrics;
+pub mod rate_limit;
+pub mod request_limits;
+pub mod request_log;
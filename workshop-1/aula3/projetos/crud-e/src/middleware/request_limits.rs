@@ -0,0 +1,291 @@
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll, ready};
+use std::time::{Duration, Instant};
+
+use async_io::Timer;
+use async_std::future;
+use futures_lite::io::BufReader;
+use futures_lite::{AsyncRead, AsyncReadExt};
+use tide::http::Method;
+use tide::http::headers::CONTENT_TYPE;
+use tide::utils::async_trait;
+use tide::{Body, Middleware, Next, Request, StatusCode};
+
+use crate::collections;
+use crate::config::LimitsConfig;
+use crate::routes::path_matches;
+
+// Limites de uma rota: tamanho máximo do body e prazo do handler
+// (`None` no prazo: sem limite de tempo)
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub max_body_bytes: u64,
+    pub deadline: Option<Duration>,
+}
+
+// Rotas cujo handler lê o body aos poucos: ele não é lido para a memória antes,
+// e os limites valem durante a leitura (ver `LimitedBody`)
+const STREAMING_ROUTES: [(Method, &str); 1] = [(Method::Post, "/data/_import")];
+
+// Fim do prazo do handler, guardado na requisição para quem precisa limitar o
+// próprio trabalho (ver src/handlers/execute.rs)
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline(pub Instant);
+
+// Associa um método HTTP + padrão de rota (ex: "/execute/:id") aos limites
+#[derive(Clone, Debug)]
+struct RouteLimits {
+    method: Method,
+    pattern: String,
+    limits: Limits,
+}
+
+// Middleware que protege os handlers de bodies grandes ou lentos e de
+// requisições que demoram demais:
+// - Content-Length acima do máximo da rota: 413 sem ler o body
+// - body lido inteiro em memória até o máximo (acima dele: 413) e dentro de
+//   `body_timeout` (estourou: 408), antes de chamar o handler; nas rotas em
+//   STREAMING_ROUTES, os mesmos limites são conferidos enquanto o handler lê
+// - handler que não responde dentro do prazo da rota: 504
+// As rotas /collections/:name/... usam os limites da rota /data... equivalente.
+#[derive(Debug)]
+pub struct RequestLimits {
+    defaults: Limits,
+    body_timeout: Option<Duration>,
+    routes: Vec<RouteLimits>,
+}
+
+impl RequestLimits {
+    // Cria o middleware com os limites da configuração (já validada)
+    pub fn from_config(config: &LimitsConfig) -> Self {
+        let defaults = Limits {
+            max_body_bytes: config.max_body_bytes,
+            deadline: secs(config.handler_timeout_secs),
+        };
+        let routes = config
+            .request_limits
+            .iter()
+            .filter_map(|route| {
+                let method = Method::from_str(&route.method).ok()?;
+                let limits = Limits {
+                    max_body_bytes: route.max_body_bytes.unwrap_or(defaults.max_body_bytes),
+                    deadline: route.timeout_secs.map_or(defaults.deadline, secs),
+                };
+                Some(RouteLimits {
+                    method,
+                    pattern: route.path.clone(),
+                    limits,
+                })
+            })
+            .collect();
+        RequestLimits {
+            defaults,
+            body_timeout: secs(config.body_timeout_secs),
+            routes,
+        }
+    }
+
+    // Limites da rota da requisição (os padrões se nenhuma rota casar)
+    fn limits_for(&self, method: Method, path: &str) -> Limits {
+        let path = collections::split_path(path).map_or(path, |(_, rest)| rest);
+        self.routes
+            .iter()
+            .find(|r| r.method == method && path_matches(&r.pattern, path))
+            .map_or(self.defaults, |r| r.limits)
+    }
+
+    fn is_streaming(method: Method, path: &str) -> bool {
+        let path = collections::split_path(path).map_or(path, |(_, rest)| rest);
+        STREAMING_ROUTES
+            .iter()
+            .any(|(m, pattern)| *m == method && path_matches(pattern, path))
+    }
+}
+
+#[async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for RequestLimits {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let limits = self.limits_for(req.method(), req.url().path());
+
+        if req
+            .len()
+            .is_some_and(|len| len as u64 > limits.max_body_bytes)
+        {
+            return Err(too_large(limits.max_body_bytes));
+        }
+        if req.len() != Some(0) {
+            if Self::is_streaming(req.method(), req.url().path()) {
+                limit_body(&mut req, limits.max_body_bytes, self.body_timeout);
+            } else {
+                buffer_body(&mut req, limits.max_body_bytes, self.body_timeout).await?;
+            }
+        }
+
+        let Some(deadline) = limits.deadline else {
+            return Ok(next.run(req).await);
+        };
+        req.set_ext(Deadline(Instant::now() + deadline));
+        future::timeout(deadline, next.run(req)).await.map_err(|_| {
+            tide::Error::from_str(
+                StatusCode::GatewayTimeout,
+                format!("Handler timeout: sem resposta em {}s", deadline.as_secs()),
+            )
+        })
+    }
+}
+
+// Lê o body inteiro (no máximo `max` bytes) e o devolve à requisição já em memória
+async fn buffer_body<State>(
+    req: &mut Request<State>,
+    max: u64,
+    timeout: Option<Duration>,
+) -> tide::Result<()> {
+    let mut bytes = Vec::new();
+    let mut body = req.take_body().take(max + 1);
+    let read = body.read_to_end(&mut bytes);
+    let result = match timeout {
+        Some(timeout) => future::timeout(timeout, read)
+            .await
+            .map_err(|_| body_timeout(timeout))?,
+        None => read.await,
+    };
+    result.map_err(body_error)?;
+    if bytes.len() as u64 > max {
+        return Err(too_large(max));
+    }
+
+    replace_body(req, bytes);
+    Ok(())
+}
+
+// Troca o body por um que falha ao passar de `max` bytes ou de `timeout`
+fn limit_body<State>(req: &mut Request<State>, max: u64, timeout: Option<Duration>) {
+    let len = req.len();
+    let limited = LimitedBody {
+        inner: req.take_body(),
+        max,
+        read: 0,
+        timer: timeout.map(|timeout| (Timer::after(timeout), timeout)),
+    };
+    set_body(req, Body::from_reader(BufReader::new(limited), len));
+}
+
+// Body lido pelo handler que conta os bytes e o tempo esperando a rede
+struct LimitedBody {
+    inner: Body,
+    max: u64,
+    read: u64,
+    timer: Option<(Timer, Duration)>,
+}
+
+impl AsyncRead for LimitedBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        // O prazo só conta quando falta chegar body: um handler lento para
+        // processar o que já chegou não é culpa do cliente
+        let read = match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(read) => read?,
+            Poll::Pending => {
+                if let Some((timer, timeout)) = &mut this.timer {
+                    ready!(Pin::new(timer).poll(cx));
+                    return Poll::Ready(Err(LimitExceeded::Timeout(*timeout).into()));
+                }
+                return Poll::Pending;
+            }
+        };
+        this.read += read as u64;
+        if this.read > this.max {
+            return Poll::Ready(Err(LimitExceeded::TooLarge(this.max).into()));
+        }
+        Poll::Ready(Ok(read))
+    }
+}
+
+// Limite estourado durante a leitura de um `LimitedBody`
+#[derive(Debug)]
+enum LimitExceeded {
+    TooLarge(u64),
+    Timeout(Duration),
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::TooLarge(max) => write!(f, "body acima de {max} bytes"),
+            LimitExceeded::Timeout(timeout) => {
+                write!(f, "body não recebido em {}s", timeout.as_secs())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl From<LimitExceeded> for io::Error {
+    fn from(err: LimitExceeded) -> Self {
+        io::Error::other(err)
+    }
+}
+
+// Erro de leitura do body em resposta: 413 ou 408 quando um limite estourou,
+// 400 nos demais casos
+pub fn body_error(err: io::Error) -> tide::Error {
+    match err
+        .get_ref()
+        .and_then(|e| e.downcast_ref::<LimitExceeded>())
+    {
+        Some(LimitExceeded::TooLarge(max)) => too_large(*max),
+        Some(LimitExceeded::Timeout(timeout)) => body_timeout(*timeout),
+        None => tide::Error::from_str(StatusCode::BadRequest, format!("Invalid body: {err}")),
+    }
+}
+
+// Como `body_error`, para os erros de `Request::body_bytes` e afins
+pub fn read_error(err: tide::Error) -> tide::Error {
+    match err.downcast::<io::Error>() {
+        Ok(err) => body_error(err),
+        Err(err) => err,
+    }
+}
+
+// Troca o body da requisição pelos bytes já lidos
+pub fn replace_body<State>(req: &mut Request<State>, bytes: Vec<u8>) {
+    set_body(req, Body::from_bytes(bytes));
+}
+
+// `set_body` preenche o Content-Type quando ele não veio; sem ele o handler
+// assume JSON (ver src/codec.rs), então o header volta a sair.
+fn set_body<State>(req: &mut Request<State>, body: Body) {
+    let had_content_type = req.header(CONTENT_TYPE).is_some();
+    req.set_body(body);
+    if !had_content_type {
+        req.remove_header(CONTENT_TYPE);
+    }
+}
+
+fn too_large(max: u64) -> tide::Error {
+    tide::Error::from_str(
+        StatusCode::PayloadTooLarge,
+        format!("Payload too large: máximo de {max} bytes"),
+    )
+}
+
+fn body_timeout(timeout: Duration) -> tide::Error {
+    tide::Error::from_str(
+        StatusCode::RequestTimeout,
+        format!("Body timeout: body não recebido em {}s", timeout.as_secs()),
+    )
+}
+
+// Segundos da configuração em Duration (0: sem limite)
+fn secs(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
@@ -0,0 +1,100 @@
+use std::time::Instant;
+
+use serde_json::json;
+use tide::http::mime;
+use tide::utils::async_trait;
+use tide::{Middleware, Next, Request, Response};
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+// Header usado para receber/propagar o id da requisição
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// Tamanho máximo aceito para um id recebido do cliente
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// Middleware de log estruturado.
+// Cada requisição roda dentro de um span `request` com o id, método, caminho,
+// status, latência e (quando houver) o id do registro acessado.
+#[derive(Debug, Default)]
+pub struct RequestLogger;
+
+impl RequestLogger {
+    pub fn new() -> Self {
+        RequestLogger
+    }
+}
+
+#[async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for RequestLogger {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        // Reaproveita o id enviado pelo cliente ou gera um novo
+        let request_id = req
+            .header(REQUEST_ID_HEADER)
+            .map(|values| values.last().as_str().to_string())
+            .filter(|id| is_valid_request_id(id))
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        // Os handlers preenchem `record_id` com `Span::current().record(...)`
+        let span = tracing::info_span!(
+            "request",
+            request_id = %request_id,
+            method = %req.method(),
+            path = %req.url().path(),
+            status = Empty,
+            latency_ms = Empty,
+            record_id = Empty,
+        );
+
+        let started = Instant::now();
+        let mut res = next.run(req).instrument(span.clone()).await;
+        let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let status: u16 = res.status().into();
+        span.record("status", status);
+        span.record("latency_ms", latency_ms);
+        let error = res.error().map(|err| err.to_string());
+        span.in_scope(|| match status {
+            500.. => tracing::error!(error, "request failed"),
+            400..=499 => tracing::warn!(error, "request rejected"),
+            _ => tracing::info!("request finished"),
+        });
+
+        if status >= 400 {
+            attach_error_body(&mut res, &request_id).await;
+        }
+        res.insert_header(REQUEST_ID_HEADER, request_id);
+        Ok(res)
+    }
+}
+
+// Registra o id do registro acessado no span da requisição atual
+pub fn record_id(id: u32) {
+    Span::current().record("record_id", id);
+}
+
+// Troca o corpo das respostas de erro por um JSON com a mensagem e o id da
+// requisição, para o suporte conseguir correlacionar os relatos com os logs.
+// Respostas que o handler já montou em JSON (ex: 409 do lote) ficam como estão.
+async fn attach_error_body(res: &mut Response, request_id: &str) {
+    if res.error().is_none() && res.content_type() == Some(mime::JSON) {
+        return;
+    }
+    let message = match res.error() {
+        Some(err) => err.to_string(),
+        None => {
+            let body = res.take_body().into_string().await.unwrap_or_default();
+            if body.is_empty() {
+                res.status().canonical_reason().to_string()
+            } else {
+                body
+            }
+        }
+    };
+    res.set_body(json!({ "error": message, "request_id": request_id }));
+}
+
+// Aceita apenas ids curtos com caracteres ASCII visíveis
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
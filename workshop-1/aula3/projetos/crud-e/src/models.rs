@@ -2,8 +2,12 @@
 // Ele será convertido automaticamente para JSON usando Serde.
 use serde::{Deserialize, Serialize};
 
+// Nome do campo de bytes (usado para convertê-lo em base64, ver src/codec.rs)
+pub const BYTES_FIELD: &str = "bytecode";
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct DataEntry {
     pub func_names: Vec<String>, // Lista de textos
-    pub bytecode: Vec<u8>,       // Lista de números inteiros (bytes)
+    #[serde(with = "crate::codec::bytes")]
+    pub bytecode: Vec<u8>, // Lista de números inteiros (bytes)
 }
@@ -0,0 +1,501 @@
+// Linguagem de consulta de GET /data/_query?q=.
+// Uma consulta compara campos derivados do registro com valores literais:
+//
+//     func_names contains "add" and size < 4096
+//     not (hash = "ab12...") or id >= 10
+//
+// Campos: id, func_names (lista), size (bytes do bytecode) e hash (sha256 do
+// bytecode, em hex). Operadores: = != < <= > >= contains, combinados com and,
+// or, not e parênteses. O planejador usa os índices secundários (ver
+// src/indexes.rs) quando pode e cai para a leitura de todos os registros quando não.
+use std::collections::HashSet;
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+use crate::indexes::SecondaryIndexes;
+use crate::models::DataEntry;
+
+// Campo derivado de um registro
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Field {
+    Id,
+    FuncNames,
+    Size,
+    Hash,
+}
+
+impl Field {
+    // Campos que podem ter índice secundário (o id já é a chave do mapa)
+    pub const INDEXABLE: [Field; 3] = [Field::FuncNames, Field::Size, Field::Hash];
+
+    pub fn parse(name: &str) -> Option<Field> {
+        match name {
+            "id" => Some(Field::Id),
+            "func_names" => Some(Field::FuncNames),
+            "size" => Some(Field::Size),
+            "hash" => Some(Field::Hash),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::FuncNames => "func_names",
+            Field::Size => "size",
+            Field::Hash => "hash",
+        }
+    }
+
+    // Campos com vários valores por registro
+    pub fn is_list(self) -> bool {
+        self == Field::FuncNames
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(self, Field::Id | Field::Size)
+    }
+
+    // Valores do campo no registro (um por nome de função em func_names)
+    pub fn values(self, id: u32, entry: &DataEntry) -> Vec<Value> {
+        match self {
+            Field::Id => vec![Value::Int(id as i64)],
+            Field::FuncNames => entry.func_names.iter().cloned().map(Value::Str).collect(),
+            Field::Size => vec![Value::Int(entry.bytecode.len() as i64)],
+            Field::Hash => vec![Value::Str(hex(&Sha256::digest(&entry.bytecode)))],
+        }
+    }
+}
+
+// Valor de um campo ou literal da consulta. A ordem (números antes de textos)
+// é a usada pelos índices.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Value {
+    Int(i64),
+    Str(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{n}"),
+            Value::Str(s) => write!(f, "{s:?}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Contains => "contains",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Compare { field: Field, op: Op, value: Value },
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+}
+
+impl Expr {
+    // Interpreta e confere os tipos de uma consulta
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let tokens = lex(text)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some((at, token)) => Err(format!("posição {at}: {token} inesperado")),
+        }
+    }
+
+    // Se o registro satisfaz a consulta.
+    // Em func_names a comparação vale se algum nome satisfaz ("!=": se nenhum é igual).
+    pub fn matches(&self, id: u32, entry: &DataEntry) -> bool {
+        match self {
+            Expr::Compare { field, op, value } => {
+                let values = field.values(id, entry);
+                match op {
+                    Op::Ne => !values.contains(value),
+                    Op::Contains if field.is_list() => values.contains(value),
+                    Op::Contains => match value {
+                        Value::Str(needle) => values
+                            .iter()
+                            .any(|v| matches!(v, Value::Str(s) if s.contains(needle.as_str()))),
+                        Value::Int(_) => false,
+                    },
+                    _ => values.iter().any(|v| compare(v, *op, value)),
+                }
+            }
+            Expr::And(a, b) => a.matches(id, entry) && b.matches(id, entry),
+            Expr::Or(a, b) => a.matches(id, entry) || b.matches(id, entry),
+            Expr::Not(a) => !a.matches(id, entry),
+        }
+    }
+}
+
+fn compare(left: &Value, op: Op, right: &Value) -> bool {
+    match op {
+        Op::Eq => left == right,
+        Op::Ne => left != right,
+        Op::Lt => left < right,
+        Op::Le => left <= right,
+        Op::Gt => left > right,
+        Op::Ge => left >= right,
+        Op::Contains => false,
+    }
+}
+
+// Como os registros candidatos são encontrados
+#[derive(Debug, PartialEq)]
+pub enum Plan {
+    // Lê todos os registros
+    Scan,
+    // Busca direta pelo id
+    Id(u32),
+    // Consulta um índice secundário
+    Index { field: Field, op: Op, value: Value },
+    // Registros presentes em todos os subplanos (and)
+    Intersect(Vec<Plan>),
+    // Registros presentes em algum subplano (or)
+    Union(Vec<Plan>),
+}
+
+// Escolhe o plano da consulta com os índices disponíveis.
+// O plano só reduz os candidatos: a consulta inteira ainda é avaliada em cada um.
+pub fn plan(expr: &Expr, indexes: &SecondaryIndexes) -> Plan {
+    match expr {
+        Expr::Compare {
+            field: Field::Id,
+            op: Op::Eq,
+            value: Value::Int(id),
+        } => u32::try_from(*id).map_or(Plan::Intersect(Vec::new()), Plan::Id),
+        Expr::Compare { field, op, value } if indexes.supports(*field, *op) => Plan::Index {
+            field: *field,
+            op: *op,
+            value: value.clone(),
+        },
+        // Em um and basta um lado indexado; os outros são filtrados depois
+        Expr::And(a, b) => match (plan(a, indexes), plan(b, indexes)) {
+            (Plan::Scan, other) | (other, Plan::Scan) => other,
+            (a, b) => Plan::Intersect(flatten(a, b, |p| match p {
+                Plan::Intersect(plans) => Ok(plans),
+                p => Err(p),
+            })),
+        },
+        // Em um or todos os lados precisam de índice
+        Expr::Or(a, b) => match (plan(a, indexes), plan(b, indexes)) {
+            (Plan::Scan, _) | (_, Plan::Scan) => Plan::Scan,
+            (a, b) => Plan::Union(flatten(a, b, |p| match p {
+                Plan::Union(plans) => Ok(plans),
+                p => Err(p),
+            })),
+        },
+        _ => Plan::Scan,
+    }
+}
+
+// Junta dois subplanos, abrindo os que já são do mesmo tipo
+fn flatten(a: Plan, b: Plan, open: impl Fn(Plan) -> Result<Vec<Plan>, Plan>) -> Vec<Plan> {
+    let mut plans = Vec::new();
+    for p in [a, b] {
+        match open(p) {
+            Ok(inner) => plans.extend(inner),
+            Err(p) => plans.push(p),
+        }
+    }
+    plans
+}
+
+impl Plan {
+    // Ids candidatos, ou None se todos os registros precisam ser lidos
+    pub fn candidates(&self, indexes: &SecondaryIndexes) -> Option<HashSet<u32>> {
+        match self {
+            Plan::Scan => None,
+            Plan::Id(id) => Some(HashSet::from([*id])),
+            Plan::Index { field, op, value } => indexes.lookup(*field, *op, value),
+            Plan::Intersect(plans) => {
+                let mut sets: Vec<HashSet<u32>> =
+                    plans.iter().filter_map(|p| p.candidates(indexes)).collect();
+                // Começa pelo menor conjunto
+                sets.sort_by_key(HashSet::len);
+                let mut sets = sets.into_iter();
+                let first = sets.next().unwrap_or_default();
+                Some(sets.fold(first, |acc, set| {
+                    acc.into_iter().filter(|id| set.contains(id)).collect()
+                }))
+            }
+            Plan::Union(plans) => {
+                let mut ids = HashSet::new();
+                for p in plans {
+                    ids.extend(p.candidates(indexes)?);
+                }
+                Some(ids)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter, name: &str, plans: &[Plan]| {
+            let parts: Vec<String> = plans.iter().map(Plan::to_string).collect();
+            write!(f, "{name}({})", parts.join(", "))
+        };
+        match self {
+            Plan::Scan => write!(f, "scan"),
+            Plan::Id(id) => write!(f, "id({id})"),
+            Plan::Index { field, op, value } => {
+                write!(f, "index({} {} {value})", field.name(), op.as_str())
+            }
+            Plan::Intersect(plans) => join(f, "intersect", plans),
+            Plan::Union(plans) => join(f, "union", plans),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Str(String),
+    Op(Op),
+    LParen,
+    RParen,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "{name:?}"),
+            Token::Int(n) => write!(f, "{n}"),
+            Token::Str(s) => write!(f, "{s:?}"),
+            Token::Op(op) => write!(f, "{:?}", op.as_str()),
+            Token::LParen => write!(f, "\"(\""),
+            Token::RParen => write!(f, "\")\""),
+        }
+    }
+}
+
+// Quebra a consulta em tokens, com a posição (em caracteres) de cada um
+fn lex(text: &str) -> Result<Vec<(usize, Token)>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        let token = match c {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '=' => Token::Op(Op::Eq),
+            '!' if chars.get(i + 1) == Some(&'=') => {
+                i += 1;
+                Token::Op(Op::Ne)
+            }
+            '<' | '>' => {
+                let or_equal = chars.get(i + 1) == Some(&'=');
+                if or_equal {
+                    i += 1;
+                }
+                Token::Op(match (c, or_equal) {
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    (_, false) => Op::Gt,
+                    (_, true) => Op::Ge,
+                })
+            }
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    i += 1;
+                    match chars.get(i) {
+                        None => {
+                            return Err(format!("posição {start}: texto sem aspas de fechamento"));
+                        }
+                        Some('"') => break,
+                        Some('\\') => {
+                            i += 1;
+                            match chars.get(i) {
+                                Some(c @ ('"' | '\\')) => s.push(*c),
+                                _ => return Err(format!("posição {i}: escape inválido")),
+                            }
+                        }
+                        Some(c) => s.push(*c),
+                    }
+                }
+                Token::Str(s)
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                while chars.get(i + 1).is_some_and(char::is_ascii_digit) {
+                    i += 1;
+                }
+                let digits: String = chars[start..=i].iter().collect();
+                Token::Int(
+                    digits
+                        .parse()
+                        .map_err(|_| format!("posição {start}: número inválido {digits:?}"))?,
+                )
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while chars
+                    .get(i + 1)
+                    .is_some_and(|c| c.is_alphanumeric() || *c == '_')
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..=i].iter().collect();
+                if word.eq_ignore_ascii_case("contains") {
+                    Token::Op(Op::Contains)
+                } else {
+                    Token::Ident(word)
+                }
+            }
+            c => return Err(format!("posição {start}: caractere inesperado {c:?}")),
+        };
+        tokens.push((start, token));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+// Analisador descendente recursivo. Precedência: not > and > or.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(usize, Token)> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<(usize, Token), String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| "consulta incompleta".to_string())?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    // Consome a palavra-chave (and, or, not), se for o próximo token
+    fn keyword(&mut self, word: &str) -> bool {
+        let found =
+            matches!(self.peek(), Some((_, Token::Ident(w))) if w.eq_ignore_ascii_case(word));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if matches!(self.peek(), Some((_, Token::LParen))) {
+            self.pos += 1;
+            let expr = self.or()?;
+            return match self.next()? {
+                (_, Token::RParen) => Ok(expr),
+                (at, token) => Err(format!("posição {at}: esperado \")\", encontrado {token}")),
+            };
+        }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> Result<Expr, String> {
+        let (at, name) = match self.next()? {
+            (at, Token::Ident(name)) => (at, name),
+            (at, token) => {
+                return Err(format!(
+                    "posição {at}: esperado um campo, encontrado {token}"
+                ));
+            }
+        };
+        let field = Field::parse(&name).ok_or_else(|| {
+            format!("posição {at}: campo desconhecido {name:?} (use id, func_names, size ou hash)")
+        })?;
+        let op = match self.next()? {
+            (_, Token::Op(op)) => op,
+            (at, token) => {
+                return Err(format!(
+                    "posição {at}: esperado um operador, encontrado {token}"
+                ));
+            }
+        };
+        let (at, value) = match self.next()? {
+            (at, Token::Int(n)) => (at, Value::Int(n)),
+            (at, Token::Str(s)) => (at, Value::Str(s)),
+            (at, token) => {
+                return Err(format!(
+                    "posição {at}: esperado um valor, encontrado {token}"
+                ));
+            }
+        };
+
+        // Confere os tipos: números com id/size, textos com func_names/hash
+        let numeric = matches!(value, Value::Int(_));
+        if field.is_numeric() != numeric {
+            let expected = if field.is_numeric() {
+                "um número"
+            } else {
+                "um texto"
+            };
+            return Err(format!("posição {at}: {} espera {expected}", field.name()));
+        }
+        if op == Op::Contains && field.is_numeric() {
+            return Err(format!(
+                "posição {at}: contains não se aplica a {}",
+                field.name()
+            ));
+        }
+        Ok(Expr::Compare { field, op, value })
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
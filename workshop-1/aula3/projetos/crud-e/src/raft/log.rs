@@ -0,0 +1,348 @@
+// O que o nó precisa guardar em disco, em `cluster.data_dir`/node-<id>:
+// - state.json: mandato atual e em quem votou (gravado antes de responder)
+// - log.jsonl: as entradas depois do snapshot, uma por linha, com fsync a cada acréscimo
+// - snapshot: uma linha com a última entrada incluída, seguida do estado em JSON
+// Uma linha final pela metade (queda no meio da escrita) é descartada ao abrir.
+// As mudanças são feitas primeiro na memória, com a trava do nó, e gravadas por
+// Node::persist sem ela.
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::write_atomic;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub index: u64,
+    pub term: u64,
+    pub command: Command,
+}
+
+// Marcado por fora ({"request": {...}}): o formato com a tag dentro do objeto
+// não aceita a semente em u128
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Command {
+    // Gravada por cada líder novo, para confirmar as entradas dos mandatos anteriores
+    Noop,
+    // Requisição de escrita, repetida em todos os nós
+    Request(WriteRequest),
+}
+
+// Uma requisição de escrita como chegou ao líder
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WriteRequest {
+    pub method: String,
+    // Caminho com a query string
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    // Body em base64
+    pub body: String,
+    // Horário e semente usados por todos os nós ao aplicar (ver state::with_apply_context)
+    pub timestamp_ms: u64,
+    pub seed: u128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HardState {
+    pub term: u64,
+    pub voted_for: Option<u64>,
+}
+
+// Última entrada incluída no snapshot
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotMeta {
+    pub last_index: u64,
+    pub last_term: u64,
+}
+
+// Entradas e snapshot na memória. As mudanças vão para o disco depois, fora da
+// trava do nó (ver `plan`, `Disk::write` e Node::persist); `durable` diz até onde
+// o disco já acompanha a memória.
+pub struct RaftLog {
+    snapshot: SnapshotMeta,
+    snapshot_data: Arc<Vec<u8>>,
+    // Entradas depois do snapshot, em ordem e sem buracos
+    entries: Vec<Entry>,
+    // Última entrada que já está em disco
+    durable: u64,
+    // Muda a cada truncamento ou compactação: o arquivo de outra geração precisa
+    // ser reescrito
+    generation: u64,
+}
+
+// O que falta gravar para o disco alcançar a memória
+pub struct Plan {
+    hard_state: Option<HardState>,
+    snapshot: Option<(SnapshotMeta, Arc<Vec<u8>>)>,
+    // Com `rewrite`, todas as entradas; sem, só as que faltam no fim do arquivo
+    entries: Vec<Entry>,
+    rewrite: bool,
+    generation: u64,
+    last_index: u64,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.hard_state.is_none()
+            && self.snapshot.is_none()
+            && self.entries.is_empty()
+            && !self.rewrite
+    }
+}
+
+// Arquivos do nó e o que já está neles. Só é usado por uma gravação de cada
+// vez, fora das threads do executor.
+pub struct Disk {
+    dir: PathBuf,
+    file: File,
+    hard_state: HardState,
+    snapshot: SnapshotMeta,
+    // Geração do log (ver RaftLog) e última entrada do arquivo
+    generation: u64,
+    last_index: u64,
+    // Uma escrita falhou no meio: o próximo plano reescreve o arquivo inteiro
+    broken: bool,
+}
+
+impl Disk {
+    pub fn hard_state(&self) -> HardState {
+        self.hard_state
+    }
+
+    // Grava o plano: mandato e voto, snapshot e entradas, nessa ordem
+    pub fn write(&mut self, plan: &Plan) -> io::Result<()> {
+        if let Some(hard_state) = plan.hard_state {
+            write_atomic(
+                &self.dir.join("state.json"),
+                &serde_json::to_vec(&hard_state)?,
+            )?;
+            self.hard_state = hard_state;
+        }
+        let result = self.write_log(plan);
+        self.broken = result.is_err();
+        result
+    }
+
+    fn write_log(&mut self, plan: &Plan) -> io::Result<()> {
+        if let Some((meta, data)) = &plan.snapshot {
+            let mut bytes = serde_json::to_vec(meta)?;
+            bytes.push(b'\n');
+            bytes.extend_from_slice(data);
+            write_atomic(&self.dir.join("snapshot"), &bytes)?;
+            self.snapshot = *meta;
+        }
+        let lines = encode(&plan.entries)?;
+        if plan.rewrite {
+            let path = self.dir.join("log.jsonl");
+            write_atomic(&path, &lines)?;
+            self.file = append_handle(&path)?;
+        } else if !lines.is_empty() {
+            self.file.write_all(&lines)?;
+            self.file.sync_data()?;
+        }
+        self.generation = plan.generation;
+        self.last_index = plan.last_index;
+        Ok(())
+    }
+}
+
+impl RaftLog {
+    pub fn open(dir: &Path) -> io::Result<(RaftLog, Disk)> {
+        fs::create_dir_all(dir)?;
+
+        let hard_state = match fs::read(dir.join("state.json")) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HardState::default(),
+            Err(err) => return Err(err),
+        };
+
+        let (snapshot, snapshot_data) = match fs::read(dir.join("snapshot")) {
+            Ok(bytes) => {
+                let split = bytes
+                    .iter()
+                    .position(|b| *b == b'\n')
+                    .unwrap_or(bytes.len());
+                let meta: SnapshotMeta = serde_json::from_slice(&bytes[..split])?;
+                let data = bytes.get(split + 1..).unwrap_or_default().to_vec();
+                (meta, data)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                (SnapshotMeta::default(), Vec::new())
+            }
+            Err(err) => return Err(err),
+        };
+
+        let path = dir.join("log.jsonl");
+        let mut entries = Vec::new();
+        let mut torn = false;
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let entry = line
+                    .ok()
+                    .and_then(|line| serde_json::from_str::<Entry>(&line).ok());
+                let expected = entries
+                    .last()
+                    .map_or(snapshot.last_index + 1, |last: &Entry| last.index + 1);
+                match entry {
+                    // Sobras de antes do último snapshot (queda entre salvar e reescrever)
+                    Some(entry) if entry.index <= snapshot.last_index && entries.is_empty() => {}
+                    Some(entry) if entry.index == expected => entries.push(entry),
+                    _ => {
+                        torn = true;
+                        break;
+                    }
+                }
+            }
+        }
+
+        let durable = entries.last().map_or(snapshot.last_index, |e| e.index);
+        let mut disk = Disk {
+            dir: dir.to_path_buf(),
+            file: append_handle(&path)?,
+            hard_state,
+            snapshot,
+            generation: 0,
+            last_index: durable,
+            broken: false,
+        };
+        if torn {
+            tracing::warn!(
+                path = %path.display(),
+                last_index = durable,
+                "final do log do Raft corrompido; descartado"
+            );
+            write_atomic(&path, &encode(&entries)?)?;
+            disk.file = append_handle(&path)?;
+        }
+        let log = RaftLog {
+            snapshot,
+            snapshot_data: Arc::new(snapshot_data),
+            entries,
+            durable,
+            generation: 0,
+        };
+        Ok((log, disk))
+    }
+
+    pub fn snapshot(&self) -> SnapshotMeta {
+        self.snapshot
+    }
+
+    pub fn snapshot_data(&self) -> Arc<Vec<u8>> {
+        self.snapshot_data.clone()
+    }
+
+    pub fn last_index(&self) -> u64 {
+        self.entries
+            .last()
+            .map_or(self.snapshot.last_index, |e| e.index)
+    }
+
+    pub fn last_term(&self) -> u64 {
+        self.entries
+            .last()
+            .map_or(self.snapshot.last_term, |e| e.term)
+    }
+
+    // Última entrada que já está em disco
+    pub fn durable_index(&self) -> u64 {
+        self.durable
+    }
+
+    // Mandato da entrada `index` (None quando ela não existe ou já foi compactada,
+    // exceto a última do snapshot)
+    pub fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot.last_index {
+            return Some(self.snapshot.last_term);
+        }
+        self.entry(index).map(|e| e.term)
+    }
+
+    pub fn entry(&self, index: u64) -> Option<&Entry> {
+        let offset = index.checked_sub(self.snapshot.last_index + 1)?;
+        self.entries.get(offset as usize)
+    }
+
+    // Até `max` entradas a partir de `index`
+    pub fn entries_from(&self, index: u64, max: usize) -> Vec<Entry> {
+        let offset = index.saturating_sub(self.snapshot.last_index + 1) as usize;
+        self.entries
+            .iter()
+            .skip(offset)
+            .take(max)
+            .cloned()
+            .collect()
+    }
+
+    // Acrescenta as entradas no fim do log (só na memória)
+    pub fn append(&mut self, entries: Vec<Entry>) {
+        self.entries.extend(entries);
+    }
+
+    // Apaga as entradas a partir de `index` (conflito com o log do líder)
+    pub fn truncate_from(&mut self, index: u64) {
+        let keep = index.saturating_sub(self.snapshot.last_index + 1) as usize;
+        self.entries.truncate(keep);
+        self.durable = self.durable.min(index.saturating_sub(1));
+        self.generation += 1;
+    }
+
+    // Troca o snapshot e descarta as entradas que ele cobre. Quando o log não tem a
+    // última entrada do snapshot (snapshot instalado pelo líder), descarta tudo.
+    pub fn compact(&mut self, meta: SnapshotMeta, data: Vec<u8>) {
+        if self.term_at(meta.last_index) == Some(meta.last_term) {
+            let covered = (meta.last_index - self.snapshot.last_index) as usize;
+            self.entries.drain(..covered.min(self.entries.len()));
+        } else {
+            self.entries.clear();
+            self.durable = self.durable.min(meta.last_index);
+        }
+        self.snapshot = meta;
+        self.snapshot_data = Arc::new(data);
+        self.generation += 1;
+    }
+
+    // O que falta gravar para o disco alcançar a memória (`hard_state`: mandato
+    // e voto, quando mudaram)
+    pub fn plan(&self, hard_state: Option<HardState>, disk: &Disk) -> Plan {
+        let rewrite = disk.broken || disk.generation != self.generation;
+        let entries = if rewrite {
+            self.entries.clone()
+        } else {
+            self.entries_from(disk.last_index + 1, usize::MAX)
+        };
+        Plan {
+            hard_state,
+            snapshot: (disk.snapshot != self.snapshot)
+                .then(|| (self.snapshot, self.snapshot_data.clone())),
+            entries,
+            rewrite,
+            generation: self.generation,
+            last_index: self.last_index(),
+        }
+    }
+
+    // Acompanha o que já chegou ao disco
+    pub fn persisted(&mut self, disk: &Disk) {
+        if disk.generation == self.generation && !disk.broken {
+            self.durable = self.durable.max(disk.last_index);
+        }
+    }
+}
+
+fn encode(entries: &[Entry]) -> io::Result<Vec<u8>> {
+    let mut lines = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut lines, entry)?;
+        lines.push(b'\n');
+    }
+    Ok(lines)
+}
+
+fn append_handle(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
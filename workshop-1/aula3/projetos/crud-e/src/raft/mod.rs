@@ -0,0 +1,955 @@
+// Replicação do estado com Raft, entre os nós de `[cluster]`.
+//
+// As escritas da API viram entradas do log (o método, o caminho, os headers e o
+// body da requisição, mais um horário e uma semente). Depois de confirmada pela
+// maioria, cada nó aplica a entrada passando a requisição pelas mesmas rotas
+// (sem os middlewares), com o horário e a semente fixados: todos chegam ao mesmo
+// estado, inclusive ids, UUIDs e horários gravados. O líder devolve ao cliente a
+// resposta da própria aplicação.
+//
+// A lixeira também expira pelo log: antes de cada escrita, o nó apaga de vez o
+// que passou da retenção no horário da entrada (e não no próprio relógio), então
+// todos apagam os mesmos registros no mesmo ponto. Sem escritas, nada expira.
+//
+// Ficam fora do log e são de cada nó: os feeds de mudanças e o registro das
+// entregas de webhooks. Só o líder entrega webhooks.
+//
+// Tarefas de cada nó: o temporizador da eleição, um replicador por nó vizinho
+// (entradas, heartbeats e snapshots) e o aplicador das entradas confirmadas.
+// A cada `snapshot_threshold` entradas aplicadas o nó salva um snapshot do estado
+// e descarta o começo do log.
+mod log;
+mod rpc;
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use async_std::channel::{self, Receiver, Sender};
+use async_std::net::TcpListener;
+use async_std::{future, task};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::Serialize;
+use tide::http::{self, Method, Url};
+use tide::{Endpoint, Response, Server};
+use uuid::Uuid;
+
+pub use log::WriteRequest;
+use log::{Command, Disk, Entry, HardState, RaftLog, SnapshotMeta};
+use rpc::{AppendReply, AppendRequest, SnapshotReply, SnapshotRequest, VoteReply, VoteRequest};
+
+use crate::client::Client;
+use crate::config::{ClusterConfig, PeerConfig};
+use crate::state::{self, AppState};
+use crate::sync::Mutex;
+use crate::{routes, storage, trash};
+
+// Intervalo do temporizador da eleição e das esperas por commit/aplicação
+const TICK: Duration = Duration::from_millis(10);
+const POLL: Duration = Duration::from_millis(2);
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+// Por que este nó não atendeu uma escrita ou leitura linearizável
+#[derive(Debug)]
+pub enum Refusal {
+    // Não é o líder; traz o líder atual, quando conhecido
+    NotLeader(Option<PeerConfig>),
+    // É o líder, mas não conseguiu confirmar a tempo (maioria fora do ar, eleição...)
+    Unavailable(&'static str),
+}
+
+#[derive(Serialize)]
+pub struct Status {
+    pub node_id: u64,
+    pub role: Role,
+    pub term: u64,
+    pub leader_id: Option<u64>,
+    pub leader_url: Option<String>,
+    pub commit_index: u64,
+    pub last_applied: u64,
+    pub last_log_index: u64,
+    pub snapshot_index: u64,
+    pub peers: Vec<PeerStatus>,
+}
+
+#[derive(Serialize)]
+pub struct PeerStatus {
+    pub id: u64,
+    pub api_url: String,
+    // Última entrada sabidamente replicada no nó (só no líder)
+    pub match_index: Option<u64>,
+}
+
+pub struct Node {
+    id: u64,
+    config: ClusterConfig,
+    // Os outros nós do cluster
+    peers: Vec<PeerConfig>,
+    state: AppState,
+    // Rotas usadas para aplicar as entradas (sem middlewares)
+    machine: Server<AppState>,
+    client: Client,
+    core: Mutex<Core>,
+    // Arquivos do log; uma gravação de cada vez (`persist_lock`), fora de `core`
+    disk: Arc<Mutex<Disk>>,
+    persist_lock: async_lock::Mutex<()>,
+    persist_kick: (Sender<()>, Receiver<()>),
+    // Aplicar entradas e instalar snapshots nunca acontecem ao mesmo tempo
+    apply_lock: async_lock::Mutex<()>,
+    apply_kick: (Sender<()>, Receiver<()>),
+    replicate_kicks: HashMap<u64, (Sender<()>, Receiver<()>)>,
+    stopped: AtomicBool,
+}
+
+struct Core {
+    role: Role,
+    term: u64,
+    voted_for: Option<u64>,
+    leader: Option<u64>,
+    log: RaftLog,
+    commit_index: u64,
+    last_applied: u64,
+    election_deadline: Instant,
+    // Estado do líder, por nó vizinho
+    next_index: HashMap<u64, u64>,
+    match_index: HashMap<u64, u64>,
+    // Rodadas de heartbeat das leituras linearizáveis (ReadIndex): cada leitura
+    // abre uma rodada e espera a maioria responder a ela ou a uma posterior
+    heartbeat_round: u64,
+    acked_round: HashMap<u64, u64>,
+    // Quem espera a aplicação de cada entrada proposta por este líder
+    waiters: HashMap<u64, Sender<tide::Result>>,
+}
+
+impl Node {
+    // Abre o log do nó, restaura o último snapshot, passa a atender as RPCs em
+    // `peer_url` e inicia as tarefas de fundo. Entradas confirmadas depois do
+    // snapshot são aplicadas de novo quando o líder informar o commit.
+    pub async fn start(config: &ClusterConfig, state: AppState) -> io::Result<Arc<Node>> {
+        let id = config.node_id.expect("cluster.node_id validado");
+        let me = config
+            .peers
+            .iter()
+            .find(|peer| peer.id == id)
+            .expect("cluster.node_id presente em cluster.peers");
+        let (log, disk) = RaftLog::open(&config.data_dir.join(format!("node-{id}")))?;
+        let hard_state = disk.hard_state();
+        let snapshot = log.snapshot();
+        if snapshot.last_index > 0 {
+            state.restore(storage::decode_snapshot(&log.snapshot_data())?);
+        }
+        // Só o líder entrega webhooks
+        state.webhooks.set_active(false);
+
+        let listener = TcpListener::bind(listen_address(&me.peer_url)?).await?;
+        let peers: Vec<PeerConfig> = config
+            .peers
+            .iter()
+            .filter(|p| p.id != id)
+            .cloned()
+            .collect();
+        let mut machine = tide::with_state(state.clone());
+        routes::register(&mut machine);
+
+        let node = Arc::new(Node {
+            id,
+            peers: peers.clone(),
+            state: state.clone(),
+            machine,
+            client: Client::new(None),
+            core: Mutex::new(Core {
+                role: Role::Follower,
+                term: hard_state.term,
+                voted_for: hard_state.voted_for,
+                leader: None,
+                log,
+                commit_index: snapshot.last_index,
+                last_applied: snapshot.last_index,
+                election_deadline: Instant::now() + election_timeout(config),
+                next_index: HashMap::new(),
+                match_index: HashMap::new(),
+                heartbeat_round: 0,
+                acked_round: HashMap::new(),
+                waiters: HashMap::new(),
+            }),
+            disk: Arc::new(Mutex::new(disk)),
+            persist_lock: async_lock::Mutex::new(()),
+            persist_kick: channel::bounded(1),
+            apply_lock: async_lock::Mutex::new(()),
+            apply_kick: channel::bounded(1),
+            replicate_kicks: peers.iter().map(|p| (p.id, channel::bounded(1))).collect(),
+            stopped: AtomicBool::new(false),
+            config: config.clone(),
+        });
+        state.set_cluster(&node);
+
+        task::spawn(rpc::serve(node.clone(), listener));
+        task::spawn(node.clone().run_election_timer());
+        task::spawn(node.clone().run_applier());
+        task::spawn(node.clone().run_persister());
+        for peer in peers {
+            task::spawn(node.clone().run_replicator(peer));
+        }
+        tracing::info!(
+            node_id = id,
+            peer_url = %me.peer_url,
+            term = hard_state.term,
+            snapshot_index = snapshot.last_index,
+            "nó do cluster iniciado"
+        );
+        Ok(node)
+    }
+
+    // Para as tarefas do nó; as RPCs recebidas depois disso respondem 503
+    pub fn shutdown(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        let mut core = self.core.lock();
+        if core.role == Role::Leader {
+            self.state.webhooks.set_active(false);
+            core.waiters.clear();
+        }
+        core.role = Role::Follower;
+        core.leader = None;
+        let _ = self.apply_kick.0.try_send(());
+        tracing::info!(node_id = self.id, "nó do cluster parado");
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    pub fn status(&self) -> Status {
+        let core = self.core.lock();
+        let leader = core
+            .leader
+            .and_then(|id| self.config.peers.iter().find(|p| p.id == id));
+        Status {
+            node_id: self.id,
+            role: core.role,
+            term: core.term,
+            leader_id: core.leader,
+            leader_url: leader.map(|p| p.api_url.clone()),
+            commit_index: core.commit_index,
+            last_applied: core.last_applied,
+            last_log_index: core.log.last_index(),
+            snapshot_index: core.log.snapshot().last_index,
+            peers: self
+                .peers
+                .iter()
+                .map(|peer| PeerStatus {
+                    id: peer.id,
+                    api_url: peer.api_url.clone(),
+                    match_index: (core.role == Role::Leader)
+                        .then(|| core.match_index.get(&peer.id).copied().unwrap_or(0)),
+                })
+                .collect(),
+        }
+    }
+
+    // Grava a escrita no log, espera a maioria confirmar e devolve a resposta
+    // da aplicação da entrada neste nó
+    pub async fn propose(&self, write: WriteRequest) -> Result<tide::Result, Refusal> {
+        let (index, receiver) = {
+            let mut core = self.core.lock();
+            if core.role != Role::Leader {
+                return Err(Refusal::NotLeader(self.leader_peer(&core)));
+            }
+            let index = core.log.last_index() + 1;
+            let entry = Entry {
+                index,
+                term: core.term,
+                command: Command::Request(write),
+            };
+            core.log.append(vec![entry]);
+            let (sender, receiver) = channel::bounded(1);
+            core.waiters.insert(index, sender);
+            (index, receiver)
+        };
+        // Os vizinhos já recebem a entrada enquanto ela vai para o disco daqui
+        self.kick_replicators();
+        if let Err(err) = self.persist().await {
+            tracing::error!(error = %err, "falha ao gravar no log do Raft");
+            self.core.lock().waiters.remove(&index);
+            return Err(Refusal::Unavailable(
+                "Raft log write failed: a escrita pode ou não ter sido aplicada",
+            ));
+        }
+
+        match future::timeout(self.commit_timeout(), receiver.recv()).await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(_)) => Err(Refusal::Unavailable(
+                "Leadership lost: a escrita pode ou não ter sido aplicada",
+            )),
+            Err(_) => {
+                self.core.lock().waiters.remove(&index);
+                Err(Refusal::Unavailable(
+                    "Commit timeout: a escrita pode ou não ter sido aplicada",
+                ))
+            }
+        }
+    }
+
+    // Garante que uma leitura feita agora neste nó vê todas as escritas já
+    // confirmadas (ReadIndex): o líder confirma que ainda é líder com a maioria
+    // e espera aplicar até o commit do momento da leitura
+    pub async fn read_barrier(&self) -> Result<(), Refusal> {
+        let deadline = Instant::now() + self.commit_timeout();
+        let (read_index, round, term) = loop {
+            {
+                let mut core = self.core.lock();
+                if core.role != Role::Leader {
+                    return Err(Refusal::NotLeader(self.leader_peer(&core)));
+                }
+                // O commit só é confiável depois de o líder confirmar uma entrada do
+                // próprio mandato (o Noop gravado ao assumir)
+                if core.log.term_at(core.commit_index) == Some(core.term) {
+                    core.heartbeat_round += 1;
+                    break (core.commit_index, core.heartbeat_round, core.term);
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(Refusal::Unavailable("Leader not ready: líder recém-eleito"));
+            }
+            task::sleep(POLL).await;
+        };
+
+        if !self.peers.is_empty() {
+            self.kick_replicators();
+            loop {
+                {
+                    let core = self.core.lock();
+                    if core.role != Role::Leader || core.term != term {
+                        return Err(Refusal::NotLeader(self.leader_peer(&core)));
+                    }
+                    let acks = 1 + core.acked_round.values().filter(|r| **r >= round).count();
+                    if acks > self.cluster_size() / 2 {
+                        break;
+                    }
+                }
+                if Instant::now() >= deadline {
+                    return Err(Refusal::Unavailable(
+                        "No quorum: a maioria do cluster não respondeu",
+                    ));
+                }
+                task::sleep(POLL).await;
+            }
+        }
+
+        while self.core.lock().last_applied < read_index {
+            if Instant::now() >= deadline {
+                return Err(Refusal::Unavailable("Apply timeout: nó atrasado"));
+            }
+            task::sleep(POLL).await;
+        }
+        Ok(())
+    }
+
+    fn cluster_size(&self) -> usize {
+        self.peers.len() + 1
+    }
+
+    fn rpc_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.rpc_timeout_ms)
+    }
+
+    fn commit_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.commit_timeout_ms)
+    }
+
+    fn leader_peer(&self, core: &Core) -> Option<PeerConfig> {
+        let leader = core.leader.filter(|id| *id != self.id)?;
+        self.peers.iter().find(|p| p.id == leader).cloned()
+    }
+
+    fn kick_replicators(&self) {
+        for (sender, _) in self.replicate_kicks.values() {
+            let _ = sender.try_send(());
+        }
+    }
+
+    // Grava em disco o que mudou no log, no mandato e no voto, sem travar `core`
+    // durante a escrita. Quando volta Ok, tudo o que estava na memória ao chamar
+    // já está em disco.
+    async fn persist(&self) -> io::Result<()> {
+        let _persisting = self.persist_lock.lock().await;
+        let plan = {
+            let mut core = self.core.lock();
+            let disk = self.disk.lock();
+            // Uma gravação anterior pode ter terminado depois de quem a pediu desistir
+            self.mark_persisted(&mut core, &disk);
+            let hard_state = HardState {
+                term: core.term,
+                voted_for: core.voted_for,
+            };
+            let changed = (hard_state != disk.hard_state()).then_some(hard_state);
+            core.log.plan(changed, &disk)
+        };
+        if plan.is_empty() {
+            return Ok(());
+        }
+        let disk = self.disk.clone();
+        task::spawn_blocking(move || disk.lock().write(&plan)).await?;
+        let mut core = self.core.lock();
+        self.mark_persisted(&mut core, &self.disk.lock());
+        Ok(())
+    }
+
+    fn mark_persisted(&self, core: &mut Core, disk: &Disk) {
+        core.log.persisted(disk);
+        // O líder só conta a própria cópia depois de ela chegar ao disco
+        if core.role == Role::Leader {
+            self.advance_commit(core);
+        }
+    }
+
+    // Pede uma gravação em segundo plano (quando ninguém espera por ela para responder)
+    fn kick_persist(&self) {
+        let _ = self.persist_kick.0.try_send(());
+    }
+
+    async fn run_persister(self: Arc<Self>) {
+        let kick = self.persist_kick.1.clone();
+        while kick.recv().await.is_ok() && !self.is_stopped() {
+            if let Err(err) = self.persist().await {
+                tracing::error!(error = %err, "falha ao gravar o log do Raft");
+            }
+        }
+    }
+
+    // Passa a seguidor; com um mandato maior, esquece o voto do mandato anterior
+    fn step_down(&self, core: &mut Core, term: u64) {
+        if term > core.term {
+            core.term = term;
+            core.voted_for = None;
+            core.leader = None;
+            self.kick_persist();
+        }
+        if core.role == Role::Leader {
+            tracing::info!(node_id = self.id, term, "deixou de ser líder");
+            self.state.webhooks.set_active(false);
+            // Quem esperava uma escrita recebe 503 (ela pode ainda ser confirmada)
+            core.waiters.clear();
+        }
+        core.role = Role::Follower;
+    }
+
+    fn become_leader(&self, core: &mut Core) {
+        tracing::info!(node_id = self.id, term = core.term, "eleito líder");
+        core.role = Role::Leader;
+        core.leader = Some(self.id);
+        let next = core.log.last_index() + 1;
+        for peer in &self.peers {
+            core.next_index.insert(peer.id, next);
+            core.match_index.insert(peer.id, 0);
+            core.acked_round.insert(peer.id, 0);
+        }
+        let noop = Entry {
+            index: next,
+            term: core.term,
+            command: Command::Noop,
+        };
+        core.log.append(vec![noop]);
+        self.state.webhooks.set_active(true);
+        self.kick_persist();
+        self.kick_replicators();
+    }
+
+    // Avança o commit até a maior entrada do mandato atual que a maioria já tem
+    fn advance_commit(&self, core: &mut Core) {
+        let mut matched: Vec<u64> = self
+            .peers
+            .iter()
+            .map(|p| core.match_index.get(&p.id).copied().unwrap_or(0))
+            .chain([core.log.durable_index()])
+            .collect();
+        matched.sort_unstable_by(|a, b| b.cmp(a));
+        let majority = matched[self.cluster_size() / 2];
+        if majority > core.commit_index && core.log.term_at(majority) == Some(core.term) {
+            core.commit_index = majority;
+            let _ = self.apply_kick.0.try_send(());
+        }
+    }
+
+    fn reset_election_deadline(&self, core: &mut Core) {
+        core.election_deadline = Instant::now() + election_timeout(&self.config);
+    }
+
+    async fn run_election_timer(self: Arc<Self>) {
+        while !self.is_stopped() {
+            task::sleep(TICK).await;
+            let request = {
+                let mut core = self.core.lock();
+                if self.is_stopped()
+                    || core.role == Role::Leader
+                    || Instant::now() < core.election_deadline
+                {
+                    continue;
+                }
+                core.term += 1;
+                core.role = Role::Candidate;
+                core.voted_for = Some(self.id);
+                core.leader = None;
+                self.reset_election_deadline(&mut core);
+                tracing::info!(node_id = self.id, term = core.term, "iniciando eleição");
+                if self.peers.is_empty() {
+                    self.become_leader(&mut core);
+                    continue;
+                }
+                VoteRequest {
+                    term: core.term,
+                    candidate_id: self.id,
+                    last_log_index: core.log.last_index(),
+                    last_log_term: core.log.last_term(),
+                }
+            };
+            task::spawn(self.clone().request_votes(request));
+        }
+    }
+
+    async fn request_votes(self: Arc<Self>, request: VoteRequest) {
+        // O novo mandato e o voto em si mesmo vão para o disco antes dos pedidos
+        if let Err(err) = self.persist().await {
+            tracing::error!(error = %err, "falha ao gravar o estado do Raft");
+            return;
+        }
+        let (sender, receiver) = channel::unbounded();
+        for peer in self.peers.clone() {
+            let node = self.clone();
+            let sender = sender.clone();
+            let request = request.clone();
+            task::spawn(async move {
+                let reply = rpc::request_vote(&node, &peer, &request).await;
+                let _ = sender.send(reply).await;
+            });
+        }
+        drop(sender);
+
+        let mut votes = 1;
+        while let Ok(reply) = receiver.recv().await {
+            let reply = match reply {
+                Ok(reply) => reply,
+                Err(err) => {
+                    tracing::debug!(error = %err, "pedido de voto sem resposta");
+                    continue;
+                }
+            };
+            let mut core = self.core.lock();
+            if reply.term > core.term {
+                self.step_down(&mut core, reply.term);
+                return;
+            }
+            if core.role != Role::Candidate || core.term != request.term {
+                return;
+            }
+            if reply.vote_granted {
+                votes += 1;
+                if votes > self.cluster_size() / 2 {
+                    self.become_leader(&mut core);
+                    return;
+                }
+            }
+        }
+    }
+
+    // Mantém um vizinho em dia: manda as entradas que faltam (ou o snapshot,
+    // quando elas já foram compactadas) e um heartbeat a cada intervalo
+    async fn run_replicator(self: Arc<Self>, peer: PeerConfig) {
+        let kick = self.replicate_kicks[&peer.id].1.clone();
+        let heartbeat = Duration::from_millis(self.config.heartbeat_interval_ms);
+        while !self.is_stopped() {
+            let _ = future::timeout(heartbeat, kick.recv()).await;
+            let message = {
+                let core = self.core.lock();
+                if core.role != Role::Leader || self.is_stopped() {
+                    continue;
+                }
+                self.next_message(&core, peer.id)
+            };
+            match message {
+                Message::Append(request, round) => {
+                    match rpc::append_entries(&self, &peer, &request).await {
+                        Ok(reply) => self.handle_append_reply(&peer, &request, round, reply),
+                        Err(err) => {
+                            tracing::debug!(peer = peer.id, error = %err, "AppendEntries sem resposta")
+                        }
+                    }
+                }
+                Message::Snapshot(request, data) => {
+                    match rpc::install_snapshot(&self, &peer, &request, data).await {
+                        Ok(reply) => {
+                            tracing::info!(
+                                peer = peer.id,
+                                last_index = request.last_index,
+                                "snapshot enviado"
+                            );
+                            self.handle_snapshot_reply(&peer, &request, reply)
+                        }
+                        Err(err) => {
+                            tracing::debug!(peer = peer.id, error = %err, "falha ao enviar o snapshot")
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn next_message(&self, core: &Core, peer: u64) -> Message {
+        let next = core.next_index.get(&peer).copied().unwrap_or(1);
+        let snapshot = core.log.snapshot();
+        if next <= snapshot.last_index {
+            let request = SnapshotRequest {
+                term: core.term,
+                leader_id: self.id,
+                last_index: snapshot.last_index,
+                last_term: snapshot.last_term,
+            };
+            return Message::Snapshot(request, core.log.snapshot_data());
+        }
+        let prev_log_index = next - 1;
+        let request = AppendRequest {
+            term: core.term,
+            leader_id: self.id,
+            prev_log_index,
+            prev_log_term: core.log.term_at(prev_log_index).unwrap_or(0),
+            entries: core
+                .log
+                .entries_from(next, self.config.max_entries_per_append),
+            leader_commit: core.commit_index,
+        };
+        Message::Append(request, core.heartbeat_round)
+    }
+
+    fn handle_append_reply(
+        &self,
+        peer: &PeerConfig,
+        request: &AppendRequest,
+        round: u64,
+        reply: AppendReply,
+    ) {
+        let mut core = self.core.lock();
+        if reply.term > core.term {
+            self.step_down(&mut core, reply.term);
+            return;
+        }
+        if core.role != Role::Leader || core.term != request.term {
+            return;
+        }
+        // Sucesso ou não, o vizinho reconheceu este líder no mandato atual
+        let acked = core.acked_round.entry(peer.id).or_default();
+        *acked = (*acked).max(round);
+
+        let next = if reply.success {
+            let matched = request.prev_log_index + request.entries.len() as u64;
+            let current = core.match_index.entry(peer.id).or_default();
+            *current = (*current).max(matched);
+            self.advance_commit(&mut core);
+            matched + 1
+        } else {
+            // Recua até o ponto em que os logs batem
+            (reply.last_log_index + 1)
+                .min(request.prev_log_index)
+                .max(1)
+        };
+        core.next_index.insert(peer.id, next);
+        if next <= core.log.last_index()
+            && let Some((sender, _)) = self.replicate_kicks.get(&peer.id)
+        {
+            let _ = sender.try_send(());
+        }
+    }
+
+    fn handle_snapshot_reply(
+        &self,
+        peer: &PeerConfig,
+        request: &SnapshotRequest,
+        reply: SnapshotReply,
+    ) {
+        let mut core = self.core.lock();
+        if reply.term > core.term {
+            self.step_down(&mut core, reply.term);
+            return;
+        }
+        if core.role != Role::Leader || core.term != request.term {
+            return;
+        }
+        let current = core.match_index.entry(peer.id).or_default();
+        *current = (*current).max(request.last_index);
+        core.next_index.insert(peer.id, request.last_index + 1);
+        if let Some((sender, _)) = self.replicate_kicks.get(&peer.id) {
+            let _ = sender.try_send(());
+        }
+    }
+
+    // Responde depois de gravar o mandato e o voto
+    async fn handle_vote(&self, request: VoteRequest) -> io::Result<VoteReply> {
+        let reply = {
+            let mut core = self.core.lock();
+            if request.term > core.term {
+                self.step_down(&mut core, request.term);
+            }
+            // Só vota em quem tem um log pelo menos tão atualizado quanto o próprio
+            let up_to_date = (request.last_log_term, request.last_log_index)
+                >= (core.log.last_term(), core.log.last_index());
+            let granted = request.term == core.term
+                && core.voted_for.is_none_or(|id| id == request.candidate_id)
+                && up_to_date;
+            if granted {
+                core.voted_for = Some(request.candidate_id);
+                self.reset_election_deadline(&mut core);
+            }
+            VoteReply {
+                term: core.term,
+                vote_granted: granted,
+            }
+        };
+        self.persist().await?;
+        Ok(reply)
+    }
+
+    // Responde depois de gravar as entradas recebidas (e o mandato)
+    async fn handle_append(&self, request: AppendRequest) -> io::Result<AppendReply> {
+        let reply = self.append_entries(request);
+        self.persist().await?;
+        Ok(reply)
+    }
+
+    fn append_entries(&self, request: AppendRequest) -> AppendReply {
+        let mut core = self.core.lock();
+        let reject = |core: &Core, last_log_index| AppendReply {
+            term: core.term,
+            success: false,
+            last_log_index,
+        };
+        if request.term < core.term {
+            return reject(&core, core.log.last_index());
+        }
+        if request.term > core.term || core.role != Role::Follower {
+            self.step_down(&mut core, request.term);
+        }
+        core.leader = Some(request.leader_id);
+        self.reset_election_deadline(&mut core);
+
+        let snapshot_index = core.log.snapshot().last_index;
+        if request.prev_log_index > core.log.last_index() {
+            return reject(&core, core.log.last_index());
+        }
+        if request.prev_log_index > snapshot_index
+            && core.log.term_at(request.prev_log_index) != Some(request.prev_log_term)
+        {
+            return reject(&core, request.prev_log_index - 1);
+        }
+
+        let last_new = request.prev_log_index + request.entries.len() as u64;
+        let mut new_entries = Vec::new();
+        for entry in request.entries {
+            if entry.index <= snapshot_index {
+                continue;
+            }
+            if new_entries.is_empty() {
+                match core.log.term_at(entry.index) {
+                    Some(term) if term == entry.term => continue,
+                    // Conflito: descarta dali em diante e fica com o log do líder
+                    Some(_) => core.log.truncate_from(entry.index),
+                    None => {}
+                }
+            }
+            new_entries.push(entry);
+        }
+        core.log.append(new_entries);
+
+        if request.leader_commit > core.commit_index {
+            core.commit_index = request.leader_commit.min(last_new).max(core.commit_index);
+            let _ = self.apply_kick.0.try_send(());
+        }
+        AppendReply {
+            term: core.term,
+            success: true,
+            last_log_index: last_new,
+        }
+    }
+
+    // Responde depois de gravar o snapshot instalado
+    async fn handle_snapshot(
+        &self,
+        request: SnapshotRequest,
+        data: Vec<u8>,
+    ) -> io::Result<SnapshotReply> {
+        {
+            let mut core = self.core.lock();
+            if request.term < core.term {
+                return Ok(SnapshotReply { term: core.term });
+            }
+            if request.term > core.term || core.role != Role::Follower {
+                self.step_down(&mut core, request.term);
+            }
+            core.leader = Some(request.leader_id);
+            self.reset_election_deadline(&mut core);
+        }
+
+        let _applying = self.apply_lock.lock().await;
+        if self.core.lock().last_applied < request.last_index {
+            match storage::decode_snapshot(&data) {
+                Ok(snapshot) => {
+                    self.state.restore(snapshot);
+                    let mut core = self.core.lock();
+                    let meta = SnapshotMeta {
+                        last_index: request.last_index,
+                        last_term: request.last_term,
+                    };
+                    core.log.compact(meta, data);
+                    core.last_applied = request.last_index;
+                    core.commit_index = core.commit_index.max(request.last_index);
+                    tracing::info!(
+                        node_id = self.id,
+                        last_index = request.last_index,
+                        "snapshot instalado"
+                    );
+                }
+                Err(err) => tracing::error!(error = %err, "snapshot recebido inválido"),
+            }
+        }
+        self.persist().await?;
+        Ok(SnapshotReply {
+            term: self.core.lock().term,
+        })
+    }
+
+    // Aplica, em ordem, as entradas confirmadas e tira os snapshots
+    async fn run_applier(self: Arc<Self>) {
+        let kick = self.apply_kick.1.clone();
+        while kick.recv().await.is_ok() && !self.is_stopped() {
+            loop {
+                let _applying = self.apply_lock.lock().await;
+                let entry = {
+                    let core = self.core.lock();
+                    if core.last_applied >= core.commit_index {
+                        break;
+                    }
+                    core.log.entry(core.last_applied + 1).cloned()
+                };
+                let Some(entry) = entry else { break };
+
+                let result = match entry.command {
+                    Command::Noop => Ok(Response::new(200)),
+                    Command::Request(write) => {
+                        self.purge_trash(&write).await;
+                        self.apply(write).await
+                    }
+                };
+                let snapshot_due = {
+                    let mut core = self.core.lock();
+                    core.last_applied = entry.index;
+                    if let Some(waiter) = core.waiters.remove(&entry.index) {
+                        let _ = waiter.try_send(result);
+                    }
+                    core.last_applied - core.log.snapshot().last_index
+                        >= self.config.snapshot_threshold
+                };
+                if snapshot_due {
+                    self.take_snapshot().await;
+                }
+            }
+        }
+    }
+
+    // Passa a escrita pelas rotas, com o horário e a semente da entrada
+    async fn apply(&self, write: WriteRequest) -> tide::Result {
+        let method: Method = write
+            .method
+            .parse()
+            .map_err(|_| tide::Error::from_str(400, "Invalid method"))?;
+        let url = Url::parse(&format!("http://localhost{}", write.path))?;
+        let body = BASE64
+            .decode(&write.body)
+            .map_err(|_| tide::Error::from_str(400, "Invalid body"))?;
+        let request = build_request(method, url, write.headers, body);
+        let request: tide::Request<()> = request.into();
+        state::with_apply_context(write.timestamp_ms, write.seed, self.machine.call(request)).await
+    }
+
+    // Apaga de vez o que passou da retenção no horário da entrada
+    async fn purge_trash(&self, write: &WriteRequest) {
+        let retention = self.state.trash_retention();
+        let purge = trash::purge_expired(&self.state, retention);
+        state::with_apply_context(write.timestamp_ms, write.seed, purge).await;
+    }
+
+    // Chamado com `apply_lock` em mãos: nada muda o estado enquanto ele é copiado
+    async fn take_snapshot(&self) {
+        let encoded = match storage::encode_snapshot(&self.state).await {
+            Ok(encoded) => encoded,
+            Err(err) => {
+                tracing::error!(error = %err, "falha ao gerar o snapshot do Raft");
+                return;
+            }
+        };
+        let meta = {
+            let mut core = self.core.lock();
+            let meta = SnapshotMeta {
+                last_index: core.last_applied,
+                last_term: core.log.term_at(core.last_applied).unwrap_or(0),
+            };
+            core.log.compact(meta, encoded.bytes);
+            meta
+        };
+        match self.persist().await {
+            Ok(()) => tracing::info!(
+                last_index = meta.last_index,
+                entries = encoded.entries,
+                "snapshot do Raft salvo"
+            ),
+            Err(err) => tracing::error!(error = %err, "falha ao salvar o snapshot do Raft"),
+        }
+    }
+}
+
+enum Message {
+    Append(AppendRequest, u64),
+    Snapshot(SnapshotRequest, Arc<Vec<u8>>),
+}
+
+// Monta a requisição com os headers dados, sem o Content-Type padrão do body
+// quando o original não tinha um
+pub fn build_request(
+    method: Method,
+    url: Url,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+) -> http::Request {
+    let mut request = http::Request::new(method, url);
+    request.set_body(body);
+    request.remove_header("Content-Type");
+    for (name, value) in headers {
+        request.append_header(name.as_str(), value.as_str());
+    }
+    request
+}
+
+// Prazo sorteado da próxima eleição
+fn election_timeout(config: &ClusterConfig) -> Duration {
+    let span = config.election_timeout_max_ms - config.election_timeout_min_ms;
+    let jitter = (Uuid::new_v4().as_u128() % (span as u128 + 1)) as u64;
+    Duration::from_millis(config.election_timeout_min_ms + jitter)
+}
+
+// host:porta de `peer_url`
+fn listen_address(peer_url: &str) -> io::Result<String> {
+    let invalid =
+        |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("{msg}: {peer_url}"));
+    let url = Url::parse(peer_url).map_err(|_| invalid("peer_url inválida"))?;
+    let host = url.host_str().ok_or_else(|| invalid("peer_url sem host"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| invalid("peer_url sem porta"))?;
+    Ok(format!("{host}:{port}"))
+}
@@ -0,0 +1,249 @@
+// Busca textual nos nomes de função dos registros (GET /data/_search?q=).
+// Cada coleção mantém um índice invertido (termo -> registros) atualizado junto
+// com o feed de alterações (ver Collection::publish). As buscas aceitam prefixo
+// ("ad" encontra "add") e erros de digitação ("mutliply" encontra "multiply"), e o
+// resultado é ordenado por relevância (BM25).
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound;
+
+use crate::models::DataEntry;
+
+// Parâmetros do BM25: saturação da frequência do termo e peso do tamanho do registro
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+// Peso de um termo encontrado por prefixo ou por aproximação, em relação ao exato
+const PREFIX_WEIGHT: f64 = 0.8;
+const FUZZY_WEIGHT: f64 = 0.5;
+
+// Como cada termo da busca pode casar com os termos do índice
+#[derive(Clone, Copy, Debug)]
+pub struct MatchOptions {
+    pub prefix: bool,
+    pub fuzzy: bool,
+}
+
+// Um registro encontrado, com os termos do índice que casaram com a busca
+pub struct Hit {
+    pub id: u32,
+    pub score: f64,
+    pub terms: HashSet<String>,
+}
+
+struct Document {
+    // Termos distintos do registro (para removê-lo do índice)
+    terms: Vec<String>,
+    // Quantidade total de termos
+    len: usize,
+}
+
+#[derive(Default)]
+pub struct SearchIndex {
+    // termo -> (id -> ocorrências do termo no registro)
+    postings: BTreeMap<String, HashMap<u32, u32>>,
+    documents: HashMap<u32, Document>,
+    total_len: usize,
+}
+
+impl SearchIndex {
+    pub fn new(entries: &HashMap<u32, DataEntry>) -> Self {
+        let mut index = SearchIndex::default();
+        for (id, entry) in entries {
+            index.insert(*id, entry);
+        }
+        index
+    }
+
+    // Indexa (ou reindexa) o registro
+    pub fn insert(&mut self, id: u32, entry: &DataEntry) {
+        self.remove(id);
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for text in &entry.func_names {
+            for (_, term) in tokenize(text) {
+                *counts.entry(term).or_default() += 1;
+            }
+        }
+        let len = counts.values().sum::<u32>() as usize;
+        for (term, count) in &counts {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(id, *count);
+        }
+        self.total_len += len;
+        self.documents.insert(
+            id,
+            Document {
+                terms: counts.into_keys().collect(),
+                len,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        let Some(document) = self.documents.remove(&id) else {
+            return;
+        };
+        self.total_len -= document.len;
+        for term in document.terms {
+            if let Some(ids) = self.postings.get_mut(&term) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    // Registros que contêm algum termo da busca, do mais para o menos relevante
+    pub fn search(&self, query: &str, options: MatchOptions) -> Vec<Hit> {
+        let total_docs = self.documents.len() as f64;
+        let avg_len = (self.total_len as f64 / total_docs.max(1.0)).max(1.0);
+        let mut hits: HashMap<u32, Hit> = HashMap::new();
+
+        for (_, query_term) in tokenize(query) {
+            // Melhor pontuação deste termo da busca em cada registro
+            let mut best: HashMap<u32, (f64, &str)> = HashMap::new();
+            for (term, weight) in self.expand(&query_term, options) {
+                let ids = &self.postings[term];
+                let df = ids.len() as f64;
+                let idf = (1.0 + (total_docs - df + 0.5) / (df + 0.5)).ln();
+                for (id, tf) in ids {
+                    let tf = *tf as f64;
+                    let len = self.documents[id].len as f64;
+                    let score =
+                        weight * idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * len / avg_len));
+                    let slot = best.entry(*id).or_insert((0.0, term));
+                    if score > slot.0 {
+                        *slot = (score, term);
+                    }
+                }
+            }
+            for (id, (score, term)) in best {
+                let hit = hits.entry(id).or_insert_with(|| Hit {
+                    id,
+                    score: 0.0,
+                    terms: HashSet::new(),
+                });
+                hit.score += score;
+                hit.terms.insert(term.to_string());
+            }
+        }
+
+        let mut hits: Vec<Hit> = hits.into_values().collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        hits
+    }
+
+    // Termos do índice que casam com um termo da busca, com o peso de cada um
+    fn expand(&self, query_term: &str, options: MatchOptions) -> Vec<(&str, f64)> {
+        let mut matches: Vec<(&str, f64)> = Vec::new();
+        if let Some((term, _)) = self.postings.get_key_value(query_term) {
+            matches.push((term, 1.0));
+        }
+        if options.prefix {
+            matches.extend(
+                self.postings
+                    .range::<str, _>((Bound::Included(query_term), Bound::Unbounded))
+                    .map(|(term, _)| term.as_str())
+                    .take_while(|term| term.starts_with(query_term))
+                    .filter(|term| *term != query_term)
+                    .map(|term| (term, PREFIX_WEIGHT)),
+            );
+        }
+        let max_distance = max_edit_distance(query_term);
+        if options.fuzzy && max_distance > 0 {
+            let query_chars: Vec<char> = query_term.chars().collect();
+            for term in self.postings.keys() {
+                if matches.iter().any(|(matched, _)| *matched == term) {
+                    continue;
+                }
+                if let Some(distance) = edit_distance(&query_chars, term, max_distance) {
+                    matches.push((term, FUZZY_WEIGHT / distance as f64));
+                }
+            }
+        }
+        matches
+    }
+}
+
+// Quebra o texto em termos: sequências de letras e dígitos, em minúsculas,
+// com a posição (em bytes) de cada uma no texto original.
+// "add_numbers" vira "add" e "numbers".
+pub fn tokenize(text: &str) -> Vec<((usize, usize), String)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                tokens.push(((s, i), text[s..i].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+// Marca com <em> os termos encontrados no texto (o resto é escapado para HTML).
+// Devolve None se nenhum termo aparece no texto.
+pub fn highlight(text: &str, terms: &HashSet<String>) -> Option<String> {
+    let mut out = String::with_capacity(text.len() + 16);
+    let mut last = 0;
+    let mut found = false;
+    for ((start, end), term) in tokenize(text) {
+        if !terms.contains(&term) {
+            continue;
+        }
+        found = true;
+        out.push_str(&escape_html(&text[last..start]));
+        out.push_str("<em>");
+        out.push_str(&escape_html(&text[start..end]));
+        out.push_str("</em>");
+        last = end;
+    }
+    out.push_str(&escape_html(&text[last..]));
+    found.then_some(out)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// Erros de digitação tolerados: nenhum em termos curtos, mais em termos longos
+fn max_edit_distance(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+// Distância de Levenshtein (com transposição de letras vizinhas contando como
+// um erro), ou None se passar de `max`
+fn edit_distance(a: &[char], b: &str, max: usize) -> Option<usize> {
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut prev2: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(prev2[j - 2] + 1);
+            }
+        }
+        if row.iter().min().is_some_and(|min| *min > max) {
+            return None;
+        }
+        prev2 = std::mem::replace(&mut prev, row);
+    }
+    Some(prev[b.len()]).filter(|d| *d <= max)
+}
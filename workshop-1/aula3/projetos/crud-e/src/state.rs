@@ -1,14 +1,309 @@
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::future::Future;
+use std::sync::{Arc, OnceLock, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-// Importamos o modelo de dados que definimos
-use crate::models::DataEntry;
+use async_std::task_local;
+use tide::StatusCode;
+use uuid::Uuid;
 
-// AppState é o estado global da aplicação.
-// Usamos Arc<Mutex<...>> para permitir acesso seguro entre múltiplas requisições.
-pub type AppState = Arc<Mutex<HashMap<u32, DataEntry>>>;
+use crate::collections::{Collection, CollectionSettings, DEFAULT_COLLECTION};
+use crate::config::{Config, HistoryConfig};
+use crate::query::Field;
+use crate::raft::Node;
+use crate::storage::{CollectionSnapshot, Snapshot};
+use crate::sync::RwLock;
+use crate::wal::{Change, Op, Pending, Wal};
+use crate::webhooks::{NewSubscription, Subscription, Webhooks};
 
-// Cria um novo estado vazio
-pub fn new_state() -> AppState {
-    Arc::new(Mutex::new(HashMap::new()))
+// AppState é o estado global da aplicação: as coleções indexadas pelo nome.
+// Cada coleção tem seus próprios travamentos (ver src/collections.rs); o mapa de
+// coleções só fica travado enquanto se pega a coleção, nunca junto com eles.
+#[derive(Clone)]
+pub struct AppState {
+    pub collections: Arc<RwLock<HashMap<String, Arc<Collection>>>>,
+    // Assinaturas de webhooks de todas as coleções (ver src/webhooks.rs)
+    pub webhooks: Arc<Webhooks>,
+    // Configuração do histórico e campos indexados das coleções criadas depois
+    history_config: HistoryConfig,
+    indexed_fields: Vec<Field>,
+    // Nó do cluster Raft, quando [cluster] está configurado (ver src/raft)
+    cluster: Arc<OnceLock<Weak<Node>>>,
+    // Write-ahead log do backend "file" (ver src/wal.rs)
+    wal: Arc<OnceLock<Arc<Wal>>>,
+    // Trava das mudanças em coleções e assinaturas (ver `lock_admin`)
+    admin: Arc<async_lock::Mutex<()>>,
+    // Tempo que um registro removido fica restaurável ([trash])
+    trash_retention: Duration,
+}
+
+impl AppState {
+    // Coleção pelo nome
+    pub fn collection(&self, name: &str) -> Option<Arc<Collection>> {
+        self.collections.read().get(name).cloned()
+    }
+
+    // Coleção usada pelas rotas /data... (sempre existe)
+    pub fn default_collection(&self) -> Arc<Collection> {
+        self.collection(DEFAULT_COLLECTION)
+            .expect("a coleção default sempre existe")
+    }
+
+    // Todas as coleções, ordenadas pelo nome
+    pub fn all_collections(&self) -> Vec<(String, Arc<Collection>)> {
+        let collections = self.collections.read();
+        let mut all: Vec<_> = collections
+            .iter()
+            .map(|(name, collection)| (name.clone(), collection.clone()))
+            .collect();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        all
+    }
+
+    // Cria uma coleção vazia, gravando no WAL. Devolve false se o nome já existe.
+    pub async fn create_collection(
+        &self,
+        name: &str,
+        settings: CollectionSettings,
+    ) -> tide::Result<bool> {
+        let admin = self.lock_admin().await;
+        if self.collections.read().contains_key(name) {
+            return Ok(false);
+        }
+        let pending = self.log_write(Change::CreateCollection {
+            name: name.to_string(),
+            settings: settings.clone(),
+        })?;
+        self.insert_collection(name, settings);
+        drop(admin);
+        pending.durable().await?;
+        Ok(true)
+    }
+
+    // Remove uma coleção com todos os registros, gravando no WAL. Devolve false
+    // se ela não existe.
+    pub async fn drop_collection(&self, name: &str) -> tide::Result<bool> {
+        let admin = self.lock_admin().await;
+        let Some(collection) = self.collection(name) else {
+            return Ok(false);
+        };
+        // Espera as escritas em andamento; as próximas veem a coleção removida
+        let _map = collection.data.write().await;
+        let pending = self.log_write(Change::DropCollection {
+            name: name.to_string(),
+        })?;
+        collection.mark_dropped();
+        self.remove_collection(name);
+        drop(admin);
+        pending.durable().await?;
+        Ok(true)
+    }
+
+    // Cria a coleção só na memória (usado também ao reaplicar o WAL)
+    pub fn insert_collection(&self, name: &str, settings: CollectionSettings) -> bool {
+        let mut collections = self.collections.write();
+        if collections.contains_key(name) {
+            return false;
+        }
+        let snapshot = CollectionSnapshot {
+            settings,
+            ..CollectionSnapshot::default()
+        };
+        let collection = Collection::new(
+            name,
+            snapshot,
+            &self.history_config,
+            &self.indexed_fields,
+            self.webhooks.clone(),
+        );
+        collections.insert(name.to_string(), Arc::new(collection));
+        true
+    }
+
+    // Remove a coleção só da memória (usado também ao reaplicar o WAL)
+    pub fn remove_collection(&self, name: &str) -> bool {
+        self.collections.write().remove(name).is_some()
+    }
+
+    // Cria uma assinatura de webhook, gravando no WAL. Devolve None acima do limite.
+    pub async fn subscribe_webhook(
+        &self,
+        new: NewSubscription,
+    ) -> tide::Result<Option<Subscription>> {
+        let admin = self.lock_admin().await;
+        let Some(subscription) = self.webhooks.new_subscription(new) else {
+            return Ok(None);
+        };
+        let pending = self.log_write(Change::Subscribe(subscription.clone()))?;
+        self.webhooks.insert(subscription.clone());
+        drop(admin);
+        pending.durable().await?;
+        Ok(Some(subscription))
+    }
+
+    // Remove uma assinatura de webhook, gravando no WAL. Devolve false se ela não existe.
+    pub async fn unsubscribe_webhook(&self, id: u32) -> tide::Result<bool> {
+        let admin = self.lock_admin().await;
+        if !self.webhooks.contains(id) {
+            return Ok(false);
+        }
+        let pending = self.log_write(Change::Unsubscribe { id })?;
+        self.webhooks.unsubscribe(id);
+        drop(admin);
+        pending.durable().await?;
+        Ok(true)
+    }
+
+    // Troca todo o conteúdo (coleções e assinaturas de webhooks) pelo do snapshot.
+    // Os assinantes do feed das coleções antigas deixam de receber eventos.
+    pub fn restore(&self, snapshot: Snapshot) {
+        let new_collection = |name: &str, saved| {
+            Arc::new(Collection::new(
+                name,
+                saved,
+                &self.history_config,
+                &self.indexed_fields,
+                self.webhooks.clone(),
+            ))
+        };
+        let mut collections: HashMap<String, Arc<Collection>> = snapshot
+            .collections
+            .into_iter()
+            .map(|(name, saved)| {
+                let collection = new_collection(&name, saved);
+                (name, collection)
+            })
+            .collect();
+        let default = CollectionSnapshot {
+            settings: CollectionSettings::default(),
+            entries: snapshot.entries,
+            trash: snapshot.trash,
+            history: snapshot.history,
+            next_id: snapshot.next_id,
+        };
+        collections.insert(
+            DEFAULT_COLLECTION.to_string(),
+            new_collection(DEFAULT_COLLECTION, default),
+        );
+        *self.collections.write() = collections;
+        self.webhooks.replace(snapshot.webhooks);
+    }
+
+    // Nó do cluster (None fora do modo cluster ou depois de ele parar)
+    pub fn cluster(&self) -> Option<Arc<Node>> {
+        self.cluster.get().and_then(Weak::upgrade)
+    }
+
+    pub fn set_cluster(&self, node: &Arc<Node>) {
+        let _ = self.cluster.set(Arc::downgrade(node));
+    }
+
+    pub fn trash_retention(&self) -> Duration {
+        self.trash_retention
+    }
+
+    pub fn wal(&self) -> Option<&Arc<Wal>> {
+        self.wal.get()
+    }
+
+    pub fn set_wal(&self, wal: Arc<Wal>) {
+        let _ = self.wal.set(wal);
+    }
+
+    // Serializa as mudanças em coleções e assinaturas entre si e com os snapshots
+    pub async fn lock_admin(&self) -> async_lock::MutexGuard<'_, ()> {
+        self.admin.lock().await
+    }
+
+    // Enfileira a mudança no WAL (quando ligado). Quem chama aplica a mudança na
+    // memória antes de soltar a trava que a ordena e espera o `Pending` antes de
+    // responder.
+    pub fn log_write(&self, change: Change) -> tide::Result<Pending> {
+        let Some(wal) = self.wal() else {
+            return Ok(Pending::ready());
+        };
+        wal.append(change).map_err(|err| {
+            tracing::error!(error = %err, "falha ao gravar no WAL");
+            tide::Error::from_str(500, "WAL write failed: a escrita não foi aplicada")
+        })
+    }
+
+    // Enfileira escritas nos registros da coleção. Chamado com `data` travado
+    // para escrita; uma coleção removida nesse meio tempo responde 404.
+    pub fn log_ops(&self, collection: &Collection, ops: Vec<Op>) -> tide::Result<Pending> {
+        if collection.is_dropped() {
+            return Err(tide::Error::from_str(404, "Collection not found"));
+        }
+        if ops.is_empty() {
+            return Ok(Pending::ready());
+        }
+        self.log_write(Change::Data {
+            collection: collection.name().to_string(),
+            ops,
+        })
+    }
+}
+
+// Cria o estado a partir de um snapshot (vazio quando não há dados salvos)
+pub fn from_snapshot(snapshot: Snapshot, config: &Config) -> AppState {
+    let state = AppState {
+        collections: Arc::default(),
+        webhooks: Arc::new(Webhooks::new(&config.webhooks)),
+        history_config: config.history.clone(),
+        indexed_fields: config.indexes.fields(),
+        cluster: Arc::default(),
+        wal: Arc::default(),
+        admin: Arc::default(),
+        trash_retention: Duration::from_secs(config.trash.retention_secs),
+    };
+    state.restore(snapshot);
+    state
+}
+
+// Maior id aceito de fora (import): o seguinte ainda precisa caber em u32
+pub const MAX_ID: u32 = u32::MAX - 1;
+
+// Erro das criações quando a coleção não tem mais ids (ver Collection::next_id)
+pub fn ids_exhausted() -> tide::Error {
+    tide::Error::from_str(
+        StatusCode::InsufficientStorage,
+        "Ids exhausted: não há mais ids livres nesta coleção",
+    )
+}
+
+task_local! {
+    // Horário e semente da entrada do log do Raft sendo aplicada (ver src/raft):
+    // todos os nós gravam os mesmos horários e valores aleatórios
+    static APPLY_CONTEXT: Cell<Option<(u64, u128)>> = Cell::new(None);
+}
+
+// Roda `f` (na mesma tarefa) com o horário e a semente fixados
+pub async fn with_apply_context<F: Future>(timestamp_ms: u64, seed: u128, f: F) -> F::Output {
+    APPLY_CONTEXT.with(|ctx| ctx.set(Some((timestamp_ms, seed))));
+    let output = f.await;
+    APPLY_CONTEXT.with(|ctx| ctx.set(None));
+    output
+}
+
+fn apply_context() -> Option<(u64, u128)> {
+    APPLY_CONTEXT.try_with(Cell::get).ok().flatten()
+}
+
+// Horário atual em milissegundos desde 1970 (UTC)
+pub fn now_ms() -> u64 {
+    if let Some((timestamp_ms, _)) = apply_context() {
+        return timestamp_ms;
+    }
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+// UUID aleatório (v4), igual em todos os nós ao aplicar uma entrada do log
+pub fn new_uuid() -> Uuid {
+    match apply_context() {
+        Some((_, seed)) => uuid::Builder::from_random_bytes(seed.to_le_bytes()).into_uuid(),
+        None => Uuid::new_v4(),
+    }
 }
@@ -0,0 +1,60 @@
+// Travas do estado do servidor.
+//
+// - `AsyncRwLock` guarda os registros de cada coleção: leituras rodam em paralelo
+//   e quem espera pela trava devolve a thread ao executor em vez de bloqueá-la.
+//   Um pânico com a trava em mãos apenas a solta (ela não fica envenenada).
+// - `RwLock` e `Mutex` embrulham as travas da std, para seções curtas que nunca
+//   atravessam um `.await` (índices, lixeira, histórico, limites de taxa...).
+//   Se uma thread entrar em pânico segurando a trava, a próxima chamada recupera
+//   o valor e segue, em vez de todas as rotas que usam a trava passarem a falhar.
+use std::sync::{self, MutexGuard, PoisonError, RwLockReadGuard, RwLockWriteGuard};
+
+pub use async_lock::RwLock as AsyncRwLock;
+
+#[derive(Debug, Default)]
+pub struct Mutex<T>(sync::Mutex<T>);
+
+impl<T> Mutex<T> {
+    pub fn new(value: T) -> Self {
+        Mutex(sync::Mutex::new(value))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(|poisoned| {
+            warn_poisoned();
+            self.0.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RwLock<T>(sync::RwLock<T>);
+
+impl<T> RwLock<T> {
+    pub fn new(value: T) -> Self {
+        RwLock(sync::RwLock::new(value))
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.0
+            .read()
+            .unwrap_or_else(|poisoned| self.recover(poisoned))
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.0
+            .write()
+            .unwrap_or_else(|poisoned| self.recover(poisoned))
+    }
+
+    fn recover<G>(&self, poisoned: PoisonError<G>) -> G {
+        warn_poisoned();
+        self.0.clear_poison();
+        poisoned.into_inner()
+    }
+}
+
+fn warn_poisoned() {
+    tracing::warn!("trava liberada por um pânico; seguindo com o valor atual");
+}
@@ -0,0 +1,58 @@
+// Cliente HTTP em processo para testes: as requisições vão direto para o
+// `tide::Server`, sem abrir porta nem rede.
+//
+//     let client = TestClient::new(build_app(state, &Config::default()));
+//     let mut res = client.post_json("/data", &entry).await;
+//     assert_eq!(res.status(), StatusCode::Ok);
+use serde::Serialize;
+use tide::Server;
+use tide::http::{Method, Request, Response, Url};
+
+pub struct TestClient<State> {
+    app: Server<State>,
+}
+
+impl<State: Clone + Send + Sync + 'static> TestClient<State> {
+    pub fn new(app: Server<State>) -> Self {
+        TestClient { app }
+    }
+
+    // Envia a requisição pelo app inteiro (middlewares e rotas)
+    pub async fn send(&self, req: Request) -> Response {
+        // Erros dos handlers já viram respostas dentro do Tide
+        match self.app.respond(req).await {
+            Ok(res) => res,
+            Err(err) => Response::new(err.status()),
+        }
+    }
+
+    pub async fn get(&self, path: &str) -> Response {
+        self.send(request(Method::Get, path)).await
+    }
+
+    pub async fn delete(&self, path: &str) -> Response {
+        self.send(request(Method::Delete, path)).await
+    }
+
+    pub async fn post_json<T: Serialize>(&self, path: &str, body: &T) -> Response {
+        self.send(json_request(Method::Post, path, body)).await
+    }
+
+    pub async fn put_json<T: Serialize>(&self, path: &str, body: &T) -> Response {
+        self.send(json_request(Method::Put, path, body)).await
+    }
+}
+
+// Cria uma requisição para um caminho do app (ex: "/data/1?rev=2")
+pub fn request(method: Method, path: &str) -> Request {
+    let url = Url::parse("http://localhost")
+        .and_then(|base| base.join(path))
+        .expect("caminho inválido");
+    Request::new(method, url)
+}
+
+fn json_request<T: Serialize>(method: Method, path: &str, body: &T) -> Request {
+    let mut req = request(method, path);
+    req.set_body(tide::Body::from_json(body).expect("corpo não serializável em JSON"));
+    req
+}
@@ -0,0 +1,175 @@
+// HTTPS com rustls.
+// Os certificados vêm dos arquivos PEM da configuração ([server.tls]) e são
+// relidos quando algum arquivo muda, sem derrubar as conexões abertas: as
+// novas conexões passam a usar os certificados novos. Se a leitura falhar
+// (ex: arquivo pela metade durante a troca), os certificados atuais continuam.
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use async_std::task;
+use futures_lite::{AsyncRead, AsyncWrite};
+use futures_rustls::TlsAcceptor;
+use futures_rustls::rustls::pki_types::pem::PemObject;
+use futures_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use futures_rustls::rustls::server::WebPkiClientVerifier;
+use futures_rustls::rustls::{RootCertStore, ServerConfig};
+
+use crate::config::TlsConfig;
+use crate::sync::{Mutex, RwLock};
+
+// Acceptor TLS que pode ser trocado enquanto o servidor roda
+pub struct Acceptor {
+    config: TlsConfig,
+    current: RwLock<TlsAcceptor>,
+}
+
+impl Acceptor {
+    // Lê os certificados; falha se algum arquivo estiver ausente ou inválido
+    pub fn new(config: &TlsConfig) -> io::Result<Self> {
+        Ok(Acceptor {
+            current: RwLock::new(load(config)?),
+            config: config.clone(),
+        })
+    }
+
+    // Acceptor com os certificados mais recentes
+    pub fn current(&self) -> TlsAcceptor {
+        self.current.read().clone()
+    }
+
+    // Verifica os arquivos a cada `reload_interval_secs` e recarrega quando mudam
+    pub fn spawn_reloader(self: &Arc<Self>) {
+        if self.config.reload_interval_secs == 0 {
+            return;
+        }
+        let interval = Duration::from_secs(self.config.reload_interval_secs);
+        let acceptor = self.clone();
+        task::spawn(async move {
+            let mut seen = acceptor.modified();
+            loop {
+                task::sleep(interval).await;
+                let modified = acceptor.modified();
+                if modified == seen {
+                    continue;
+                }
+                match load(&acceptor.config) {
+                    Ok(tls) => {
+                        *acceptor.current.write() = tls;
+                        seen = modified;
+                        tracing::info!("certificados TLS recarregados");
+                    }
+                    // `seen` fica igual: tenta de novo na próxima verificação
+                    Err(err) => {
+                        tracing::warn!(error = %err, "falha ao recarregar os certificados TLS")
+                    }
+                }
+            }
+        });
+    }
+
+    // Data de modificação de cada arquivo de certificado
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        self.paths()
+            .map(|path| path.metadata().and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        let config = &self.config;
+        [&config.cert_path, &config.key_path, &config.client_ca_path]
+            .into_iter()
+            .flatten()
+    }
+}
+
+fn load(config: &TlsConfig) -> io::Result<TlsAcceptor> {
+    let (Some(cert_path), Some(key_path)) = (&config.cert_path, &config.key_path) else {
+        return Err(io::Error::other("TLS sem cert_path/key_path"));
+    };
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(cert_path, e))?;
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| invalid(key_path, e))?;
+
+    let builder = ServerConfig::builder();
+    let builder = match &config.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(ca_path).map_err(|e| invalid(ca_path, e))? {
+                roots
+                    .add(cert.map_err(|e| invalid(ca_path, e))?)
+                    .map_err(|e| invalid(ca_path, e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+            let verifier = if config.require_client_cert {
+                verifier
+            } else {
+                verifier.allow_unauthenticated()
+            };
+            let verifier = verifier.build().map_err(|e| invalid(ca_path, e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut tls = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| invalid(cert_path, e))?;
+    tls.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(tls)))
+}
+
+fn invalid(path: &Path, err: impl std::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {err}", path.display()),
+    )
+}
+
+// O async-h1 precisa clonar a conexão (uma cópia lê, outra escreve), e o
+// stream TLS não é clonável: as cópias compartilham o stream atrás de uma trava
+// que só fica presa durante cada `poll`.
+pub struct SharedStream<S>(Arc<Mutex<S>>);
+
+impl<S> SharedStream<S> {
+    pub fn new(stream: S) -> Self {
+        SharedStream(Arc::new(Mutex::new(stream)))
+    }
+}
+
+impl<S> Clone for SharedStream<S> {
+    fn clone(&self) -> Self {
+        SharedStream(self.0.clone())
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for SharedStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.0.lock()).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for SharedStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.0.lock()).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.0.lock()).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.0.lock()).poll_close(cx)
+    }
+}
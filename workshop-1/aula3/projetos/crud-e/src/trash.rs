@@ -0,0 +1,132 @@
+// Lixeira dos registros removidos.
+// DELETE /data/:id move o registro para cá com a hora da remoção; ele some das
+// leituras normais, pode voltar com POST /data/:id/restore e é apagado de vez
+// (junto com o histórico) por uma tarefa em segundo plano depois do período de
+// retenção ([trash] na configuração).
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_std::task;
+use serde::{Deserialize, Serialize};
+
+use crate::collections::Collection;
+use crate::config::TrashConfig;
+use crate::models::DataEntry;
+use crate::state::{AppState, now_ms};
+use crate::wal::Op;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TrashedEntry {
+    pub entry: DataEntry,
+    pub deleted_at_ms: u64,
+}
+
+#[derive(Default)]
+pub struct Trash {
+    entries: HashMap<u32, TrashedEntry>,
+}
+
+impl Trash {
+    pub fn new(entries: HashMap<u32, TrashedEntry>) -> Self {
+        Trash { entries }
+    }
+
+    pub fn insert(&mut self, id: u32, entry: DataEntry) {
+        let trashed = TrashedEntry {
+            entry,
+            deleted_at_ms: now_ms(),
+        };
+        self.entries.insert(id, trashed);
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    // Tira o registro da lixeira (para restaurar)
+    pub fn take(&mut self, id: u32) -> Option<DataEntry> {
+        self.entries.remove(&id).map(|trashed| trashed.entry)
+    }
+
+    pub fn all(&self) -> &HashMap<u32, TrashedEntry> {
+        &self.entries
+    }
+
+    // Maior id na lixeira; ids removidos não podem ser reaproveitados enquanto
+    // ainda puderem ser restaurados
+    pub fn max_id(&self) -> Option<u32> {
+        self.entries.keys().max().copied()
+    }
+
+    // Ids dos registros removidos há mais de `retention`
+    pub fn expired(&self, retention: Duration) -> Vec<u32> {
+        let cutoff = now_ms().saturating_sub(retention.as_millis() as u64);
+        let mut expired: Vec<u32> = self
+            .entries
+            .iter()
+            .filter(|(_, trashed)| trashed.deleted_at_ms < cutoff)
+            .map(|(id, _)| *id)
+            .collect();
+        expired.sort_unstable();
+        expired
+    }
+}
+
+// Apaga de vez registros da lixeira, junto com o histórico deles.
+// Chamado com `data` travado para escrita.
+pub fn purge(collection: &Collection, ids: &[u32]) {
+    // Ordem dos travamentos: `trash` antes de `history` (ver src/collections.rs)
+    let mut trash = collection.trash.write();
+    let mut history = collection.history.write();
+    for id in ids {
+        if trash.take(*id).is_some() {
+            history.remove(*id);
+        }
+    }
+}
+
+// Apaga de vez o que passou da retenção em todas as coleções, gravando no WAL
+pub async fn purge_expired(state: &AppState, retention: Duration) {
+    for (name, collection) in state.all_collections() {
+        // Confere sem travar `data`, que segura as escritas da coleção
+        if collection.trash.read().expired(retention).is_empty() {
+            continue;
+        }
+        let map = collection.data.write().await;
+        let expired = collection.trash.read().expired(retention);
+        if expired.is_empty() {
+            continue;
+        }
+        let count = expired.len();
+        let pending = match state.log_ops(
+            &collection,
+            vec![Op::Purge {
+                ids: expired.clone(),
+            }],
+        ) {
+            Ok(pending) => pending,
+            Err(err) => {
+                tracing::warn!(collection = %name, error = %err, "lixeira: limpeza adiada");
+                continue;
+            }
+        };
+        purge(&collection, &expired);
+        drop(map);
+        if pending.durable().await.is_ok() {
+            tracing::info!(collection = %name, count, "lixeira: registros apagados de vez");
+        }
+    }
+}
+
+// Inicia a tarefa que esvazia a lixeira periodicamente, pelo relógio do
+// servidor. No cluster, a limpeza acompanha as entradas do log (ver src/raft).
+pub fn spawn_purger(state: AppState, config: &TrashConfig) {
+    let retention = state.trash_retention();
+    let interval = Duration::from_secs(config.purge_interval_secs);
+    task::spawn(async move {
+        loop {
+            task::sleep(interval).await;
+            purge_expired(&state, retention).await;
+        }
+    });
+}
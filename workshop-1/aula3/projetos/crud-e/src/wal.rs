@@ -0,0 +1,608 @@
+// Write-ahead log do backend "file" ([storage.wal]).
+//
+// Toda mudança do estado vira um registro do log: escritas nos registros (create,
+// update, delete, lotes, transações, importações, reverts, restaurações e a limpeza
+// da lixeira), coleções criadas ou removidas e assinaturas de webhooks. Quem muda o
+// estado enfileira o registro com a trava da coleção (ou `admin`, ver src/state.rs)
+// em mãos, aplica a mudança na memória e só responde depois que a thread de escrita
+// gravar o registro (com fsync, na política "always"). Assim a ordem do log é a
+// ordem em que as mudanças foram aplicadas, e nenhuma thread do executor espera o
+// disco.
+//
+// O snapshot vira o checkpoint: ele guarda o número (`wal_seq`) do último registro
+// que já contém, e o log fica só com os posteriores. Ao iniciar, os registros
+// depois do checkpoint são reaplicados com o horário original (ver
+// state::with_apply_context).
+//
+// Cada registro ocupa: tamanho do conteúdo (u32), checksum (4 primeiros bytes do
+// SHA-256 do número e do conteúdo) e número (u64), em little-endian, seguidos do
+// conteúdo em JSON. Um registro incompleto ou com checksum errado (queda no meio
+// da escrita) marca o fim do log: ele e o que vier depois são descartados.
+//
+// Se o disco falhar, o WAL para de aceitar escritas (500) até o servidor
+// reiniciar: a memória pode ter mudanças que não chegaram ao log.
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use async_std::channel::{self, Receiver, Sender};
+use async_std::task;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::changes::ChangeKind;
+use crate::collections::{Collection, CollectionSettings};
+use crate::config::{FsyncPolicy, StorageConfig, WalConfig};
+use crate::models::DataEntry;
+use crate::state::{self, AppState};
+use crate::storage::{self, Saved, write_atomic};
+use crate::sync::Mutex;
+use crate::trash;
+use crate::webhooks::Subscription;
+
+const HEADER_LEN: usize = 16;
+// Registros maiores que isso só podem ser lixo de uma escrita pela metade
+const MAX_RECORD_LEN: usize = 256 * 1024 * 1024;
+// Intervalo entre as verificações de checkpoint
+const CHECKPOINT_TICK: Duration = Duration::from_secs(1);
+
+// Uma mudança no estado, como gravada no log
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    // Escritas nos registros de uma coleção, aplicadas em ordem (um lote ou uma
+    // transação vai inteiro em um único registro do log)
+    Data {
+        collection: String,
+        ops: Vec<Op>,
+    },
+    CreateCollection {
+        name: String,
+        settings: CollectionSettings,
+    },
+    DropCollection {
+        name: String,
+    },
+    Subscribe(Subscription),
+    Unsubscribe {
+        id: u32,
+    },
+}
+
+// Uma escrita em um registro da coleção
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    // Grava o registro como uma nova revisão, criando-o se ele não existe (se ele
+    // estiver na lixeira, sai de lá, como na importação)
+    Put {
+        id: u32,
+        entry: DataEntry,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reverted_from: Option<u32>,
+    },
+    // Move o registro para a lixeira
+    Delete {
+        id: u32,
+    },
+    // Devolve o registro da lixeira
+    Restore {
+        id: u32,
+    },
+    // Apaga de vez registros da lixeira, junto com o histórico
+    Purge {
+        ids: Vec<u32>,
+    },
+}
+
+impl Op {
+    pub fn put(id: u32, entry: DataEntry) -> Self {
+        Op::Put {
+            id,
+            entry,
+            reverted_from: None,
+        }
+    }
+}
+
+// Conteúdo de um registro do log (o número fica no cabeçalho)
+#[derive(Serialize, Deserialize)]
+struct Record {
+    timestamp_ms: u64,
+    change: Change,
+}
+
+// Um registro lido do arquivo, com os bytes como estão no disco
+struct Frame {
+    seq: u64,
+    bytes: Vec<u8>,
+    record: Record,
+}
+
+enum Command {
+    Append {
+        payload: Vec<u8>,
+        done: Sender<io::Result<()>>,
+    },
+    // Tira do arquivo os registros até `seq` (já contidos no snapshot)
+    Truncate {
+        seq: u64,
+        done: Sender<io::Result<()>>,
+    },
+}
+
+pub struct Wal {
+    config: WalConfig,
+    // Fila da thread de escrita. O número de cada registro é dado pela ordem da fila.
+    queue: Mutex<Queue>,
+    // Depois de uma falha de disco, nenhuma escrita é aceita
+    failed: Arc<AtomicBool>,
+    // Último registro contido no snapshot salvo
+    checkpoint_seq: AtomicU64,
+    // Um checkpoint por vez; guarda a hora do último
+    last_checkpoint: async_lock::Mutex<Instant>,
+}
+
+struct Queue {
+    last_seq: u64,
+    sender: mpsc::Sender<Command>,
+}
+
+// Registro enfileirado, ainda sem a confirmação do disco (sem WAL, já pronto)
+#[must_use]
+pub struct Pending(Option<Receiver<io::Result<()>>>);
+
+impl Pending {
+    pub fn ready() -> Self {
+        Pending(None)
+    }
+
+    // Espera a thread de escrita gravar o registro
+    pub async fn durable(self) -> tide::Result<()> {
+        let Some(receiver) = self.0 else {
+            return Ok(());
+        };
+        let result = receiver.recv().await.unwrap_or_else(|_| Err(stopped()));
+        result.map_err(|err| {
+            tracing::error!(error = %err, "falha ao gravar no WAL");
+            tide::Error::from_str(500, "WAL write failed: a escrita pode não estar no disco")
+        })
+    }
+}
+
+impl Wal {
+    // Número do último registro enfileirado
+    pub fn last_seq(&self) -> u64 {
+        self.queue.lock().last_seq
+    }
+
+    // Enfileira a mudança para a thread de escrita. Quem chama aplica a mudança
+    // logo depois, sem soltar a trava que a ordena, e espera o `Pending` antes
+    // de responder.
+    pub fn append(&self, change: Change) -> io::Result<Pending> {
+        if self.failed.load(Ordering::SeqCst) {
+            return Err(io::Error::other(
+                "o WAL parou de aceitar escritas depois de uma falha de disco",
+            ));
+        }
+        let record = Record {
+            timestamp_ms: state::now_ms(),
+            change,
+        };
+        let payload = serde_json::to_vec(&record)?;
+        let (done, receiver) = channel::bounded(1);
+        let mut queue = self.queue.lock();
+        queue
+            .sender
+            .send(Command::Append { payload, done })
+            .map_err(|_| stopped())?;
+        queue.last_seq += 1;
+        Ok(Pending(Some(receiver)))
+    }
+
+    fn checkpoint_due(&self) -> bool {
+        // Outro checkpoint em andamento
+        let Some(last_checkpoint) = self.last_checkpoint.try_lock() else {
+            return false;
+        };
+        let records = self.last_seq() - self.checkpoint_seq.load(Ordering::SeqCst);
+        let by_records =
+            self.config.checkpoint_records > 0 && records >= self.config.checkpoint_records;
+        let by_time = self.config.checkpoint_interval_secs > 0
+            && records > 0
+            && last_checkpoint.elapsed()
+                >= Duration::from_secs(self.config.checkpoint_interval_secs);
+        by_records || by_time
+    }
+
+    // Salva o snapshot e tira do log os registros que ele já contém
+    pub async fn checkpoint(
+        &self,
+        storage: &StorageConfig,
+        state: &AppState,
+    ) -> io::Result<Option<Saved>> {
+        let mut last_checkpoint = self.last_checkpoint.lock().await;
+        let Some(saved) = storage::save_snapshot(storage, state).await? else {
+            return Ok(None);
+        };
+        // Registros enfileirados enquanto o snapshot era salvo continuam no log
+        let (done, receiver) = channel::bounded(1);
+        self.queue
+            .lock()
+            .sender
+            .send(Command::Truncate {
+                seq: saved.wal_seq,
+                done,
+            })
+            .map_err(|_| stopped())?;
+        receiver.recv().await.unwrap_or_else(|_| Err(stopped()))?;
+        self.checkpoint_seq.store(saved.wal_seq, Ordering::SeqCst);
+        *last_checkpoint = Instant::now();
+        Ok(Some(saved))
+    }
+}
+
+// Abre o WAL configurado em [storage], reaplica no estado os registros
+// posteriores ao snapshot (`checkpoint_seq`) e inicia a thread de escrita.
+// Sem WAL (backend "memory" ou `enabled = false`), devolve None.
+pub async fn open(
+    storage: &StorageConfig,
+    checkpoint_seq: u64,
+    state: &AppState,
+) -> io::Result<Option<Arc<Wal>>> {
+    let Some(path) = storage.wal_path() else {
+        return Ok(None);
+    };
+    let (frames, valid_len) = read_frames(&path)?;
+    let file_len = std::fs::metadata(&path).map_or(0, |m| m.len());
+    if valid_len < file_len {
+        tracing::warn!(
+            path = %path.display(),
+            discarded_bytes = file_len - valid_len,
+            "final do WAL incompleto ou corrompido; descartado"
+        );
+        let file = OpenOptions::new().write(true).open(&path)?;
+        file.set_len(valid_len)?;
+        file.sync_all()?;
+    }
+
+    // Os assinantes de webhooks já receberam esses eventos antes da queda
+    state.webhooks.set_active(false);
+    let mut last_seq = checkpoint_seq;
+    let mut replayed = 0;
+    for Frame { seq, record, .. } in frames {
+        last_seq = last_seq.max(seq);
+        if seq <= checkpoint_seq {
+            continue;
+        }
+        replay(state, record).await;
+        replayed += 1;
+    }
+    state.webhooks.set_active(true);
+    tracing::info!(path = %path.display(), replayed, last_seq, "WAL reaplicado");
+
+    let failed = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    let writer = Writer {
+        file: open_append(&path)?,
+        path,
+        len: valid_len,
+        last_seq,
+        policy: storage.wal.fsync,
+        interval: Duration::from_millis(storage.wal.fsync_interval_ms),
+        unsynced: false,
+        last_sync: Instant::now(),
+        failed: failed.clone(),
+    };
+    thread::Builder::new()
+        .name("wal-writer".to_string())
+        .spawn(move || writer.run(receiver))?;
+
+    Ok(Some(Arc::new(Wal {
+        config: storage.wal.clone(),
+        queue: Mutex::new(Queue { last_seq, sender }),
+        failed,
+        checkpoint_seq: AtomicU64::new(checkpoint_seq),
+        last_checkpoint: async_lock::Mutex::new(Instant::now()),
+    })))
+}
+
+// Salva checkpoints em segundo plano quando o log passa dos limites de [storage.wal]
+pub fn spawn_checkpointer(state: AppState, storage: StorageConfig, wal: Arc<Wal>) {
+    task::spawn(async move {
+        loop {
+            task::sleep(CHECKPOINT_TICK).await;
+            if !wal.checkpoint_due() {
+                continue;
+            }
+            match wal.checkpoint(&storage, &state).await {
+                Ok(Some(saved)) => tracing::info!(
+                    entries = saved.entries,
+                    wal_seq = saved.wal_seq,
+                    "checkpoint do WAL salvo"
+                ),
+                Ok(None) => {}
+                Err(err) => tracing::error!(error = %err, "falha no checkpoint do WAL"),
+            }
+        }
+    });
+}
+
+// Reaplica um registro como os handlers fazem (histórico, lixeira, índices e feed)
+async fn replay(state: &AppState, record: Record) {
+    state::with_apply_context(record.timestamp_ms, 0, async {
+        match record.change {
+            Change::Data { collection, ops } => {
+                let Some(collection) = state.collection(&collection) else {
+                    tracing::warn!(%collection, "registro do WAL de uma coleção inexistente; ignorado");
+                    return;
+                };
+                let mut map = collection.data.write().await;
+                for op in ops {
+                    apply(&collection, &mut map, op);
+                }
+            }
+            Change::CreateCollection { name, settings } => {
+                state.insert_collection(&name, settings);
+            }
+            Change::DropCollection { name } => {
+                state.remove_collection(&name);
+            }
+            Change::Subscribe(subscription) => state.webhooks.insert(subscription),
+            Change::Unsubscribe { id } => {
+                state.webhooks.unsubscribe(id);
+            }
+        }
+    })
+    .await;
+}
+
+fn apply(collection: &Collection, map: &mut HashMap<u32, DataEntry>, op: Op) {
+    match op {
+        Op::Put {
+            id,
+            entry,
+            reverted_from,
+        } => {
+            collection.trash.write().take(id);
+            let kind = if map.contains_key(&id) {
+                ChangeKind::Update
+            } else {
+                ChangeKind::Create
+            };
+            collection
+                .history
+                .write()
+                .record(id, entry.clone(), reverted_from);
+            collection.publish(kind, id, Some(entry.clone()));
+            map.insert(id, entry);
+        }
+        Op::Delete { id } => {
+            if let Some(entry) = map.remove(&id) {
+                collection.trash.write().insert(id, entry);
+                collection.publish(ChangeKind::Delete, id, None);
+            }
+        }
+        Op::Restore { id } => {
+            let restored = collection.trash.write().take(id);
+            if let Some(entry) = restored {
+                collection.publish(ChangeKind::Restore, id, Some(entry.clone()));
+                map.insert(id, entry);
+            }
+        }
+        Op::Purge { ids } => {
+            trash::purge(collection, &ids);
+        }
+    }
+}
+
+// Thread dona do arquivo: grava os registros na ordem da fila, juntando os que
+// chegam ao mesmo tempo em uma escrita (e um fsync)
+struct Writer {
+    path: PathBuf,
+    file: File,
+    // Tamanho do arquivo até o último registro completo
+    len: u64,
+    last_seq: u64,
+    policy: FsyncPolicy,
+    interval: Duration,
+    // Registros gravados e ainda sem fsync (política "interval")
+    unsynced: bool,
+    last_sync: Instant,
+    failed: Arc<AtomicBool>,
+}
+
+impl Writer {
+    fn run(mut self, receiver: mpsc::Receiver<Command>) {
+        let mut next = None;
+        loop {
+            let command = match next.take() {
+                Some(command) => command,
+                None => match self.sync_deadline() {
+                    Some(deadline) => match receiver.recv_timeout(deadline) {
+                        Ok(command) => command,
+                        Err(mpsc::RecvTimeoutError::Timeout) => {
+                            self.sync();
+                            continue;
+                        }
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    },
+                    None => match receiver.recv() {
+                        Ok(command) => command,
+                        Err(_) => break,
+                    },
+                },
+            };
+            match command {
+                Command::Append { payload, done } => {
+                    let mut batch = vec![(payload, done)];
+                    while let Ok(command) = receiver.try_recv() {
+                        match command {
+                            Command::Append { payload, done } => batch.push((payload, done)),
+                            other => {
+                                next = Some(other);
+                                break;
+                            }
+                        }
+                    }
+                    self.write(batch);
+                }
+                Command::Truncate { seq, done } => {
+                    let _ = done.try_send(self.truncate(seq));
+                }
+            }
+        }
+        self.sync();
+    }
+
+    fn write(&mut self, batch: Vec<(Vec<u8>, Sender<io::Result<()>>)>) {
+        if self.failed.load(Ordering::SeqCst) {
+            for (_, done) in batch {
+                let _ = done.try_send(Err(io::Error::other("o WAL parou depois de uma falha")));
+            }
+            return;
+        }
+        let mut bytes = Vec::new();
+        let mut seq = self.last_seq;
+        for (payload, _) in &batch {
+            seq += 1;
+            encode(&mut bytes, seq, payload);
+        }
+        let result = self
+            .file
+            .write_all(&bytes)
+            .and_then(|()| match self.policy {
+                FsyncPolicy::Always => self.file.sync_data(),
+                FsyncPolicy::Interval | FsyncPolicy::Never => {
+                    self.unsynced = true;
+                    Ok(())
+                }
+            });
+        match result {
+            Ok(()) => {
+                self.len += bytes.len() as u64;
+                self.last_seq = seq;
+                for (_, done) in batch {
+                    let _ = done.try_send(Ok(()));
+                }
+            }
+            Err(err) => {
+                // Tira o que ficou pela metade; as próximas escritas são recusadas
+                let _ = self.file.set_len(self.len);
+                self.fail(&err);
+                for (_, done) in batch {
+                    let _ = done.try_send(Err(io::Error::new(err.kind(), err.to_string())));
+                }
+            }
+        }
+    }
+
+    // Prazo até o próximo fsync da política "interval"
+    fn sync_deadline(&self) -> Option<Duration> {
+        (self.policy == FsyncPolicy::Interval && self.unsynced)
+            .then(|| self.interval.saturating_sub(self.last_sync.elapsed()))
+    }
+
+    fn sync(&mut self) {
+        if !self.unsynced {
+            return;
+        }
+        if let Err(err) = self.file.sync_data() {
+            self.fail(&err);
+        }
+        self.unsynced = false;
+        self.last_sync = Instant::now();
+    }
+
+    fn truncate(&mut self, seq: u64) -> io::Result<()> {
+        let mut kept = Vec::new();
+        for frame in read_frames(&self.path)?.0 {
+            if frame.seq > seq {
+                kept.extend_from_slice(&frame.bytes);
+            }
+        }
+        write_atomic(&self.path, &kept)?;
+        // O arquivo antigo saiu do lugar: sem reabrir, as escritas se perderiam
+        self.file = open_append(&self.path).inspect_err(|err| self.fail(err))?;
+        self.len = kept.len() as u64;
+        self.unsynced = false;
+        Ok(())
+    }
+
+    fn fail(&self, err: &io::Error) {
+        tracing::error!(error = %err, path = %self.path.display(), "falha de disco no WAL; escritas recusadas até reiniciar");
+        self.failed.store(true, Ordering::SeqCst);
+    }
+}
+
+fn encode(bytes: &mut Vec<u8>, seq: u64, payload: &[u8]) {
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&checksum(seq, payload).to_le_bytes());
+    bytes.extend_from_slice(&seq.to_le_bytes());
+    bytes.extend_from_slice(payload);
+}
+
+fn checksum(seq: u64, payload: &[u8]) -> u32 {
+    let digest = Sha256::new()
+        .chain_update(seq.to_le_bytes())
+        .chain_update(payload)
+        .finalize();
+    u32::from_le_bytes([digest[0], digest[1], digest[2], digest[3]])
+}
+
+// Registros completos do arquivo, em ordem, e até onde eles vão
+fn read_frames(path: &Path) -> io::Result<(Vec<Frame>, u64)> {
+    let mut bytes = Vec::new();
+    match File::open(path) {
+        Ok(mut file) => {
+            file.read_to_end(&mut bytes)?;
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(err) => return Err(err),
+    }
+
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = bytes.get(offset..offset + HEADER_LEN) {
+        let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let sum = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let seq = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        if len > MAX_RECORD_LEN {
+            break;
+        }
+        let end = offset + HEADER_LEN + len;
+        let Some(payload) = bytes.get(offset + HEADER_LEN..end) else {
+            break;
+        };
+        if checksum(seq, payload) != sum {
+            break;
+        }
+        let Ok(record) = serde_json::from_slice::<Record>(payload) else {
+            break;
+        };
+        // Números sempre crescentes; um repetido é sobra de outra escrita
+        if frames.last().is_some_and(|last| seq <= last.seq) {
+            break;
+        }
+        frames.push(Frame {
+            seq,
+            bytes: bytes[offset..end].to_vec(),
+            record,
+        });
+        offset = end;
+    }
+    Ok((frames, offset as u64))
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn stopped() -> io::Error {
+    io::Error::other("a thread de escrita do WAL parou")
+}
@@ -0,0 +1,507 @@
+// Webhooks: avisam outros serviços das alterações nos registros.
+// Cada assinatura (POST /_webhooks) tem uma URL, filtros opcionais de eventos
+// (create/update/delete/restore) e de coleções, e um segredo. A cada alteração
+// publicada por uma coleção (ver Collection::publish), as assinaturas que casam
+// recebem um POST com o evento em JSON, assinado com HMAC-SHA256:
+//   X-Webhook-Timestamp: segundos desde 1970 (UTC)
+//   X-Webhook-Signature: sha256=<hex do HMAC de "<timestamp>.<body>" com o segredo>
+// Respostas 2xx confirmam a entrega. Falhas de rede, 408, 429 e 5xx são tentadas
+// de novo com espera exponencial; as outras respostas e a última tentativa
+// levam o evento para a lista de falhas (dead letters), de onde ele pode ser
+// reenviado. Cada entrega roda em uma tarefa própria, então eventos seguidos
+// podem chegar fora de ordem: o receptor deve ordenar pela coleção e `event_id`.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use async_std::task;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tide::StatusCode;
+use tide::http::headers::CONTENT_TYPE;
+use tide::http::{Method, Request, Url};
+
+use crate::changes::{ChangeEvent, ChangeKind};
+use crate::client::Client;
+use crate::collections;
+use crate::config::WebhooksConfig;
+use crate::state::{new_uuid, now_ms};
+use crate::sync::Mutex;
+
+// Tamanho máximo do segredo informado pelo cliente
+const MAX_SECRET_LEN: usize = 256;
+
+// Uma assinatura de webhook (salva no snapshot, com o segredo)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Subscription {
+    pub id: u32,
+    pub url: String,
+    // Eventos enviados (vazio: todos)
+    #[serde(default)]
+    pub events: Vec<ChangeKind>,
+    // Coleções observadas (vazio: todas)
+    #[serde(default)]
+    pub collections: Vec<String>,
+    pub secret: String,
+    pub created_ms: u64,
+}
+
+impl Subscription {
+    // Coleções com API keys próprias só vão para quem as nomeou no filtro
+    // (o acesso é conferido na criação, ver middleware::auth::WebhookAccess)
+    fn matches(&self, collection: &str, protected: bool, op: ChangeKind) -> bool {
+        let watched = if self.collections.is_empty() {
+            !protected
+        } else {
+            self.collections.iter().any(|c| c == collection)
+        };
+        watched && (self.events.is_empty() || self.events.contains(&op))
+    }
+}
+
+// Corpo de POST /_webhooks
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewSubscription {
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<ChangeKind>,
+    #[serde(default)]
+    pub collections: Vec<String>,
+    // Sem segredo, um aleatório é gerado e devolvido só na criação
+    pub secret: Option<String>,
+}
+
+impl NewSubscription {
+    pub fn validate(&self) -> Result<(), String> {
+        let url = Url::parse(&self.url).map_err(|e| format!("url inválida: {e}"))?;
+        if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+            return Err("url deve ser http:// ou https:// com um host".to_string());
+        }
+        for name in &self.collections {
+            collections::validate_name(name)?;
+        }
+        if let Some(secret) = &self.secret
+            && (secret.is_empty() || secret.len() > MAX_SECRET_LEN)
+        {
+            return Err(format!("secret deve ter de 1 a {MAX_SECRET_LEN} bytes"));
+        }
+        Ok(())
+    }
+}
+
+// Resultado de uma tentativa de entrega
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Delivered,
+    // Falhou; haverá outra tentativa
+    Retrying,
+    // Falhou de vez; o evento foi para a lista de falhas
+    Failed,
+}
+
+// Uma tentativa no log de entregas de uma assinatura
+#[derive(Serialize, Clone, Debug)]
+pub struct DeliveryAttempt {
+    pub delivery_id: u64,
+    pub event_id: u64,
+    pub collection: String,
+    pub op: ChangeKind,
+    pub attempt: u32,
+    pub outcome: Outcome,
+    // Status HTTP da resposta (ausente em falhas de rede e prazo esgotado)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub timestamp_ms: u64,
+}
+
+// Evento que esgotou as tentativas de entrega
+#[derive(Serialize, Clone, Debug)]
+pub struct DeadLetter {
+    pub delivery_id: u64,
+    pub subscription_id: u32,
+    pub url: String,
+    pub collection: String,
+    pub event: ChangeEvent,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_ms: u64,
+}
+
+// Um evento a caminho de uma assinatura
+#[derive(Clone)]
+struct Delivery {
+    id: u64,
+    subscription_id: u32,
+    collection: String,
+    event: ChangeEvent,
+}
+
+// Corpo enviado ao receptor: o evento com a entrega e a coleção
+#[derive(Serialize)]
+struct Payload<'a> {
+    delivery_id: u64,
+    subscription_id: u32,
+    collection: &'a str,
+    #[serde(flatten)]
+    event: &'a ChangeEvent,
+}
+
+struct Inner {
+    next_id: u32,
+    next_delivery_id: u64,
+    subscriptions: BTreeMap<u32, Subscription>,
+    // Últimas tentativas de cada assinatura (no máximo `log_size`)
+    logs: HashMap<u32, VecDeque<DeliveryAttempt>>,
+    // Mais antigas primeiro (no máximo `dead_letter_size`)
+    dead_letters: VecDeque<DeadLetter>,
+}
+
+pub struct Webhooks {
+    config: WebhooksConfig,
+    client: Client,
+    // Só o nó ativo entrega os eventos: no cluster, apenas o líder (ver src/raft)
+    active: AtomicBool,
+    inner: Mutex<Inner>,
+}
+
+impl Webhooks {
+    // Cria o registro de webhooks, sem assinaturas (ver `replace`)
+    pub fn new(config: &WebhooksConfig) -> Self {
+        Webhooks {
+            config: config.clone(),
+            client: Client::new(config.ca_path.as_deref()),
+            active: AtomicBool::new(true),
+            inner: Mutex::new(Inner {
+                next_id: 1,
+                next_delivery_id: 1,
+                subscriptions: BTreeMap::new(),
+                logs: HashMap::new(),
+                dead_letters: VecDeque::new(),
+            }),
+        }
+    }
+
+    // Monta uma assinatura (já validada) com o próximo id, sem guardá-la (ver
+    // `insert`). Devolve None acima de `max_subscriptions`.
+    pub fn new_subscription(&self, new: NewSubscription) -> Option<Subscription> {
+        let inner = self.inner.lock();
+        if inner.subscriptions.len() >= self.config.max_subscriptions {
+            return None;
+        }
+        Some(Subscription {
+            id: inner.next_id,
+            url: new.url,
+            events: new.events,
+            collections: new.collections,
+            secret: new
+                .secret
+                .unwrap_or_else(|| format!("whsec_{}", new_uuid().simple())),
+            created_ms: now_ms(),
+        })
+    }
+
+    // Guarda uma assinatura criada por `new_subscription` (ou reaplicada do WAL)
+    pub fn insert(&self, subscription: Subscription) {
+        let mut inner = self.inner.lock();
+        inner.next_id = inner.next_id.max(subscription.id + 1);
+        inner.subscriptions.insert(subscription.id, subscription);
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.inner.lock().subscriptions.contains_key(&id)
+    }
+
+    // Liga ou desliga as entregas deste nó
+    pub fn set_active(&self, active: bool) {
+        self.active.store(active, Ordering::SeqCst);
+    }
+
+    // Troca todas as assinaturas pelas de um snapshot (logs e falhas são descartados)
+    pub fn replace(&self, saved: Vec<Subscription>) {
+        let mut inner = self.inner.lock();
+        inner.next_id = saved.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+        inner.subscriptions = saved.into_iter().map(|s| (s.id, s)).collect();
+        inner.logs.clear();
+        inner.dead_letters.clear();
+    }
+
+    // Todas as assinaturas, ordenadas pelo id
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.inner.lock().subscriptions.values().cloned().collect()
+    }
+
+    // Remove uma assinatura e o log dela. As entregas pendentes param na
+    // próxima tentativa. Devolve false se ela não existe.
+    pub fn unsubscribe(&self, id: u32) -> bool {
+        let mut inner = self.inner.lock();
+        inner.logs.remove(&id);
+        inner.subscriptions.remove(&id).is_some()
+    }
+
+    // Log de entregas de uma assinatura, mais recentes primeiro
+    pub fn deliveries(&self, id: u32) -> Option<Vec<DeliveryAttempt>> {
+        let inner = self.inner.lock();
+        inner.subscriptions.get(&id)?;
+        let log = inner.logs.get(&id);
+        Some(log.map_or_else(Vec::new, |log| log.iter().rev().cloned().collect()))
+    }
+
+    // Lista de falhas, mais recentes primeiro
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.inner
+            .lock()
+            .dead_letters
+            .iter()
+            .rev()
+            .cloned()
+            .collect()
+    }
+
+    // Tira o evento da lista de falhas e começa as tentativas de novo
+    pub fn retry_dead_letter(self: &Arc<Self>, delivery_id: u64) -> tide::Result<()> {
+        let mut inner = self.inner.lock();
+        let Some(pos) = inner
+            .dead_letters
+            .iter()
+            .position(|d| d.delivery_id == delivery_id)
+        else {
+            return Err(tide::Error::from_str(
+                StatusCode::NotFound,
+                "Dead letter not found",
+            ));
+        };
+        if !inner
+            .subscriptions
+            .contains_key(&inner.dead_letters[pos].subscription_id)
+        {
+            return Err(tide::Error::from_str(
+                StatusCode::Conflict,
+                "Webhook subscription removed: a assinatura do evento não existe mais",
+            ));
+        }
+        let dead = inner.dead_letters.remove(pos).expect("posição encontrada");
+        drop(inner);
+        self.spawn(Delivery {
+            id: dead.delivery_id,
+            subscription_id: dead.subscription_id,
+            collection: dead.collection,
+            event: dead.event,
+        });
+        Ok(())
+    }
+
+    // Envia o evento para as assinaturas que casam com a coleção e a operação.
+    // Chamado com a coleção travada: só agenda as entregas, sem esperar por elas.
+    pub fn notify(self: &Arc<Self>, collection: &str, protected: bool, event: &ChangeEvent) {
+        if !self.active.load(Ordering::SeqCst) {
+            return;
+        }
+        let deliveries: Vec<Delivery> = {
+            let mut inner = self.inner.lock();
+            let ids: Vec<u32> = inner
+                .subscriptions
+                .values()
+                .filter(|s| s.matches(collection, protected, event.op))
+                .map(|s| s.id)
+                .collect();
+            ids.into_iter()
+                .map(|subscription_id| {
+                    let id = inner.next_delivery_id;
+                    inner.next_delivery_id += 1;
+                    Delivery {
+                        id,
+                        subscription_id,
+                        collection: collection.to_string(),
+                        event: event.clone(),
+                    }
+                })
+                .collect()
+        };
+        for delivery in deliveries {
+            self.spawn(delivery);
+        }
+    }
+
+    fn spawn(self: &Arc<Self>, delivery: Delivery) {
+        let webhooks = self.clone();
+        task::spawn(async move { webhooks.deliver(delivery).await });
+    }
+
+    // Tenta entregar até `max_attempts` vezes, esperando cada vez mais entre elas
+    async fn deliver(&self, delivery: Delivery) {
+        let max_attempts = self.config.max_attempts;
+        for attempt in 1..=max_attempts {
+            // A assinatura pode ter sido removida (ou trocada) entre as tentativas
+            let Some(subscription) = self
+                .inner
+                .lock()
+                .subscriptions
+                .get(&delivery.subscription_id)
+                .cloned()
+            else {
+                return;
+            };
+
+            let started = Instant::now();
+            let result = self.send(&subscription, &delivery, attempt).await;
+            let (status, error) = match &result {
+                Ok(status) if status.is_success() => (Some(*status), None),
+                Ok(status) => (Some(*status), Some(format!("resposta {status}"))),
+                Err(err) => (None, Some(err.clone())),
+            };
+            let retryable = status.is_none_or(|s| {
+                s.is_server_error()
+                    || s == StatusCode::RequestTimeout
+                    || s == StatusCode::TooManyRequests
+            });
+            let outcome = match &error {
+                None => Outcome::Delivered,
+                Some(_) if retryable && attempt < max_attempts => Outcome::Retrying,
+                Some(_) => Outcome::Failed,
+            };
+
+            self.record(
+                &subscription,
+                &delivery,
+                DeliveryAttempt {
+                    delivery_id: delivery.id,
+                    event_id: delivery.event.event_id,
+                    collection: delivery.collection.clone(),
+                    op: delivery.event.op,
+                    attempt,
+                    outcome,
+                    status: status.map(|s| s as u16),
+                    error,
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    timestamp_ms: now_ms(),
+                },
+            );
+            match outcome {
+                Outcome::Retrying => task::sleep(self.backoff(attempt)).await,
+                Outcome::Delivered | Outcome::Failed => return,
+            }
+        }
+    }
+
+    // Espera depois da tentativa `attempt`: initial_backoff * 2^(attempt-1),
+    // até max_backoff
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << (attempt - 1).min(32);
+        let ms = self
+            .config
+            .initial_backoff_ms
+            .saturating_mul(factor)
+            .min(self.config.max_backoff_ms);
+        Duration::from_millis(ms)
+    }
+
+    // Guarda a tentativa no log e, se foi a última, o evento na lista de falhas
+    fn record(&self, subscription: &Subscription, delivery: &Delivery, attempt: DeliveryAttempt) {
+        match attempt.outcome {
+            Outcome::Delivered => tracing::debug!(
+                subscription = subscription.id,
+                delivery = delivery.id,
+                "webhook entregue"
+            ),
+            Outcome::Retrying => tracing::debug!(
+                subscription = subscription.id,
+                delivery = delivery.id,
+                attempt = attempt.attempt,
+                error = attempt.error.as_deref().unwrap_or_default(),
+                "falha ao entregar webhook; tentando de novo"
+            ),
+            Outcome::Failed => tracing::warn!(
+                subscription = subscription.id,
+                delivery = delivery.id,
+                attempts = attempt.attempt,
+                error = attempt.error.as_deref().unwrap_or_default(),
+                "webhook não entregue; evento movido para a lista de falhas"
+            ),
+        }
+
+        let mut inner = self.inner.lock();
+        // Removida durante a tentativa: nada a guardar
+        if !inner.subscriptions.contains_key(&subscription.id) {
+            return;
+        }
+        if attempt.outcome == Outcome::Failed {
+            if inner.dead_letters.len() == self.config.dead_letter_size {
+                inner.dead_letters.pop_front();
+            }
+            inner.dead_letters.push_back(DeadLetter {
+                delivery_id: delivery.id,
+                subscription_id: subscription.id,
+                url: subscription.url.clone(),
+                collection: delivery.collection.clone(),
+                event: delivery.event.clone(),
+                attempts: attempt.attempt,
+                last_error: attempt.error.clone().unwrap_or_default(),
+                failed_ms: attempt.timestamp_ms,
+            });
+        }
+        let log = inner.logs.entry(subscription.id).or_default();
+        if log.len() == self.config.log_size {
+            log.pop_front();
+        }
+        log.push_back(attempt);
+    }
+
+    // Uma tentativa: POST assinado na URL da assinatura, dentro de `timeout_secs`
+    async fn send(
+        &self,
+        subscription: &Subscription,
+        delivery: &Delivery,
+        attempt: u32,
+    ) -> Result<StatusCode, String> {
+        let url = Url::parse(&subscription.url).map_err(|e| e.to_string())?;
+        let body = serde_json::to_vec(&Payload {
+            delivery_id: delivery.id,
+            subscription_id: subscription.id,
+            collection: &delivery.collection,
+            event: &delivery.event,
+        })
+        .map_err(|e| e.to_string())?;
+        let timestamp = now_ms() / 1000;
+
+        let mut req = Request::new(Method::Post, url.clone());
+        req.insert_header(CONTENT_TYPE, "application/json");
+        req.insert_header(
+            "User-Agent",
+            concat!("crud-e-webhooks/", env!("CARGO_PKG_VERSION")),
+        );
+        req.insert_header("X-Webhook-Id", delivery.id.to_string());
+        req.insert_header("X-Webhook-Event", delivery.event.op.as_str());
+        req.insert_header("X-Webhook-Attempt", attempt.to_string());
+        req.insert_header("X-Webhook-Timestamp", timestamp.to_string());
+        req.insert_header(
+            "X-Webhook-Signature",
+            format!("sha256={}", sign(&subscription.secret, timestamp, &body)),
+        );
+        req.set_body(body);
+
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        let res = self.client.send(req, timeout).await?;
+        Ok(res.status())
+    }
+}
+
+// HMAC-SHA256 de "<timestamp>.<body>" em hexadecimal
+pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC aceita qualquer chave");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
@@ -0,0 +1,2443 @@
+// Testes da API em processo: o app monta as mesmas rotas e middlewares do
+// binário, mas as requisições não passam pela rede (ver src/testing.rs)
+use crud_e::build_app;
+use crud_e::config::{Config, LogFormat, RequestLimitConfig, RouteLimitConfig, StorageBackend};
+use crud_e::models::DataEntry;
+use crud_e::state::{self, AppState};
+use crud_e::storage::{self, Snapshot};
+use crud_e::testing::{TestClient, request};
+use crud_e::{logging, trash, wal};
+use futures_lite::io::{BufReader, Cursor};
+use futures_lite::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, StreamExt};
+use serde_json::{Value, json};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tide::StatusCode;
+use tide::http::Method;
+
+fn new_state(config: &Config) -> AppState {
+    state::from_snapshot(Snapshot::default(), config)
+}
+
+fn client() -> TestClient<AppState> {
+    let config = Config::default();
+    TestClient::new(build_app(new_state(&config), &config))
+}
+
+#[async_std::test]
+async fn crud_round_trip() {
+    let client = client();
+
+    let mut res = client
+        .post_json(
+            "/data",
+            &json!({ "func_names": ["add"], "bytecode": [0, 97, 115, 109] }),
+        )
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let created: Value = res.body_json().await.unwrap();
+    assert_eq!(created, json!({ "id": 1 }));
+
+    let mut res = client.get("/data/1").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let entry: Value = res.body_json().await.unwrap();
+    assert_eq!(entry["bytecode"], json!([0, 97, 115, 109]));
+
+    let res = client
+        .put_json(
+            "/data/1",
+            &json!({ "func_names": ["sub"], "bytecode": "AGFzbQ==" }),
+        )
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert_eq!(res.header("X-Revision").unwrap().as_str(), "2");
+
+    let res = client.delete("/data/1").await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+    let res = client.get("/data/1").await;
+    assert_eq!(res.status(), StatusCode::NotFound);
+}
+
+#[async_std::test]
+async fn negotiates_cbor_and_msgpack() {
+    let client = client();
+    let entry = DataEntry {
+        func_names: vec!["add".to_string()],
+        bytecode: vec![0, 97, 115, 109],
+    };
+
+    // Corpo em CBOR pelo Content-Type
+    let mut body = Vec::new();
+    ciborium::into_writer(&entry, &mut body).unwrap();
+    let mut req = request(Method::Post, "/data");
+    req.insert_header("Content-Type", "application/cbor");
+    req.set_body(body);
+    let res = client.send(req).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    // Resposta em MessagePack pelo Accept, com os bytes em binário
+    let mut req = request(Method::Get, "/data/1");
+    req.insert_header("Accept", "application/msgpack");
+    let mut res = client.send(req).await;
+    assert_eq!(
+        res.header("Content-Type").unwrap().as_str(),
+        "application/msgpack"
+    );
+    let bytes = res.body_bytes().await.unwrap();
+    let read: DataEntry = rmp_serde::from_slice(&bytes).unwrap();
+    assert_eq!(read.func_names, entry.func_names);
+    assert_eq!(read.bytecode, entry.bytecode);
+
+    // Corpo em MessagePack e resposta em CBOR, escolhida pelos pesos do Accept
+    let update = DataEntry {
+        func_names: vec!["sub".to_string()],
+        bytecode: vec![255],
+    };
+    let mut req = request(Method::Put, "/data/1");
+    req.insert_header("Content-Type", "application/x-msgpack");
+    req.set_body(rmp_serde::to_vec_named(&update).unwrap());
+    assert_eq!(client.send(req).await.status(), StatusCode::Ok);
+    let mut req = request(Method::Get, "/data/1");
+    req.insert_header("Accept", "application/json;q=0.5, application/cbor");
+    let mut res = client.send(req).await;
+    assert_eq!(
+        res.header("Content-Type").unwrap().as_str(),
+        "application/cbor"
+    );
+    let bytes = res.body_bytes().await.unwrap();
+    let read: DataEntry = ciborium::from_reader(bytes.as_slice()).unwrap();
+    assert_eq!(read.bytecode, vec![255]);
+
+    // JSON com os bytes em base64
+    let mut req = request(Method::Get, "/data/1");
+    req.insert_header("Accept", "application/json; bytes=base64");
+    let mut res = client.send(req).await;
+    let read: Value = res.body_json().await.unwrap();
+    assert_eq!(read["bytecode"], json!("/w=="));
+}
+
+#[async_std::test]
+async fn rejects_unsupported_media_types() {
+    let client = client();
+    client
+        .post_json("/data", &json!({ "func_names": [], "bytecode": [] }))
+        .await;
+
+    // Sem nenhum formato aceitável no Accept: 406
+    let mut req = request(Method::Get, "/data/1");
+    req.insert_header("Accept", "text/html, application/json;q=0");
+    let res = client.send(req).await;
+    assert_eq!(res.status(), StatusCode::NotAcceptable);
+
+    // Corpos de formulário e outros tipos desconhecidos: 415
+    let body = r#"{ "func_names": [], "bytecode": [] }"#;
+    for content_type in ["application/x-www-form-urlencoded", "text/plain"] {
+        let mut req = request(Method::Post, "/data");
+        req.insert_header("Content-Type", content_type);
+        req.set_body(body);
+        let res = client.send(req).await;
+        assert_eq!(res.status(), StatusCode::UnsupportedMediaType);
+    }
+    let mut res = client.get("/data").await;
+    let all: Value = res.body_json().await.unwrap();
+    assert_eq!(all.as_object().unwrap().len(), 1);
+}
+
+#[async_std::test]
+async fn rejects_invalid_id() {
+    let res = client().get("/data/abc").await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    assert!(res.header("X-Request-Id").is_some());
+}
+
+#[async_std::test]
+async fn requires_configured_api_key() {
+    let mut config = Config::default();
+    config.auth.api_keys = vec!["secret".to_string()];
+    let client = TestClient::new(build_app(new_state(&config), &config));
+
+    let res = client.get("/data").await;
+    assert_eq!(res.status(), StatusCode::Unauthorized);
+
+    let mut req = request(Method::Get, "/data");
+    req.insert_header("X-Api-Key", "secret");
+    let res = client.send(req).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    // Documentação continua pública
+    let res = client.get("/openapi.json").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+}
+
+#[async_std::test]
+async fn nests_under_a_prefix() {
+    let config = Config::default();
+    let state = new_state(&config);
+    let mut outer = tide::new();
+    outer.at("/crud").nest(build_app(state.clone(), &config));
+    outer.at("/health").get(|_| async { Ok("ok") });
+    let client = TestClient::new(outer);
+
+    let res = client
+        .post_json("/crud/data", &json!({ "func_names": [], "bytecode": [] }))
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert!(
+        state
+            .default_collection()
+            .data
+            .read()
+            .await
+            .contains_key(&1)
+    );
+
+    // A página de documentação busca o documento relativo a ela, sob o prefixo
+    let mut res = client.get("/crud/docs").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let page = res.body_string().await.unwrap();
+    assert!(page.contains("fetch('openapi.json')"));
+    assert!(!page.contains("/openapi.json"));
+    let res = client.get("/crud/openapi.json").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    let res = client.get("/health").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+}
+
+#[async_std::test]
+async fn collections_are_isolated() {
+    let client = client();
+    let entry = json!({ "func_names": [], "bytecode": [1, 2, 3] });
+
+    let res = client
+        .post_json(
+            "/collections",
+            &json!({ "name": "tenant-a", "max_entries": 1 }),
+        )
+        .await;
+    assert_eq!(res.status(), StatusCode::Created);
+    let res = client
+        .post_json("/collections", &json!({ "name": "tenant-a" }))
+        .await;
+    assert_eq!(res.status(), StatusCode::Conflict);
+
+    // Cada coleção tem sua própria sequência de ids
+    client.post_json("/data", &entry).await;
+    let mut res = client.post_json("/collections/tenant-a/data", &entry).await;
+    let created: Value = res.body_json().await.unwrap();
+    assert_eq!(created, json!({ "id": 1 }));
+
+    // Limite de registros da coleção
+    let res = client.post_json("/collections/tenant-a/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::InsufficientStorage);
+
+    let mut res = client.get("/collections").await;
+    let list: Value = res.body_json().await.unwrap();
+    assert_eq!(list[0]["name"], "default");
+    assert_eq!(list[1]["entries"], 1);
+
+    let res = client.delete("/collections/tenant-a").await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+    let res = client.get("/collections/tenant-a/data/1").await;
+    assert_eq!(res.status(), StatusCode::NotFound);
+    let res = client.delete("/collections/default").await;
+    assert_eq!(res.status(), StatusCode::Conflict);
+}
+
+#[async_std::test]
+async fn collection_api_keys() {
+    let client = client();
+    client
+        .post_json(
+            "/collections",
+            &json!({ "name": "private", "api_keys": ["tenant-key"] }),
+        )
+        .await;
+
+    let res = client.get("/collections/private/data").await;
+    assert_eq!(res.status(), StatusCode::Unauthorized);
+
+    let mut req = request(Method::Get, "/collections/private/data");
+    req.insert_header("X-Api-Key", "tenant-key");
+    assert_eq!(client.send(req).await.status(), StatusCode::Ok);
+
+    // Sem chaves globais, as outras coleções continuam abertas
+    let res = client.get("/data").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    let res = client.delete("/collections/private").await;
+    assert_eq!(res.status(), StatusCode::Unauthorized);
+    let mut req = request(Method::Delete, "/collections/private");
+    req.insert_header("X-Api-Key", "tenant-key");
+    assert_eq!(client.send(req).await.status(), StatusCode::NoContent);
+}
+
+#[async_std::test]
+async fn search_ranks_and_highlights() {
+    let client = client();
+    for names in [
+        json!(["add_numbers", "sub"]),
+        json!(["multiply"]),
+        json!(["add", "add_one"]),
+    ] {
+        client
+            .post_json("/data", &json!({ "func_names": names, "bytecode": [] }))
+            .await;
+    }
+
+    let mut res = client.get("/data/_search?q=add").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let found: Value = res.body_json().await.unwrap();
+    assert_eq!(found["total"], 2);
+    assert_eq!(found["results"][0]["id"], 3);
+    assert_eq!(
+        found["results"][0]["highlights"],
+        json!(["<em>add</em>", "<em>add</em>_one"])
+    );
+
+    // Prefixo e erro de digitação
+    let mut res = client.get("/data/_search?q=mul").await;
+    let found: Value = res.body_json().await.unwrap();
+    assert_eq!(found["results"][0]["id"], 2);
+    let mut res = client.get("/data/_search?q=mutliply&prefix=false").await;
+    let found: Value = res.body_json().await.unwrap();
+    assert_eq!(
+        found["results"][0]["highlights"],
+        json!(["<em>multiply</em>"])
+    );
+
+    // Registros removidos saem do índice
+    client.delete("/data/2").await;
+    let mut res = client.get("/data/_search?q=multiply").await;
+    let found: Value = res.body_json().await.unwrap();
+    assert_eq!(found["total"], 0);
+
+    let res = client.get("/data/_search").await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+}
+
+#[async_std::test]
+async fn query_uses_secondary_indexes() {
+    let mut config = Config::default();
+    config.indexes.fields = vec!["func_names".to_string(), "size".to_string()];
+    let client = TestClient::new(build_app(new_state(&config), &config));
+    for (names, size) in [
+        (json!(["add", "sub"]), 10),
+        (json!(["mul"]), 5000),
+        (json!(["add"]), 100),
+    ] {
+        client
+            .post_json(
+                "/data",
+                &json!({ "func_names": names, "bytecode": vec![0; size] }),
+            )
+            .await;
+    }
+
+    let mut res = client
+        .get("/data/_query?q=func_names contains \"add\" and size < 4096")
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let found: Value = res.body_json().await.unwrap();
+    assert_eq!(
+        found["plan"],
+        "intersect(index(func_names contains \"add\"), index(size < 4096))"
+    );
+    assert_eq!(found["count"], 2);
+    assert_eq!(
+        found["entries"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .collect::<Vec<_>>(),
+        ["1", "3"]
+    );
+
+    // Parte sem índice: filtrada depois de consultar o índice de size
+    let mut res = client
+        .get("/data/_query?q=size > 50 and not func_names = \"mul\"")
+        .await;
+    let found: Value = res.body_json().await.unwrap();
+    assert_eq!(found["plan"], "index(size > 50)");
+    assert_eq!(found["scanned"], 2);
+    assert_eq!(found["count"], 1);
+
+    // Um or com um lado sem índice lê todos os registros
+    let mut res = client.get("/data/_query?q=id = 2 or hash != \"x\"").await;
+    let found: Value = res.body_json().await.unwrap();
+    assert_eq!(found["plan"], "scan");
+    assert_eq!(found["count"], 3);
+
+    // Atualizações chegam ao índice
+    client
+        .put_json("/data/2", &json!({ "func_names": ["add"], "bytecode": [] }))
+        .await;
+    let mut res = client.get("/data/_query?q=func_names = \"add\"").await;
+    let found: Value = res.body_json().await.unwrap();
+    assert_eq!(found["count"], 3);
+
+    let res = client.get("/data/_query?q=size < \"big\"").await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+}
+
+#[async_std::test]
+async fn transaction_swaps_with_version_checks() {
+    let client = client();
+    for (name, bytes) in [("a", [1, 1]), ("b", [2, 2])] {
+        client
+            .post_json("/data", &json!({ "func_names": [name], "bytecode": bytes }))
+            .await;
+    }
+
+    // Troca os bytes dos dois registros, desde que ninguém os tenha alterado
+    let swap = json!([
+        { "op": "check", "id": 1, "version": 1 },
+        { "op": "check", "id": 2, "version": 1 },
+        { "op": "update", "id": 1, "entry": { "func_names": ["a"], "bytecode": [2, 2] } },
+        { "op": "update", "id": 2, "entry": { "func_names": ["b"], "bytecode": [1, 1] } },
+        { "op": "read", "id": 1 }
+    ]);
+    let mut res = client.post_json("/_tx", &swap).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body["committed"], true);
+    assert_eq!(body["results"][2]["version"], 2);
+    assert_eq!(body["results"][4]["entry"]["bytecode"], json!([2, 2]));
+
+    // As versões mudaram: a mesma transação agora é desfeita por inteiro
+    let mut res = client
+        .post_json(
+            "/_tx",
+            &json!([
+                { "op": "delete", "id": 2 },
+                { "op": "check", "id": 1, "version": 1 }
+            ]),
+        )
+        .await;
+    assert_eq!(res.status(), StatusCode::Conflict);
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body["failed"]["index"], 1);
+    assert_eq!(client.get("/data/2").await.status(), StatusCode::Ok);
+
+    let mut res = client.get("/data/2/history").await;
+    let history: Value = res.body_json().await.unwrap();
+    assert_eq!(history["revisions"].as_array().map(Vec::len), Some(2));
+}
+
+fn entry(name: &str, byte: u8) -> Value {
+    json!({ "func_names": [name], "bytecode": [byte] })
+}
+
+#[async_std::test]
+async fn reads_and_reverts_revisions() {
+    let client = client();
+    client.post_json("/data", &entry("v1", 1)).await;
+    client.put_json("/data/1", &entry("v2", 2)).await;
+    client.put_json("/data/1", &entry("v3", 3)).await;
+
+    // ?rev=N responde o registro como estava naquela revisão
+    let mut res = client.get("/data/1?rev=1").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert_eq!(res.header("X-Revision").unwrap().as_str(), "1");
+    let old: Value = res.body_json().await.unwrap();
+    assert_eq!(old["func_names"], json!(["v1"]));
+    let res = client.get("/data/1").await;
+    assert_eq!(res.header("X-Revision").unwrap().as_str(), "3");
+    assert_eq!(
+        client.get("/data/1?rev=9").await.status(),
+        StatusCode::NotFound
+    );
+    assert_eq!(
+        client.get("/data/1?rev=x").await.status(),
+        StatusCode::BadRequest
+    );
+
+    // O revert grava o conteúdo antigo como uma revisão nova
+    let mut res = client
+        .post_json("/data/1/revert", &json!({ "rev": 1 }))
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert_eq!(res.header("X-Revision").unwrap().as_str(), "4");
+    let reverted: Value = res.body_json().await.unwrap();
+    assert_eq!(reverted, json!({ "id": 1, "rev": 4 }));
+    let mut res = client.get("/data/1").await;
+    let current: Value = res.body_json().await.unwrap();
+    assert_eq!(current["func_names"], json!(["v1"]));
+
+    let mut res = client.get("/data/1/history").await;
+    let history: Value = res.body_json().await.unwrap();
+    let revisions = history["revisions"].as_array().unwrap();
+    let revs: Vec<_> = revisions
+        .iter()
+        .map(|r| r["rev"].as_u64().unwrap())
+        .collect();
+    assert_eq!(revs, [1, 2, 3, 4]);
+    assert_eq!(revisions[3]["reverted_from"], json!(1));
+    assert_eq!(revisions[1]["entry"]["func_names"], json!(["v2"]));
+
+    let res = client
+        .post_json("/data/1/revert", &json!({ "rev": 9 }))
+        .await;
+    assert_eq!(res.status(), StatusCode::NotFound);
+    let res = client
+        .post_json("/data/2/revert", &json!({ "rev": 1 }))
+        .await;
+    assert_eq!(res.status(), StatusCode::NotFound);
+    assert_eq!(
+        client.get("/data/2/history").await.status(),
+        StatusCode::NotFound
+    );
+}
+
+#[async_std::test]
+async fn trims_history_by_count_and_age() {
+    let mut config = Config::default();
+    config.history.max_revisions = 2;
+    let client = TestClient::new(build_app(new_state(&config), &config));
+    client.post_json("/data", &entry("v1", 1)).await;
+    client.put_json("/data/1", &entry("v2", 2)).await;
+    client.put_json("/data/1", &entry("v3", 3)).await;
+
+    // Só as duas revisões mais novas ficam guardadas
+    let mut res = client.get("/data/1/history").await;
+    let history: Value = res.body_json().await.unwrap();
+    let revs: Vec<_> = history["revisions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["rev"].as_u64().unwrap())
+        .collect();
+    assert_eq!(revs, [2, 3]);
+    assert_eq!(
+        client.get("/data/1?rev=1").await.status(),
+        StatusCode::NotFound
+    );
+    let res = client
+        .post_json("/data/1/revert", &json!({ "rev": 1 }))
+        .await;
+    assert_eq!(res.status(), StatusCode::NotFound);
+
+    // Por idade, as revisões antigas somem mas a atual sempre fica
+    let mut config = Config::default();
+    config.history.max_age_secs = 1;
+    let client = TestClient::new(build_app(new_state(&config), &config));
+    client.post_json("/data", &entry("v1", 1)).await;
+    client.put_json("/data/1", &entry("v2", 2)).await;
+    assert_eq!(client.get("/data/1?rev=1").await.status(), StatusCode::Ok);
+    async_std::task::sleep(std::time::Duration::from_millis(1100)).await;
+
+    let mut res = client.get("/data/1/history").await;
+    let history: Value = res.body_json().await.unwrap();
+    let revisions = history["revisions"].as_array().unwrap();
+    assert_eq!(revisions.len(), 1);
+    assert_eq!(revisions[0]["rev"], json!(2));
+    assert_eq!(
+        client.get("/data/1?rev=1").await.status(),
+        StatusCode::NotFound
+    );
+    assert_eq!(client.get("/data/1?rev=2").await.status(), StatusCode::Ok);
+}
+
+#[async_std::test]
+async fn restores_deleted_entries_from_the_trash() {
+    let client = client();
+    client.post_json("/data", &entry("a", 1)).await;
+    client.post_json("/data", &entry("b", 2)).await;
+
+    // O DELETE move o registro para a lixeira, com a hora da remoção
+    assert_eq!(
+        client.delete("/data/1").await.status(),
+        StatusCode::NoContent
+    );
+    assert_eq!(client.get("/data/1").await.status(), StatusCode::NotFound);
+    let mut res = client.get("/data/_trash").await;
+    let trash: Value = res.body_json().await.unwrap();
+    assert_eq!(trash["1"]["entry"]["func_names"], json!(["a"]));
+    assert!(trash["1"]["deleted_at_ms"].as_u64().unwrap() > 0);
+    assert!(trash.get("2").is_none());
+    // Nem o histórico nem as revisões do registro removido ficam visíveis
+    assert_eq!(
+        client.get("/data/1/history").await.status(),
+        StatusCode::NotFound
+    );
+    assert_eq!(
+        client.get("/data/1?rev=1").await.status(),
+        StatusCode::NotFound
+    );
+
+    // O id removido não é reaproveitado enquanto estiver na lixeira
+    let mut res = client.post_json("/data", &entry("c", 3)).await;
+    let created: Value = res.body_json().await.unwrap();
+    assert_eq!(created, json!({ "id": 3 }));
+
+    let mut res = client.post_json("/data/1/restore", &json!({})).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let restored: Value = res.body_json().await.unwrap();
+    assert_eq!(restored, json!({ "id": 1 }));
+    let mut res = client.get("/data/1").await;
+    let entry: Value = res.body_json().await.unwrap();
+    assert_eq!(entry["func_names"], json!(["a"]));
+    assert_eq!(client.get("/data/1?rev=1").await.status(), StatusCode::Ok);
+    let mut res = client.get("/data/_trash").await;
+    let trash: Value = res.body_json().await.unwrap();
+    assert_eq!(trash, json!({}));
+
+    let res = client.post_json("/data/1/restore", &json!({})).await;
+    assert_eq!(res.status(), StatusCode::NotFound);
+}
+
+#[async_std::test]
+async fn restore_keeps_a_live_entry_with_the_same_id() {
+    // Snapshot com o id 1 ativo e também na lixeira
+    let snapshot: Snapshot = serde_json::from_value(json!({
+        "entries": { "1": { "func_names": ["vivo"], "bytecode": [1] } },
+        "trash": {
+            "1": {
+                "entry": { "func_names": ["removido"], "bytecode": [2] },
+                "deleted_at_ms": 1
+            }
+        }
+    }))
+    .unwrap();
+    let config = Config::default();
+    let client = TestClient::new(build_app(state::from_snapshot(snapshot, &config), &config));
+
+    let res = client.post_json("/data/1/restore", &json!({})).await;
+    assert_eq!(res.status(), StatusCode::Conflict);
+    let mut res = client.get("/data/1").await;
+    let entry: Value = res.body_json().await.unwrap();
+    assert_eq!(entry["func_names"], json!(["vivo"]));
+    let mut res = client.get("/data/_trash").await;
+    let trash: Value = res.body_json().await.unwrap();
+    assert_eq!(trash["1"]["entry"]["func_names"], json!(["removido"]));
+}
+
+#[async_std::test]
+async fn purges_the_trash_after_the_retention() {
+    let mut config = Config::default();
+    config.trash.retention_secs = 1;
+    let state = new_state(&config);
+    let client = TestClient::new(build_app(state.clone(), &config));
+    client.post_json("/data", &entry("a", 1)).await;
+    client.put_json("/data/1", &entry("b", 2)).await;
+    client.delete("/data/1").await;
+
+    // Dentro da retenção, a limpeza não apaga nada
+    trash::purge_expired(&state, state.trash_retention()).await;
+    let mut res = client.get("/data/_trash").await;
+    let trash: Value = res.body_json().await.unwrap();
+    assert!(trash.get("1").is_some());
+
+    // Depois dela, o registro some de vez junto com o histórico
+    async_std::task::sleep(std::time::Duration::from_millis(1100)).await;
+    trash::purge_expired(&state, state.trash_retention()).await;
+    let mut res = client.get("/data/_trash").await;
+    let trash: Value = res.body_json().await.unwrap();
+    assert_eq!(trash, json!({}));
+    assert_eq!(
+        client.get("/data/1/history").await.status(),
+        StatusCode::NotFound
+    );
+    let res = client.post_json("/data/1/restore", &json!({})).await;
+    assert_eq!(res.status(), StatusCode::NotFound);
+}
+
+#[async_std::test]
+async fn rejects_bodies_over_the_route_limit() {
+    let mut config = Config::default();
+    config.limits.max_body_bytes = 64;
+    let client = TestClient::new(build_app(new_state(&config), &config));
+    let entry = json!({ "func_names": ["big"], "bytecode": vec![7; 40] });
+
+    let mut res = client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::PayloadTooLarge);
+    let body: Value = res.body_json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("64 bytes"));
+    let res = client.post_json("/collections/default/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::PayloadTooLarge);
+
+    // O lote tem um limite próprio, maior que o padrão
+    let ops = json!([{ "op": "create", "entry": entry }]);
+    let res = client.post_json("/data/_bulk", &ops).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    // Bodies dentro do limite chegam inteiros ao handler
+    let res = client
+        .post_json("/data", &json!({ "func_names": [], "bytecode": [1] }))
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+}
+
+// Body que nunca termina de chegar
+struct Stalled;
+
+impl AsyncRead for Stalled {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        _: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Pending
+    }
+}
+
+// NDJSON no formato de GET /data/_export
+fn ndjson(ids: std::ops::RangeInclusive<u32>) -> String {
+    ids.map(|id| {
+        format!(
+            "{}\n",
+            json!({ "id": id, "func_names": [], "bytecode": [id % 256] })
+        )
+    })
+    .collect()
+}
+
+// POST /data/_import com o body enviado aos poucos, sem Content-Length
+fn streamed_import(body: impl AsyncBufRead + Unpin + Send + Sync + 'static) -> tide::http::Request {
+    let mut req = request(Method::Post, "/data/_import");
+    req.set_body(tide::Body::from_reader(body, None));
+    req
+}
+
+#[async_std::test]
+async fn limits_streamed_import_bodies() {
+    let mut config = Config::default();
+    config.limits.body_timeout_secs = 1;
+    config.limits.request_limits = vec![RequestLimitConfig {
+        method: "POST".to_string(),
+        path: "/data/_import".to_string(),
+        max_body_bytes: Some(200),
+        timeout_secs: None,
+    }];
+    let client = TestClient::new(build_app(new_state(&config), &config));
+
+    // O body não é lido antes do handler, mas o limite vale durante a leitura
+    let body = ndjson(1..=10);
+    assert!(body.len() > 200);
+    let mut res = client
+        .send(streamed_import(Cursor::new(body.into_bytes())))
+        .await;
+    assert_eq!(res.status(), StatusCode::PayloadTooLarge);
+    let error: Value = res.body_json().await.unwrap();
+    assert!(error["error"].as_str().unwrap().contains("200 bytes"));
+    assert_eq!(client.get("/data/1").await.status(), StatusCode::NotFound);
+
+    let res = client
+        .send(streamed_import(Cursor::new(ndjson(1..=2).into_bytes())))
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert_eq!(client.get("/data/2").await.status(), StatusCode::Ok);
+
+    // Cliente que para de enviar no meio do body
+    let stalled = Cursor::new(ndjson(3..=3).into_bytes()).chain(Stalled);
+    let res = client.send(streamed_import(BufReader::new(stalled))).await;
+    assert_eq!(res.status(), StatusCode::RequestTimeout);
+}
+
+fn import(path: &str, body: &str) -> tide::http::Request {
+    let mut req = request(Method::Post, path);
+    req.set_body(body.to_string());
+    req
+}
+
+#[async_std::test]
+async fn exports_ndjson_and_csv() {
+    let client = client();
+    for entry in [
+        json!({ "func_names": ["add", "sub"], "bytecode": [0, 97] }),
+        json!({ "func_names": ["a,b"], "bytecode": [255] }),
+    ] {
+        let res = client.post_json("/data", &entry).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+    }
+
+    let mut res = client.get("/data/_export").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert_eq!(
+        res.content_type().unwrap().essence(),
+        "application/x-ndjson"
+    );
+    let disposition = res.header("Content-Disposition").unwrap().as_str();
+    assert!(disposition.contains("data.ndjson"));
+    let ndjson = res.body_string().await.unwrap();
+    let records: Vec<Value> = ndjson
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        records,
+        vec![
+            json!({ "id": 1, "func_names": ["add", "sub"], "bytecode": [0, 97] }),
+            json!({ "id": 2, "func_names": ["a,b"], "bytecode": [255] }),
+        ]
+    );
+
+    // CSV pelo ?format ou pelo Accept, com os campos escapados
+    let csv = "id,func_names,bytecode_len,bytecode_hex\n1,add|sub,2,0061\n2,\"a,b\",1,ff\n";
+    let mut res = client.get("/data/_export?format=csv").await;
+    assert_eq!(res.content_type().unwrap().essence(), "text/csv");
+    assert_eq!(res.body_string().await.unwrap(), csv);
+    let mut req = request(Method::Get, "/data/_export");
+    req.insert_header("Accept", "text/csv");
+    let mut res = client.send(req).await;
+    assert_eq!(res.body_string().await.unwrap(), csv);
+    let res = client.get("/data/_export?format=xml").await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+
+    // O NDJSON exportado volta pelo import em outra coleção, com os mesmos ids
+    let res = client
+        .post_json("/collections", &json!({ "name": "copia" }))
+        .await;
+    assert_eq!(res.status(), StatusCode::Created);
+    let res = client
+        .send(import("/collections/copia/data/_import", &ndjson))
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let mut res = client.get("/collections/copia/data/2").await;
+    let entry: Value = res.body_json().await.unwrap();
+    assert_eq!(entry["func_names"], json!(["a,b"]));
+}
+
+#[async_std::test]
+async fn imports_with_each_conflict_strategy() {
+    // Mais importações que o limite de taxa padrão da rota
+    let mut config = Config::default();
+    config.limits.rate_limits.clear();
+    let client = TestClient::new(build_app(new_state(&config), &config));
+    let mut res = client.send(import("/data/_import", &ndjson(1..=3))).await;
+    let summary: Value = res.body_json().await.unwrap();
+    assert_eq!(
+        summary,
+        json!({ "created": 3, "overwritten": 0, "skipped": 0 })
+    );
+
+    // fail (padrão): nada do arquivo é gravado
+    let mut res = client.send(import("/data/_import", &ndjson(3..=4))).await;
+    assert_eq!(res.status(), StatusCode::Conflict);
+    let error: Value = res.body_json().await.unwrap();
+    assert_eq!(error["error"], "1 conflicting ids: 3");
+    assert_eq!(client.get("/data/4").await.status(), StatusCode::NotFound);
+    let repeated = format!("{}{}", ndjson(5..=5), ndjson(5..=5));
+    let res = client.send(import("/data/_import", &repeated)).await;
+    assert_eq!(res.status(), StatusCode::Conflict);
+
+    // skip mantém o registro atual
+    let changed = json!({ "id": 3, "func_names": ["novo"], "bytecode": [99] });
+    let body = format!("{changed}\n{}", ndjson(4..=4));
+    let mut res = client
+        .send(import("/data/_import?on_conflict=skip", &body))
+        .await;
+    let summary: Value = res.body_json().await.unwrap();
+    assert_eq!(
+        summary,
+        json!({ "created": 1, "overwritten": 0, "skipped": 1 })
+    );
+    let mut res = client.get("/data/3").await;
+    let entry: Value = res.body_json().await.unwrap();
+    assert_eq!(entry["bytecode"], json!([3]));
+
+    // overwrite grava uma nova revisão, inclusive por cima da lixeira
+    let res = client.delete("/data/1").await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+    let body = format!("{changed}\n{}", ndjson(1..=1));
+    let mut res = client
+        .send(import("/data/_import?on_conflict=overwrite", &body))
+        .await;
+    let summary: Value = res.body_json().await.unwrap();
+    assert_eq!(
+        summary,
+        json!({ "created": 0, "overwritten": 2, "skipped": 0 })
+    );
+    let mut res = client.get("/data/3").await;
+    assert_eq!(res.header("X-Revision").unwrap().as_str(), "2");
+    let entry: Value = res.body_json().await.unwrap();
+    assert_eq!(entry["bytecode"], json!([99]));
+    assert_eq!(client.get("/data/1").await.status(), StatusCode::Ok);
+
+    let res = client
+        .send(import("/data/_import?on_conflict=merge", &body))
+        .await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let mut res = client.send(import("/data/_import", "{}\n")).await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let error: Value = res.body_json().await.unwrap();
+    assert!(error["error"].as_str().unwrap().contains("line 1"));
+}
+
+#[async_std::test]
+async fn stops_creating_when_ids_run_out() {
+    let client = client();
+    let record = |id: u32| format!(r#"{{"id":{id},"func_names":["f"],"bytecode":[1]}}"#);
+
+    // Um id sem sucessor em u32 é recusado
+    let res = client
+        .send(import("/data/_import", &record(u32::MAX)))
+        .await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let res = client
+        .send(import("/data/_import", &record(u32::MAX - 1)))
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    // O último id ainda é usado; depois dele as criações falham sem dar a volta
+    let entry = json!({ "func_names": ["g"], "bytecode": [2] });
+    let mut res = client.post_json("/data", &entry).await;
+    let created: Value = res.body_json().await.unwrap();
+    assert_eq!(created, json!({ "id": u32::MAX }));
+    let res = client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::InsufficientStorage);
+    let ops = json!([{ "op": "create", "entry": entry }]);
+    let mut res = client.post_json("/data/_bulk", &ops).await;
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(statuses(&body), vec![507]);
+    let mut res = client
+        .post_json("/_tx", &json!([{ "op": "create", "entry": entry }]))
+        .await;
+    assert_eq!(res.status(), StatusCode::Conflict);
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body["committed"], false);
+    assert_eq!(client.get("/data/0").await.status(), StatusCode::NotFound);
+}
+
+#[async_std::test]
+async fn imports_large_files_in_batches() {
+    let client = client();
+    let mut res = client
+        .send(import("/data/_import", &ndjson(1..=1200)))
+        .await;
+    let summary: Value = res.body_json().await.unwrap();
+    assert_eq!(summary["created"], 1200);
+
+    // No modo fail, o conflito no segundo lote desfaz o primeiro: nada do
+    // arquivo fica, nem na lixeira
+    let body = format!("{}{}", ndjson(2001..=2600), ndjson(1..=1));
+    let mut res = client.send(import("/data/_import", &body)).await;
+    assert_eq!(res.status(), StatusCode::Conflict);
+    let error: Value = res.body_json().await.unwrap();
+    assert!(
+        error["error"]
+            .as_str()
+            .unwrap()
+            .contains("1 conflicting ids: 1")
+    );
+    for id in [2001, 2500, 2501] {
+        let res = client.get(&format!("/data/{id}")).await;
+        assert_eq!(res.status(), StatusCode::NotFound);
+    }
+    let mut res = client.get("/data/_trash").await;
+    let trash: Value = res.body_json().await.unwrap();
+    assert_eq!(trash, json!({}));
+    let mut res = client.get("/data").await;
+    let all: Value = res.body_json().await.unwrap();
+    assert_eq!(all.as_object().unwrap().len(), 1200);
+
+    // Nos outros modos, um erro no meio mantém os lotes anteriores
+    let body = format!("{}{{\n", ndjson(3001..=3600));
+    let mut res = client
+        .send(import("/data/_import?on_conflict=overwrite", &body))
+        .await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let error: Value = res.body_json().await.unwrap();
+    assert!(error["error"].as_str().unwrap().contains("500 registros"));
+    assert_eq!(client.get("/data/3500").await.status(), StatusCode::Ok);
+    assert_eq!(
+        client.get("/data/3501").await.status(),
+        StatusCode::NotFound
+    );
+}
+
+// Módulo wasm com `add(a, b) = a + b` e `spin`, um laço sem fim
+const WASM_MODULE: [u8; 58] = [
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // cabeçalho
+    0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // (i32, i32) -> i32
+    0x03, 0x03, 0x02, 0x00, 0x00, // duas funções desse tipo
+    0x07, 0x0e, 0x02, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, 0x04, 0x73, 0x70, 0x69, 0x6e, 0x00,
+    0x01, // exports "add" e "spin"
+    0x0a, 0x12, 0x02, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, 0x08, 0x00, 0x03, 0x40, 0x0c,
+    0x00, 0x0b, 0x00, 0x0b, // corpos
+];
+
+#[async_std::test]
+async fn stops_wasm_that_runs_past_the_deadline() {
+    let mut config = Config::default();
+    config.limits.request_limits = vec![RequestLimitConfig {
+        method: "POST".to_string(),
+        path: "/execute/:id".to_string(),
+        max_body_bytes: None,
+        timeout_secs: Some(2),
+    }];
+    let client = TestClient::new(build_app(new_state(&config), &config));
+    let entry = json!({ "func_names": ["add", "spin"], "bytecode": WASM_MODULE.to_vec() });
+    let res = client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    let mut res = client
+        .post_json("/execute/1", &json!({ "fn": "add", "arg": [2, 3] }))
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body, json!({ "result": 5 }));
+
+    // O combustível acaba antes do prazo: quem responde é o handler, não o 504
+    // do middleware
+    let mut res = client
+        .post_json("/execute/1", &json!({ "fn": "spin", "arg": [0, 0] }))
+        .await;
+    assert_eq!(res.status(), StatusCode::GatewayTimeout);
+    let body: Value = res.body_json().await.unwrap();
+    assert!(body["error"].as_str().unwrap().contains("combustível"));
+}
+
+#[async_std::test]
+async fn exposes_prometheus_metrics_by_route_pattern() {
+    let client = client();
+    let entry = json!({ "func_names": ["add"], "bytecode": WASM_MODULE.to_vec() });
+    client.post_json("/data", &entry).await;
+    client.get("/data/1").await;
+    client.get("/data/7").await;
+    client.get("/data/8").await;
+    client.get("/nada/aqui").await;
+    client
+        .post_json("/execute/1", &json!({ "fn": "add", "arg": [2, 3] }))
+        .await;
+    client
+        .post_json("/execute/1", &json!({ "fn": "sub", "arg": [2, 3] }))
+        .await;
+    // O módulo 1 de outra coleção é outro módulo
+    client
+        .post_json("/collections", &json!({ "name": "outra" }))
+        .await;
+    client.post_json("/collections/outra/data", &entry).await;
+    client
+        .post_json(
+            "/collections/outra/execute/1",
+            &json!({ "fn": "add", "arg": [1, 1] }),
+        )
+        .await;
+
+    let mut res = client.get("/metrics").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let content_type = res.content_type().unwrap();
+    assert_eq!(content_type.essence(), "text/plain");
+    assert_eq!(content_type.param("version").unwrap(), "0.0.4");
+    let text = res.body_string().await.unwrap();
+
+    // Toda linha é comentário (# HELP / # TYPE) ou `nome{labels} valor`
+    for line in text.lines() {
+        if line.starts_with("# HELP ") || line.starts_with("# TYPE ") {
+            continue;
+        }
+        let (series, value) = line.rsplit_once(' ').unwrap();
+        assert!(value.parse::<f64>().is_ok(), "valor inválido: {line}");
+        let name = series.split('{').next().unwrap();
+        assert!(
+            name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "nome inválido: {line}"
+        );
+    }
+    assert!(text.contains("# TYPE http_requests_total counter\n"));
+    assert!(text.contains("# TYPE http_request_duration_seconds histogram\n"));
+
+    // O label da rota é o padrão registrado, não o caminho com o id
+    let lines: Vec<&str> = text.lines().collect();
+    for expected in [
+        r#"http_requests_total{method="GET",route="/data/:id",status="200"} 1"#,
+        r#"http_requests_total{method="GET",route="/data/:id",status="404"} 2"#,
+        r#"http_requests_total{method="GET",route="unmatched",status="404"} 1"#,
+        r#"http_requests_total{method="POST",route="/execute/:id",status="200"} 1"#,
+        r#"http_request_duration_seconds_bucket{method="POST",route="/data",status="200",le="+Inf"} 1"#,
+        r#"http_request_duration_seconds_count{method="POST",route="/data",status="200"} 1"#,
+        "crud_store_entries 2",
+        r#"wasm_executions_total{collection="default",module="1"} 2"#,
+        r#"wasm_execution_duration_seconds_count{collection="default",module="1"} 2"#,
+        r#"wasm_execution_failures_total{collection="default",module="1",reason="function_not_found"} 1"#,
+        r#"wasm_executions_total{collection="outra",module="1"} 1"#,
+    ] {
+        assert!(lines.contains(&expected), "faltou {expected:?} em:\n{text}");
+    }
+    assert!(!text.contains("/data/7") && !text.contains("/nada/aqui"));
+
+    // Os buckets do histograma são cumulativos
+    let buckets: Vec<u64> = lines
+        .iter()
+        .filter(|l| {
+            l.starts_with(r#"http_request_duration_seconds_bucket{method="GET",route="/data/:id",status="404""#)
+        })
+        .map(|l| l.rsplit_once(' ').unwrap().1.parse().unwrap())
+        .collect();
+    assert_eq!(buckets.len(), 12);
+    assert!(buckets.windows(2).all(|w| w[0] <= w[1]));
+    assert_eq!(buckets.last(), Some(&2));
+}
+
+fn route_limit(method: &str, path: &str, capacity: u32) -> RouteLimitConfig {
+    RouteLimitConfig {
+        method: method.to_string(),
+        path: path.to_string(),
+        capacity,
+        refill_per_sec: 0.01,
+        daily_quota: None,
+    }
+}
+
+// Requisição vinda de outro endereço (o TestClient não tem IP de origem)
+fn from_peer(method: Method, path: &str, peer: &str) -> tide::http::Request {
+    let mut req = request(method, path);
+    req.set_peer_addr(Some(peer));
+    req
+}
+
+#[async_std::test]
+async fn rate_limits_each_client() {
+    let mut config = Config::default();
+    config.limits.rate_limits = vec![route_limit("GET", "/data", 2)];
+    let client = TestClient::new(build_app(new_state(&config), &config));
+    let res = client
+        .post_json("/collections", &json!({ "name": "outra" }))
+        .await;
+    assert_eq!(res.status(), StatusCode::Created);
+
+    for remaining in ["1", "0"] {
+        let res = client
+            .send(from_peer(Method::Get, "/data", "10.0.0.1:4000"))
+            .await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(res.header("RateLimit-Limit").unwrap().as_str(), "2");
+        assert_eq!(
+            res.header("RateLimit-Remaining").unwrap().as_str(),
+            remaining
+        );
+        assert!(res.header("RateLimit-Reset").is_some());
+    }
+    let res = client
+        .send(from_peer(Method::Get, "/data", "10.0.0.1:4001"))
+        .await;
+    assert_eq!(res.status(), StatusCode::TooManyRequests);
+    assert_eq!(res.header("RateLimit-Remaining").unwrap().as_str(), "0");
+    let retry_after: u64 = res.header("Retry-After").unwrap().as_str().parse().unwrap();
+    assert!(retry_after >= 1);
+
+    // Uma API key que ninguém validou não dá um balde novo
+    let mut req = from_peer(Method::Get, "/data", "10.0.0.1:4002");
+    req.insert_header("X-Api-Key", "qualquer");
+    let res = client.send(req).await;
+    assert_eq!(res.status(), StatusCode::TooManyRequests);
+
+    // Outro IP tem o próprio balde; outra coleção usa o mesmo
+    let res = client
+        .send(from_peer(Method::Get, "/data", "10.0.0.2:4000"))
+        .await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let res = client
+        .send(from_peer(
+            Method::Get,
+            "/collections/outra/data",
+            "10.0.0.1:4000",
+        ))
+        .await;
+    assert_eq!(res.status(), StatusCode::TooManyRequests);
+
+    // Com autenticação, cada chave válida tem o próprio balde, de qualquer IP
+    config.auth.api_keys = vec!["a".to_string(), "b".to_string()];
+    let client = TestClient::new(build_app(new_state(&config), &config));
+    for (key, peer, status) in [
+        ("a", "10.0.0.1:4000", StatusCode::Ok),
+        ("a", "10.0.0.2:4000", StatusCode::Ok),
+        ("a", "10.0.0.3:4000", StatusCode::TooManyRequests),
+        ("b", "10.0.0.1:4000", StatusCode::Ok),
+    ] {
+        let mut req = from_peer(Method::Get, "/data", peer);
+        req.insert_header("X-Api-Key", key);
+        assert_eq!(client.send(req).await.status(), status);
+    }
+
+    // As tentativas sem chave válida contam pelo IP e também esbarram no limite
+    for status in [
+        StatusCode::Unauthorized,
+        StatusCode::Unauthorized,
+        StatusCode::TooManyRequests,
+    ] {
+        let mut req = from_peer(Method::Get, "/data", "10.0.0.9:4000");
+        req.insert_header("X-Api-Key", "errada");
+        assert_eq!(client.send(req).await.status(), status);
+    }
+}
+
+#[async_std::test]
+async fn enforces_the_daily_quota() {
+    let mut config = Config::default();
+    config.limits.rate_limits = vec![RouteLimitConfig {
+        daily_quota: Some(2),
+        ..route_limit("POST", "/execute/:id", 100)
+    }];
+    let client = TestClient::new(build_app(new_state(&config), &config));
+    let call = json!({ "func": "add", "args": [1, 2] });
+
+    // A cota conta a chamada mesmo quando o handler responde erro
+    for remaining in ["1", "0"] {
+        let res = client.post_json("/execute/1", &call).await;
+        assert_ne!(res.status(), StatusCode::TooManyRequests);
+        assert_eq!(res.header("X-Quota-Limit").unwrap().as_str(), "2");
+        assert_eq!(res.header("X-Quota-Remaining").unwrap().as_str(), remaining);
+    }
+    let res = client.post_json("/execute/1", &call).await;
+    assert_eq!(res.status(), StatusCode::TooManyRequests);
+    assert_eq!(res.header("X-Quota-Remaining").unwrap().as_str(), "0");
+    let retry_after: u64 = res.header("Retry-After").unwrap().as_str().parse().unwrap();
+    assert!((1..=86_400).contains(&retry_after));
+}
+
+fn statuses(results: &Value) -> Vec<u64> {
+    results["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["status"].as_u64().unwrap())
+        .collect()
+}
+
+#[async_std::test]
+async fn bulk_reports_each_operation() {
+    let client = client();
+    let entry = json!({ "func_names": ["add"], "bytecode": [1] });
+    let changed = json!({ "func_names": ["sub"], "bytecode": [2] });
+    let res = client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    // Sem ?atomic, cada operação vale por si e as que falham não impedem as outras
+    let ops = json!([
+        { "op": "create", "entry": entry },
+        { "op": "update", "id": 1, "entry": changed },
+        { "op": "delete", "id": 1 },
+        { "op": "delete", "id": 99 },
+        { "op": "rename", "id": 2 },
+        { "op": "update", "id": 42, "entry": changed },
+    ]);
+    let mut res = client.post_json("/data/_bulk", &ops).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body["committed"], true);
+    assert_eq!(statuses(&body), vec![200, 200, 204, 404, 400, 404]);
+    assert_eq!(
+        body["results"][0],
+        json!({ "index": 0, "op": "create", "id": 2, "status": 200 })
+    );
+    assert!(body["results"][4].get("op").is_none());
+    assert!(
+        body["results"][4]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid operation")
+    );
+
+    assert_eq!(client.get("/data/2").await.status(), StatusCode::Ok);
+    assert_eq!(client.get("/data/1").await.status(), StatusCode::NotFound);
+    // O registro removido vai para a lixeira com o conteúdo do update
+    let mut res = client.get("/data/_trash").await;
+    let trash: Value = res.body_json().await.unwrap();
+    assert_eq!(trash["1"]["entry"]["bytecode"], json!([2]));
+}
+
+#[async_std::test]
+async fn atomic_bulk_rolls_back_on_any_failure() {
+    let client = client();
+    let entry = json!({ "func_names": ["add"], "bytecode": [1] });
+    let changed = json!({ "func_names": ["sub"], "bytecode": [2] });
+    let res = client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    let ops = json!([
+        { "op": "create", "entry": entry },
+        { "op": "update", "id": 1, "entry": changed },
+        { "op": "delete", "id": 1 },
+        { "op": "delete", "id": 99 },
+    ]);
+    let mut res = client.post_json("/data/_bulk?atomic=true", &ops).await;
+    assert_eq!(res.status(), StatusCode::Conflict);
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body["committed"], false);
+    assert_eq!(statuses(&body), vec![200, 200, 204, 404]);
+
+    // Nada do lote ficou: nem o registro criado, nem a revisão, nem a lixeira
+    let mut res = client.get("/data/1").await;
+    assert_eq!(res.header("X-Revision").unwrap().as_str(), "1");
+    let current: Value = res.body_json().await.unwrap();
+    assert_eq!(current["bytecode"], json!([1]));
+    assert_eq!(client.get("/data/2").await.status(), StatusCode::NotFound);
+    let mut res = client.get("/data/_trash").await;
+    let trash: Value = res.body_json().await.unwrap();
+    assert_eq!(trash, json!({}));
+
+    // O id reservado pelo create desfeito volta a ser usado
+    let ops = json!([{ "op": "create", "entry": entry }]);
+    let mut res = client.post_json("/data/_bulk?atomic=true", &ops).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body["results"][0]["id"], 2);
+}
+
+// Saída dos logs guardada em memória, para o teste ler as linhas de JSON
+#[derive(Clone, Default)]
+struct LogBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl LogBuffer {
+    fn lines(&self) -> Vec<Value> {
+        let bytes = self.0.lock().unwrap();
+        std::str::from_utf8(&bytes)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+#[async_std::test]
+async fn logs_json_and_returns_the_request_id_on_errors() {
+    let mut config = Config::default();
+    config.log.format = LogFormat::Json;
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let dispatch = logging::dispatch(&config.log, move || writer.clone());
+    // O coletor vale só para esta thread, onde o TestClient roda as requisições
+    let _guard = tracing::dispatcher::set_default(&dispatch);
+    let client = TestClient::new(build_app(new_state(&config), &config));
+
+    // O id enviado pelo cliente volta no header e no corpo do erro
+    let mut req = request(Method::Get, "/data/9");
+    req.insert_header("X-Request-Id", "suporte-123");
+    let mut res = client.send(req).await;
+    assert_eq!(res.status(), StatusCode::NotFound);
+    assert_eq!(res.header("X-Request-Id").unwrap().as_str(), "suporte-123");
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body["request_id"], "suporte-123");
+    assert!(body["error"].is_string());
+
+    // Sem id (ou com um inválido), o servidor gera um
+    let mut req = request(Method::Get, "/data/abc");
+    req.insert_header("X-Request-Id", "x".repeat(200));
+    let mut res = client.send(req).await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+    let generated = res.header("X-Request-Id").unwrap().as_str().to_string();
+    assert!(uuid::Uuid::parse_str(&generated).is_ok());
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(
+        body,
+        json!({ "error": "Invalid id", "request_id": generated })
+    );
+
+    // Cada linha do log é um JSON com o span da requisição
+    let lines = logs.lines();
+    let rejected: Vec<&Value> = lines
+        .iter()
+        .filter(|line| line["fields"]["message"] == "request rejected")
+        .collect();
+    assert_eq!(rejected.len(), 2);
+    let span = &rejected[0]["span"];
+    assert_eq!(rejected[0]["level"], "WARN");
+    assert_eq!(span["name"], "request");
+    assert_eq!(span["request_id"], "suporte-123");
+    assert_eq!(span["method"], "GET");
+    assert_eq!(span["path"], "/data/9");
+    assert_eq!(span["status"], 404);
+    assert_eq!(span["record_id"], 9);
+    assert!(span["latency_ms"].is_number());
+    assert_eq!(rejected[1]["span"]["request_id"], generated.as_str());
+    assert_eq!(rejected[1]["fields"]["error"], "Invalid id");
+}
+
+#[async_std::test]
+async fn cors_for_allowed_origins() {
+    let mut config = Config::default();
+    config.auth.api_keys = vec!["secret".to_string()];
+    config.cors.allowed_origins = vec!["http://localhost:5173".to_string()];
+    let client = TestClient::new(build_app(new_state(&config), &config));
+
+    // O preflight não leva a API key e é respondido antes da autenticação
+    let mut req = request(Method::Options, "/data/1");
+    req.insert_header("Origin", "http://localhost:5173");
+    req.insert_header("Access-Control-Request-Method", "PUT");
+    req.insert_header("Access-Control-Request-Headers", "content-type, x-api-key");
+    let res = client.send(req).await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+    assert_eq!(
+        res.header("Access-Control-Allow-Origin").unwrap().as_str(),
+        "http://localhost:5173"
+    );
+    assert_eq!(
+        res.header("Access-Control-Max-Age").unwrap().as_str(),
+        "600"
+    );
+
+    // Respostas de erro também são liberadas, para a página ler o motivo
+    let mut req = request(Method::Get, "/data");
+    req.insert_header("Origin", "http://localhost:5173");
+    let res = client.send(req).await;
+    assert_eq!(res.status(), StatusCode::Unauthorized);
+    assert!(res.header("Access-Control-Allow-Origin").is_some());
+    let exposed = res.header("Access-Control-Expose-Headers").unwrap();
+    assert!(exposed.as_str().contains("X-Request-Id"));
+
+    // Outras origens e headers fora da lista
+    let mut req = request(Method::Get, "/openapi.json");
+    req.insert_header("Origin", "https://evil.example");
+    let res = client.send(req).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert!(res.header("Access-Control-Allow-Origin").is_none());
+    let mut req = request(Method::Options, "/data");
+    req.insert_header("Origin", "http://localhost:5173");
+    req.insert_header("Access-Control-Request-Method", "POST");
+    req.insert_header("Access-Control-Request-Headers", "x-custom");
+    assert_eq!(client.send(req).await.status(), StatusCode::Forbidden);
+}
+
+#[async_std::test]
+async fn replays_requests_with_the_same_idempotency_key() {
+    let client = client();
+    let create = |key: &str, entry: Value| {
+        let mut req = request(Method::Post, "/data");
+        req.insert_header("Idempotency-Key", key);
+        req.set_body(tide::Body::from_json(&entry).unwrap());
+        req
+    };
+    let entry = json!({ "func_names": ["once"], "bytecode": [1] });
+
+    let mut res = client.send(create("retry-1", entry.clone())).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert!(res.header("Idempotent-Replayed").is_none());
+    let first: Value = res.body_json().await.unwrap();
+
+    // A repetição devolve a mesma resposta sem criar outro registro
+    let mut res = client.send(create("retry-1", entry.clone())).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert_eq!(res.header("Idempotent-Replayed").unwrap().as_str(), "true");
+    let replayed: Value = res.body_json().await.unwrap();
+    assert_eq!(replayed, first);
+    let mut res = client.get("/data").await;
+    let all: Value = res.body_json().await.unwrap();
+    assert_eq!(all.as_object().unwrap().len(), 1);
+
+    // Mesma chave com outro body
+    let other = json!({ "func_names": ["twice"], "bytecode": [2] });
+    let res = client.send(create("retry-1", other.clone())).await;
+    assert_eq!(res.status(), StatusCode::UnprocessableEntity);
+
+    // Outra chave cria normalmente; erros também são repetidos
+    let mut res = client.send(create("retry-2", other)).await;
+    let created: Value = res.body_json().await.unwrap();
+    assert_eq!(created, json!({ "id": 2 }));
+    let invalid = json!({ "func_names": "x" });
+    let res = client.send(create("retry-3", invalid.clone())).await;
+    let status = res.status();
+    assert!(status.is_client_error());
+    let mut res = client.send(create("retry-3", invalid)).await;
+    assert_eq!(res.status(), status);
+    let body: Value = res.body_json().await.unwrap();
+    assert!(body["error"].is_string());
+}
+
+#[async_std::test]
+async fn refuses_new_idempotency_keys_while_all_are_in_flight() {
+    let mut config = Config::default();
+    config.idempotency.max_keys = 2;
+    let state = new_state(&config);
+    let client = TestClient::new(build_app(state.clone(), &config));
+    let create = |key: &str| {
+        let mut req = request(Method::Post, "/data");
+        req.insert_header("Idempotency-Key", key);
+        req.set_body(tide::Body::from_json(&entry("a", 1)).unwrap());
+        req
+    };
+
+    // Com a coleção travada, as duas primeiras ficam paradas no handler, em
+    // andamento; a terceira não tem chave pronta para descartar
+    let collection = state.default_collection();
+    let guard = collection.data.write().await;
+    let stalled = futures_lite::future::zip(client.send(create("k1")), client.send(create("k2")));
+    let third = async {
+        let wait = std::time::Duration::from_secs(5);
+        let res = async_std::future::timeout(wait, client.send(create("k3"))).await;
+        drop(guard);
+        res.expect("a terceira chave ficou esperando a coleção")
+    };
+    let ((first, second), third) = futures_lite::future::zip(stalled, third).await;
+    assert_eq!(first.status(), StatusCode::Ok);
+    assert_eq!(second.status(), StatusCode::Ok);
+    assert_eq!(third.status(), StatusCode::ServiceUnavailable);
+
+    // Com respostas guardadas, a mais antiga dá lugar à chave nova
+    let res = client.send(create("k3")).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let res = client.send(create("k2")).await;
+    assert_eq!(res.header("Idempotent-Replayed").unwrap().as_str(), "true");
+}
+
+// Próximo evento do stream SSE: (evento, id, dados)
+async fn next_sse_event(
+    lines: &mut (impl futures_lite::Stream<Item = std::io::Result<String>> + Unpin),
+) -> (String, Option<String>, String) {
+    let read = async {
+        let (mut event, mut id, mut data) = (String::new(), None, String::new());
+        while let Some(line) = lines.next().await {
+            let line = line.unwrap();
+            if line.is_empty() {
+                return (event, id, data);
+            }
+            let (field, value) = line.split_once(':').unwrap_or((&line, ""));
+            let value = value.trim_start().to_string();
+            match field {
+                "event" => event = value,
+                "id" => id = Some(value),
+                "data" => data = value,
+                _ => {}
+            }
+        }
+        panic!("o stream SSE terminou");
+    };
+    async_std::future::timeout(std::time::Duration::from_secs(5), read)
+        .await
+        .expect("nenhum evento SSE")
+}
+
+// Espera o feed da coleção default ficar com `count` assinantes
+async fn wait_for_subscribers(state: &AppState, count: usize) {
+    let changes = &state.default_collection().changes;
+    for _ in 0..200 {
+        if changes.subscriber_count() == count {
+            return;
+        }
+        async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!(
+        "o feed continua com {} assinantes",
+        changes.subscriber_count()
+    );
+}
+
+#[async_std::test]
+async fn streams_changes_over_sse() {
+    let config = Config::default();
+    let state = new_state(&config);
+    let client = TestClient::new(build_app(state.clone(), &config));
+    let entry = json!({ "func_names": ["add"], "bytecode": [1] });
+    let res = client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    let mut res = client.get("/data/_changes").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    assert_eq!(res.content_type().unwrap().essence(), "text/event-stream");
+    let mut lines = res.take_body().lines();
+    // A assinatura acontece na tarefa do stream, depois dos headers
+    wait_for_subscribers(&state, 1).await;
+    let res = client.put_json("/data/1", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let (event, id, data) = next_sse_event(&mut lines).await;
+    assert_eq!((event.as_str(), id.as_deref()), ("update", Some("2")));
+    let data: Value = serde_json::from_str(&data).unwrap();
+    assert_eq!(data["id"], 1);
+    assert_eq!(data["entry"]["func_names"], json!(["add"]));
+
+    // Quem reconecta com Last-Event-ID recebe o que perdeu, filtrado por ?id
+    let res = client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let mut req = request(Method::Get, "/data/_changes?id=2");
+    req.insert_header("Last-Event-ID", "1");
+    let mut res = client.send(req).await;
+    let mut resumed = res.take_body().lines();
+    let (event, id, _) = next_sse_event(&mut resumed).await;
+    assert_eq!((event.as_str(), id.as_deref()), ("create", Some("3")));
+
+    // Um id que o servidor não conhece pede para recarregar tudo
+    let mut res = client.get("/data/_changes?last_event_id=99").await;
+    let mut reset = res.take_body().lines();
+    let (event, _, _) = next_sse_event(&mut reset).await;
+    assert_eq!(event, "reset");
+    let res = client.get("/data/_changes?id=abc").await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+
+    // Clientes que desconectaram saem do feed
+    wait_for_subscribers(&state, 3).await;
+    drop((lines, resumed, reset));
+    let res = client.delete("/data/2").await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+    wait_for_subscribers(&state, 0).await;
+}
+
+#[async_std::test]
+async fn streams_changes_over_websocket() {
+    use async_tungstenite::tungstenite::Message;
+
+    let config = Config::default();
+    let state = new_state(&config);
+    let client = TestClient::new(build_app(state.clone(), &config));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    async_std::task::spawn(build_app(state.clone(), &config).listen(listener));
+
+    let entry = json!({ "func_names": ["add"], "bytecode": [1] });
+    let res = client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+
+    // O WebSocket do navegador não manda headers: a retomada vem pela query
+    let stream = async_std::net::TcpStream::connect(addr).await.unwrap();
+    let url = format!("ws://{addr}/data/_changes?last_event_id=0");
+    let (mut ws, _) = async_tungstenite::client_async(url, stream).await.unwrap();
+    let mut next_event = async || match ws.next().await {
+        Some(Ok(Message::Text(text))) => serde_json::from_str::<Value>(&text).unwrap(),
+        other => panic!("mensagem inesperada: {other:?}"),
+    };
+    let backlog = next_event().await;
+    assert_eq!(
+        (backlog["op"].as_str(), backlog["event_id"].as_u64()),
+        (Some("create"), Some(1))
+    );
+
+    let res = client.delete("/data/1").await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+    let deleted = next_event().await;
+    assert_eq!(deleted["op"], "delete");
+    assert!(deleted.get("entry").is_none());
+
+    // Fechar a conexão tira o assinante do feed, mesmo sem novos eventos
+    wait_for_subscribers(&state, 1).await;
+    ws.close(None).await.unwrap();
+    wait_for_subscribers(&state, 0).await;
+}
+
+// Receptor HTTP local: responde `statuses` em ordem (depois disso, 200) e
+// repassa cada requisição recebida (headers e body) pelo canal
+async fn webhook_receiver(
+    statuses: Vec<StatusCode>,
+) -> (
+    String,
+    async_std::channel::Receiver<(tide::http::Request, Vec<u8>)>,
+) {
+    let listener = async_std::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let (tx, rx) = async_std::channel::unbounded();
+    let statuses = std::sync::Arc::new(std::sync::Mutex::new(statuses.into_iter()));
+    async_std::task::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let tx = tx.clone();
+            let statuses = statuses.clone();
+            async_std::task::spawn(async_h1::accept(stream, move |mut req| {
+                let tx = tx.clone();
+                let status = statuses.lock().unwrap().next().unwrap_or(StatusCode::Ok);
+                async move {
+                    let body = req.body_bytes().await?;
+                    tx.send((req, body)).await.unwrap();
+                    Ok(tide::http::Response::new(status))
+                }
+            }));
+        }
+    });
+    (url, rx)
+}
+
+// Repete o GET até `done` aceitar o corpo (as entregas rodam em segundo plano)
+async fn wait_for(
+    client: &TestClient<AppState>,
+    path: &str,
+    done: impl Fn(&Value) -> bool,
+) -> Value {
+    for _ in 0..200 {
+        let body: Value = client.get(path).await.body_json().await.unwrap();
+        if done(&body) {
+            return body;
+        }
+        async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("{path} não chegou ao estado esperado");
+}
+
+#[async_std::test]
+async fn delivers_signed_webhooks_with_retries() {
+    let mut config = Config::default();
+    config.webhooks.max_attempts = 2;
+    config.webhooks.initial_backoff_ms = 10;
+    config.webhooks.max_backoff_ms = 10;
+    let client = TestClient::new(build_app(new_state(&config), &config));
+    let (url, received) = webhook_receiver(vec![StatusCode::InternalServerError]).await;
+
+    let mut res = client
+        .post_json(
+            "/_webhooks",
+            &json!({ "url": url, "events": ["create"], "secret": "s3cret" }),
+        )
+        .await;
+    assert_eq!(res.status(), StatusCode::Created);
+    let webhook: Value = res.body_json().await.unwrap();
+    assert_eq!(webhook["secret"], "s3cret");
+    let res = client
+        .post_json("/_webhooks", &json!({ "url": "ftp://example.com" }))
+        .await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+
+    let entry = json!({ "func_names": ["add"], "bytecode": [1] });
+    client.post_json("/data", &entry).await;
+    // Fora do filtro de eventos: não é enviado
+    client.put_json("/data/1", &entry).await;
+
+    // A primeira tentativa recebe 500; a segunda, o mesmo evento, é confirmada
+    let (first, _) = received.recv().await.unwrap();
+    let (second, body) = received.recv().await.unwrap();
+    assert_eq!(first["X-Webhook-Attempt"].as_str(), "1");
+    assert_eq!(second["X-Webhook-Attempt"].as_str(), "2");
+    assert_eq!(
+        first["X-Webhook-Id"].as_str(),
+        second["X-Webhook-Id"].as_str()
+    );
+    assert_eq!(second["X-Webhook-Event"].as_str(), "create");
+    let timestamp: u64 = second["X-Webhook-Timestamp"].as_str().parse().unwrap();
+    let signature = crud_e::webhooks::sign("s3cret", timestamp, &body);
+    assert_eq!(
+        second["X-Webhook-Signature"].as_str(),
+        format!("sha256={signature}")
+    );
+    let payload: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload["collection"], "default");
+    assert_eq!(payload["op"], "create");
+    assert_eq!(payload["entry"]["func_names"], json!(["add"]));
+
+    let log = wait_for(&client, "/_webhooks/1/deliveries", |log| {
+        log.as_array().unwrap().len() == 2
+    })
+    .await;
+    assert_eq!(log[0]["outcome"], "delivered");
+    assert_eq!(log[1]["outcome"], "retrying");
+    assert_eq!(log[1]["status"], 500);
+    assert!(received.try_recv().is_err());
+
+    // Receptor fora do ar: as tentativas se esgotam e o evento vai para a lista de falhas
+    let closed = async_std::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap();
+    let closed_url = format!("http://{}/hook", closed.local_addr().unwrap());
+    drop(closed);
+    client
+        .post_json(
+            "/_webhooks",
+            &json!({ "url": closed_url, "events": ["delete"] }),
+        )
+        .await;
+    client.delete("/data/1").await;
+    let dead = wait_for(&client, "/_webhooks/_dead_letters", |dead| {
+        dead.as_array().unwrap().len() == 1
+    })
+    .await;
+    assert_eq!(dead[0]["subscription_id"], 2);
+    assert_eq!(dead[0]["attempts"], 2);
+    assert_eq!(dead[0]["event"]["op"], "delete");
+    let retry = format!("/_webhooks/_dead_letters/{}/retry", dead[0]["delivery_id"]);
+    let res = client.send(request(Method::Post, &retry)).await;
+    assert_eq!(res.status(), StatusCode::Accepted);
+
+    let res = client.delete("/_webhooks/2").await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+    let webhooks: Value = client.get("/_webhooks").await.body_json().await.unwrap();
+    assert_eq!(webhooks.as_array().unwrap().len(), 1);
+    assert!(webhooks[0].get("secret").is_none());
+}
+
+#[async_std::test]
+async fn webhooks_require_the_collection_api_key() {
+    let client = client();
+    client
+        .post_json(
+            "/collections",
+            &json!({ "name": "private", "api_keys": ["tenant-key"] }),
+        )
+        .await;
+    let (named_url, named) = webhook_receiver(vec![]).await;
+    let (all_url, all) = webhook_receiver(vec![]).await;
+
+    // Nomear a coleção exige a chave dela
+    let hook = json!({ "url": named_url, "collections": ["private"] });
+    let res = client.post_json("/_webhooks", &hook).await;
+    assert_eq!(res.status(), StatusCode::Unauthorized);
+    let mut req = request(Method::Post, "/_webhooks");
+    req.insert_header("X-Api-Key", "outra-chave");
+    req.set_body(tide::Body::from_json(&hook).unwrap());
+    assert_eq!(client.send(req).await.status(), StatusCode::Unauthorized);
+    let mut req = request(Method::Post, "/_webhooks");
+    req.insert_header("X-Api-Key", "tenant-key");
+    req.set_body(tide::Body::from_json(&hook).unwrap());
+    assert_eq!(client.send(req).await.status(), StatusCode::Created);
+
+    // Sem filtro de coleções, a assinatura não recebe as protegidas
+    let res = client
+        .post_json("/_webhooks", &json!({ "url": all_url }))
+        .await;
+    assert_eq!(res.status(), StatusCode::Created);
+
+    let entry = json!({ "func_names": ["segredo"], "bytecode": [1] });
+    let mut req = request(Method::Post, "/collections/private/data");
+    req.insert_header("X-Api-Key", "tenant-key");
+    req.set_body(tide::Body::from_json(&entry).unwrap());
+    assert_eq!(client.send(req).await.status(), StatusCode::Ok);
+    client.post_json("/data", &entry).await;
+
+    let (_, body) = named.recv().await.unwrap();
+    let payload: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload["collection"], "private");
+    let (_, body) = all.recv().await.unwrap();
+    let payload: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload["collection"], "default");
+    async_std::task::sleep(std::time::Duration::from_millis(100)).await;
+    assert!(named.try_recv().is_err());
+    assert!(all.try_recv().is_err());
+}
+
+// Nó de um cluster de teste: RPCs do Raft em uma porta local e a API em processo
+struct ClusterNode {
+    node: std::sync::Arc<crud_e::raft::Node>,
+    client: TestClient<AppState>,
+}
+
+async fn start_cluster(size: u64, dir: &std::path::Path) -> Vec<ClusterNode> {
+    start_cluster_with(size, dir, |_| {}).await
+}
+
+async fn start_cluster_with(
+    size: u64,
+    dir: &std::path::Path,
+    configure: impl Fn(&mut Config),
+) -> Vec<ClusterNode> {
+    use crud_e::config::{FollowerWrites, PeerConfig};
+
+    let mut peers = Vec::new();
+    for id in 1..=size {
+        // Porta livre para as RPCs (o nó abre de novo ao iniciar)
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        peers.push(PeerConfig {
+            id,
+            peer_url: format!("http://{}", listener.local_addr().unwrap()),
+            api_url: format!("http://node{id}.test"),
+        });
+    }
+    let mut nodes = Vec::new();
+    for id in 1..=size {
+        let mut config = Config::default();
+        config.cluster.node_id = Some(id);
+        config.cluster.data_dir = dir.to_path_buf();
+        config.cluster.heartbeat_interval_ms = 20;
+        config.cluster.election_timeout_min_ms = 150;
+        config.cluster.election_timeout_max_ms = 300;
+        config.cluster.snapshot_threshold = 4;
+        config.cluster.follower_writes = FollowerWrites::Redirect;
+        config.cluster.peers = peers.clone();
+        configure(&mut config);
+        config.validate().unwrap();
+        let state = new_state(&config);
+        let node = crud_e::raft::Node::start(&config.cluster, state.clone())
+            .await
+            .unwrap();
+        nodes.push(ClusterNode {
+            node,
+            client: TestClient::new(build_app(state, &config)),
+        });
+    }
+    nodes
+}
+
+// Espera um dos nós ainda no ar (fora `skip`) virar líder e devolve a posição dele
+async fn wait_for_leader(nodes: &[ClusterNode], skip: Option<usize>) -> usize {
+    for _ in 0..500 {
+        let leader = nodes.iter().enumerate().position(|(i, n)| {
+            Some(i) != skip && matches!(n.node.status().role, crud_e::raft::Role::Leader)
+        });
+        if let Some(leader) = leader {
+            return leader;
+        }
+        async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("nenhum líder eleito");
+}
+
+// Leitura no próprio nó, sem passar pelo líder
+fn stale_get(path: &str) -> tide::http::Request {
+    let mut req = request(Method::Get, path);
+    req.insert_header("X-Read-Consistency", "stale");
+    req
+}
+
+#[async_std::test]
+async fn replicates_writes_across_a_raft_cluster() {
+    let dir = std::env::temp_dir().join(format!("crud-e-raft-{}", uuid::Uuid::new_v4()));
+    let nodes = start_cluster(3, &dir).await;
+    let leader = wait_for_leader(&nodes, None).await;
+    let follower = (leader + 1) % 3;
+
+    let entry = json!({ "func_names": ["add"], "bytecode": [1] });
+    let mut res = nodes[leader].client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body["id"], 1);
+
+    // O seguidor manda escritas e leituras linearizáveis para o líder
+    let res = nodes[follower].client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::TemporaryRedirect);
+    let leader_url = format!("http://node{}.test/data", leader + 1);
+    assert_eq!(res["Location"].as_str(), leader_url);
+    let res = nodes[follower].client.get("/data/1").await;
+    assert_eq!(res.status(), StatusCode::TemporaryRedirect);
+    let res = nodes[leader].client.get("/data/1").await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let mut req = request(Method::Get, "/data/1");
+    req.insert_header("X-Read-Consistency", "eventual");
+    let res = nodes[leader].client.send(req).await;
+    assert_eq!(res.status(), StatusCode::BadRequest);
+
+    // Leitura stale: o seguidor responde com o que já aplicou
+    let mut replicated = false;
+    for _ in 0..200 {
+        if nodes[follower]
+            .client
+            .send(stale_get("/data/1"))
+            .await
+            .status()
+            == StatusCode::Ok
+        {
+            replicated = true;
+            break;
+        }
+        async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert!(replicated);
+    let mut res = nodes[follower].client.get("/_cluster").await;
+    let status: Value = res.body_json().await.unwrap();
+    assert_eq!(status["role"], "follower");
+    assert_eq!(status["leader_id"], leader as u64 + 1);
+
+    // Mais escritas: o log passa do limite e vira snapshot
+    for _ in 0..4 {
+        let res = nodes[leader].client.post_json("/data", &entry).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+    }
+    assert!(nodes[leader].node.status().snapshot_index > 0);
+
+    // Sem o líder, os outros dois elegem um novo e seguem aceitando escritas
+    nodes[leader].node.shutdown();
+    let new_leader = wait_for_leader(&nodes, Some(leader)).await;
+    let mut res = nodes[new_leader].client.post_json("/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body["id"], 6);
+    let mut res = nodes[new_leader].client.get("/data").await;
+    let all: Value = res.body_json().await.unwrap();
+    assert_eq!(all.as_object().unwrap().len(), 6);
+
+    for node in &nodes {
+        node.node.shutdown();
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[async_std::test]
+async fn cluster_nodes_expire_the_trash_at_the_same_entry() {
+    let dir = std::env::temp_dir().join(format!("crud-e-raft-{}", uuid::Uuid::new_v4()));
+    let nodes = start_cluster_with(3, &dir, |config| config.trash.retention_secs = 0).await;
+    let leader = wait_for_leader(&nodes, None).await;
+
+    let entry = json!({ "func_names": ["add"], "bytecode": [1] });
+    for _ in 0..2 {
+        let res = nodes[leader].client.post_json("/data", &entry).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+    }
+    let res = nodes[leader].client.delete("/data/2").await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+    async_std::task::sleep(std::time::Duration::from_millis(20)).await;
+
+    // A próxima escrita apaga de vez o id 2 em todos os nós (a sequência de ids
+    // não volta atrás)
+    let mut res = nodes[leader].client.post_json("/data", &entry).await;
+    let body: Value = res.body_json().await.unwrap();
+    assert_eq!(body["id"], 3);
+    for node in &nodes {
+        let mut applied = false;
+        for _ in 0..200 {
+            if node.client.send(stale_get("/data/3")).await.status() == StatusCode::Ok {
+                applied = true;
+                break;
+            }
+            async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(applied);
+        let mut res = node.client.send(stale_get("/data/_trash")).await;
+        let trash: Value = res.body_json().await.unwrap();
+        assert_eq!(trash, json!({}));
+        let res = node.client.send(stale_get("/data/2/history")).await;
+        assert_eq!(res.status(), StatusCode::NotFound);
+    }
+
+    for node in &nodes {
+        node.node.shutdown();
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// Abre o estado como o binário faz: snapshot do backend "file" e depois o WAL
+async fn open_file_store(config: &Config) -> (AppState, std::sync::Arc<wal::Wal>) {
+    let snapshot = storage::load_snapshot(&config.storage).unwrap();
+    let checkpoint_seq = snapshot.wal_seq;
+    let state = state::from_snapshot(snapshot, config);
+    let wal = wal::open(&config.storage, checkpoint_seq, &state)
+        .await
+        .unwrap()
+        .unwrap();
+    state.set_wal(wal.clone());
+    (state, wal)
+}
+
+#[async_std::test]
+async fn replays_the_wal_after_a_crash() {
+    let dir = std::env::temp_dir().join(format!("crud-e-wal-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::default();
+    config.storage.backend = StorageBackend::File;
+    config.storage.path = Some(dir.join("data.json"));
+    let wal_path = config.storage.wal_path().unwrap();
+
+    // Escritas sem snapshot: só o WAL sabe delas
+    let (state, _wal) = open_file_store(&config).await;
+    let client = TestClient::new(build_app(state, &config));
+    for n in 1..=3 {
+        let res = client
+            .post_json(
+                "/data",
+                &json!({ "func_names": [format!("f{n}")], "bytecode": [n] }),
+            )
+            .await;
+        assert_eq!(res.status(), StatusCode::Ok);
+    }
+    let entry = json!({ "func_names": ["sub"], "bytecode": [9] });
+    let res = client.put_json("/data/2", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let res = client.delete("/data/3").await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+
+    // Os outros caminhos de escrita também vão para o WAL
+    let res = client.delete("/data/1").await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+    let res = client.post_json("/data/1/restore", &json!({})).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let res = client.put_json("/data/1", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let ops = json!([
+        { "op": "create", "entry": entry },
+        { "op": "create", "entry": entry },
+    ]);
+    let res = client.post_json("/data/_bulk", &ops).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let mut req = request(Method::Post, "/data/_import");
+    req.set_body(r#"{"id":10,"func_names":["imp"],"bytecode":[10]}"#);
+    let res = client.send(req).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let limited = json!({ "name": "limited", "max_entries": 1 });
+    let res = client.post_json("/collections", &limited).await;
+    assert_eq!(res.status(), StatusCode::Created);
+    let res = client.post_json("/collections/limited/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    let res = client
+        .post_json("/collections", &json!({ "name": "gone" }))
+        .await;
+    assert_eq!(res.status(), StatusCode::Created);
+    let res = client.delete("/collections/gone").await;
+    assert_eq!(res.status(), StatusCode::NoContent);
+    let hook = json!({ "url": "http://127.0.0.1:9/hook" });
+    let res = client.post_json("/_webhooks", &hook).await;
+    assert_eq!(res.status(), StatusCode::Created);
+    let mut res = client.get("/data").await;
+    let before: Value = res.body_json().await.unwrap();
+    let mut res = client.get("/collections").await;
+    let collections: Value = res.body_json().await.unwrap();
+    let mut res = client.get("/_webhooks").await;
+    let webhooks: Value = res.body_json().await.unwrap();
+
+    // Queda no meio de uma escrita: o último registro fica pela metade
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&wal_path)
+        .unwrap();
+    std::io::Write::write_all(&mut file, &[40, 0, 0, 0, 1, 2, 3, 4, b'{']).unwrap();
+    drop(file);
+    let torn_len = std::fs::metadata(&wal_path).unwrap().len();
+
+    let (state, wal) = open_file_store(&config).await;
+    assert!(std::fs::metadata(&wal_path).unwrap().len() < torn_len);
+    assert_eq!(wal.last_seq(), 15);
+    let client = TestClient::new(build_app(state.clone(), &config));
+    let mut res = client.get("/data").await;
+    let after: Value = res.body_json().await.unwrap();
+    assert_eq!(after, before);
+    let mut res = client.get("/data/2/history").await;
+    let history: Value = res.body_json().await.unwrap();
+    assert_eq!(history["revisions"].as_array().unwrap().len(), 2);
+    // O registro restaurado não fica também na lixeira
+    let mut res = client.get("/data/_trash").await;
+    let trash: Value = res.body_json().await.unwrap();
+    assert_eq!(trash.as_object().unwrap().len(), 1);
+    assert_eq!(trash["3"]["entry"]["func_names"], json!(["f3"]));
+    // A coleção volta com os limites com que foi criada
+    let mut res = client.get("/collections").await;
+    let reopened: Value = res.body_json().await.unwrap();
+    assert_eq!(reopened, collections);
+    let res = client.post_json("/collections/limited/data", &entry).await;
+    assert_eq!(res.status(), StatusCode::InsufficientStorage);
+    let mut res = client.get("/_webhooks").await;
+    let reopened: Value = res.body_json().await.unwrap();
+    assert_eq!(reopened, webhooks);
+
+    // Depois do checkpoint o log fica vazio e os ids continuam de onde pararam
+    let saved = wal
+        .checkpoint(&config.storage, &state)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(saved.wal_seq, 15);
+    assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+    let (state, wal) = open_file_store(&config).await;
+    assert_eq!(wal.last_seq(), 15);
+    let client = TestClient::new(build_app(state, &config));
+    let mut res = client.get("/data").await;
+    let reopened: Value = res.body_json().await.unwrap();
+    assert_eq!(reopened, before);
+    let mut res = client.post_json("/data", &entry).await;
+    let created: Value = res.body_json().await.unwrap();
+    assert_eq!(created, json!({ "id": 11 }));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[async_std::test]
+async fn keeps_the_id_sequence_across_purges_and_restarts() {
+    let dir = std::env::temp_dir().join(format!("crud-e-wal-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::default();
+    config.storage.backend = StorageBackend::File;
+    config.storage.path = Some(dir.join("data.json"));
+    config.trash.retention_secs = 0;
+    async fn create(client: &TestClient<AppState>) -> Value {
+        let mut res = client.post_json("/data", &entry("a", 1)).await;
+        let created: Value = res.body_json().await.unwrap();
+        created["id"].clone()
+    }
+
+    // A importação avança a sequência e o id apagado de vez não volta
+    let (state, _wal) = open_file_store(&config).await;
+    let client = TestClient::new(build_app(state.clone(), &config));
+    assert_eq!(create(&client).await, 1);
+    let res = client.send(import("/data/_import", &ndjson(10..=10))).await;
+    assert_eq!(res.status(), StatusCode::Ok);
+    client.delete("/data/10").await;
+    trash::purge_expired(&state, state.trash_retention()).await;
+    assert_eq!(create(&client).await, 11);
+
+    // Só com o WAL
+    let (state, wal) = open_file_store(&config).await;
+    let client = TestClient::new(build_app(state.clone(), &config));
+    assert_eq!(create(&client).await, 12);
+
+    // Só com o snapshot, sem nenhum registro com o maior id usado
+    client.delete("/data/12").await;
+    trash::purge_expired(&state, state.trash_retention()).await;
+    wal.checkpoint(&config.storage, &state)
+        .await
+        .unwrap()
+        .unwrap();
+    let (state, _wal) = open_file_store(&config).await;
+    let client = TestClient::new(build_app(state, &config));
+    assert_eq!(create(&client).await, 13);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// Espera o servidor abrir a porta
+async fn wait_for_port(port: u16) {
+    for _ in 0..500 {
+        if async_std::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_ok()
+        {
+            return;
+        }
+        async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("o servidor não abriu a porta {port}");
+}
+
+// Requisição HTTP crua com `Connection: close`; devolve a resposta inteira
+async fn raw_request(port: u16, method: &str, path: &str, body: &str) -> String {
+    use futures_lite::AsyncWriteExt;
+
+    let mut stream = async_std::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .unwrap();
+    let head = format!(
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+         Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+// Sobe o binário com o arquivo de configuração e espera a porta abrir
+async fn spawn_binary(config_path: &std::path::Path, port: u16) -> std::process::Child {
+    let child = std::process::Command::new(env!("CARGO_BIN_EXE_crud-e"))
+        .arg("--config")
+        .arg(config_path)
+        .arg("--bind")
+        .arg(format!("127.0.0.1:{port}"))
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    wait_for_port(port).await;
+    child
+}
+
+// Manda SIGTERM e espera o processo terminar sozinho
+async fn terminate(mut child: std::process::Child) -> std::process::ExitStatus {
+    let killed = std::process::Command::new("kill")
+        .args(["-TERM", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    for _ in 0..1000 {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status;
+        }
+        async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    child.kill().unwrap();
+    panic!("o servidor não terminou depois do SIGTERM");
+}
+
+#[cfg(unix)]
+#[async_std::test]
+async fn saves_a_snapshot_on_shutdown_and_restores_it() {
+    let dir = std::env::temp_dir().join(format!("crud-e-snapshot-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let data = dir.join("data.json");
+    let config_path = dir.join("config.toml");
+    // Sem WAL, só o snapshot da saída guarda as escritas
+    let toml = format!(
+        "[storage]\nbackend = \"file\"\npath = {:?}\n\n[storage.wal]\nenabled = false\n",
+        data.to_str().unwrap()
+    );
+    std::fs::write(&config_path, toml).unwrap();
+
+    let port = free_port();
+    let child = spawn_binary(&config_path, port).await;
+    let entry = r#"{ "func_names": ["add"], "bytecode": [1, 2] }"#;
+    let response = raw_request(port, "POST", "/data", entry).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    raw_request(port, "DELETE", "/data/1", "").await;
+    raw_request(port, "POST", "/data", entry).await;
+    assert!(!data.exists());
+
+    // O SIGTERM salva o snapshot antes de sair
+    assert!(terminate(child).await.success());
+    let snapshot = storage::decode_snapshot(&std::fs::read(&data).unwrap()).unwrap();
+    assert_eq!(snapshot.entries[&2].func_names, ["add"]);
+    assert!(snapshot.trash.contains_key(&1));
+
+    // Na subida, o estado volta do snapshot (registros, lixeira e ids)
+    let port = free_port();
+    let child = spawn_binary(&config_path, port).await;
+    let response = raw_request(port, "GET", "/data/2", "").await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.ends_with(r#"{"func_names":["add"],"bytecode":[1,2]}"#));
+    let response = raw_request(port, "POST", "/data/1/restore", "{}").await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    let response = raw_request(port, "POST", "/data", entry).await;
+    assert!(response.ends_with(r#"{"id":3}"#), "{response}");
+    assert!(terminate(child).await.success());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// CA de teste: gera certificados assinados por ela (PEM do certificado e da chave)
+struct TestCa {
+    cert: rcgen::Certificate,
+    key: rcgen::KeyPair,
+}
+
+impl TestCa {
+    fn new() -> Self {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let cert = params.self_signed(&key).unwrap();
+        TestCa { cert, key }
+    }
+
+    fn issue(&self, name: &str) -> (String, String) {
+        let key = rcgen::KeyPair::generate().unwrap();
+        let params = rcgen::CertificateParams::new(vec![name.to_string()]).unwrap();
+        let cert = params.signed_by(&key, &self.cert, &self.key).unwrap();
+        (cert.pem(), key.serialize_pem())
+    }
+}
+
+fn free_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+// Sobe o servidor de verdade (com TLS, se configurado) e espera a porta abrir
+async fn start_server(config: Config) -> u16 {
+    let port = free_port();
+    let mut config = config;
+    config.server.bind = format!("127.0.0.1:{port}");
+    let app = build_app(new_state(&config), &config);
+    async_std::task::spawn(async move { crud_e::server::serve(app, &config.server).await });
+    wait_for_port(port).await;
+    port
+}
+
+// GET com `Connection: close` pela conexão já aberta; devolve a resposta crua
+async fn raw_get<S>(mut stream: S, host: &str, path: &str) -> std::io::Result<String>
+where
+    S: futures_lite::AsyncRead + futures_lite::AsyncWrite + Unpin,
+{
+    use futures_lite::AsyncWriteExt;
+
+    let head = format!("GET {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.flush().await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    Ok(response)
+}
+
+// GET por HTTPS confiando só em `ca`, com certificado de cliente opcional
+async fn https_get(
+    port: u16,
+    ca: &TestCa,
+    client_cert: Option<(String, String)>,
+    path: &str,
+) -> std::io::Result<String> {
+    use futures_rustls::rustls::pki_types::pem::PemObject;
+    use futures_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+    use futures_rustls::rustls::{ClientConfig, RootCertStore};
+
+    let mut roots = RootCertStore::empty();
+    roots.add(ca.cert.der().clone()).unwrap();
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+    let config = match client_cert {
+        Some((cert, key)) => builder
+            .with_client_auth_cert(
+                vec![CertificateDer::from_pem_slice(cert.as_bytes()).unwrap()],
+                PrivateKeyDer::from_pem_slice(key.as_bytes()).unwrap(),
+            )
+            .unwrap(),
+        None => builder.with_no_client_auth(),
+    };
+    let connector = futures_rustls::TlsConnector::from(std::sync::Arc::new(config));
+    let stream = async_std::net::TcpStream::connect(("127.0.0.1", port)).await?;
+    let name = ServerName::try_from("localhost").unwrap();
+    let stream = connector.connect(name, stream).await?;
+    raw_get(stream, "localhost", path).await
+}
+
+fn is_ok_response(response: &std::io::Result<String>) -> bool {
+    matches!(response, Ok(text) if text.starts_with("HTTP/1.1 200"))
+}
+
+#[async_std::test]
+async fn serves_https_and_redirects_plain_http() {
+    let dir = std::env::temp_dir().join(format!("crud-e-tls-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+    let ca = TestCa::new();
+    let (cert, key) = ca.issue("localhost");
+    std::fs::write(&cert_path, cert).unwrap();
+    std::fs::write(&key_path, key).unwrap();
+
+    let mut config = Config::default();
+    let redirect_port = free_port();
+    config.server.tls.cert_path = Some(cert_path.clone());
+    config.server.tls.key_path = Some(key_path.clone());
+    config.server.tls.reload_interval_secs = 1;
+    config.server.tls.redirect_bind = Some(format!("127.0.0.1:{redirect_port}"));
+    let port = start_server(config).await;
+
+    let response = https_get(port, &ca, None, "/data").await;
+    assert!(is_ok_response(&response), "{response:?}");
+    let other_ca = TestCa::new();
+    let response = https_get(port, &other_ca, None, "/data").await;
+    assert!(response.is_err(), "{response:?}");
+
+    // O HTTP puro recebe 308 para a mesma URL no HTTPS
+    let stream = async_std::net::TcpStream::connect(("127.0.0.1", redirect_port))
+        .await
+        .unwrap();
+    let host = format!("localhost:{redirect_port}");
+    let response = raw_get(stream, &host, "/data/1?rev=2").await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 308"), "{response}");
+    let location = format!("location: https://localhost:{port}/data/1?rev=2\r\n");
+    assert!(response.to_lowercase().contains(&location), "{response}");
+
+    // Certificados trocados no disco valem para as novas conexões
+    let (cert, key) = other_ca.issue("localhost");
+    std::fs::write(&cert_path, cert).unwrap();
+    std::fs::write(&key_path, key).unwrap();
+    let mut reloaded = false;
+    for _ in 0..50 {
+        if is_ok_response(&https_get(port, &other_ca, None, "/data").await) {
+            reloaded = true;
+            break;
+        }
+        async_std::task::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(reloaded, "os certificados não foram recarregados");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[async_std::test]
+async fn requires_client_certificates_for_mutual_tls() {
+    let dir = std::env::temp_dir().join(format!("crud-e-tls-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let server_ca = TestCa::new();
+    let client_ca = TestCa::new();
+    let (cert, key) = server_ca.issue("localhost");
+    std::fs::write(dir.join("cert.pem"), cert).unwrap();
+    std::fs::write(dir.join("key.pem"), key).unwrap();
+    std::fs::write(dir.join("clients.pem"), client_ca.cert.pem()).unwrap();
+
+    let mut config = Config::default();
+    config.server.tls.cert_path = Some(dir.join("cert.pem"));
+    config.server.tls.key_path = Some(dir.join("key.pem"));
+    config.server.tls.client_ca_path = Some(dir.join("clients.pem"));
+    let port = start_server(config).await;
+
+    // Sem certificado, ou com um de outra CA, a conexão é recusada
+    let response = https_get(port, &server_ca, None, "/data").await;
+    assert!(!is_ok_response(&response), "{response:?}");
+    let stranger = server_ca.issue("cliente");
+    let response = https_get(port, &server_ca, Some(stranger), "/data").await;
+    assert!(!is_ok_response(&response), "{response:?}");
+
+    let client = client_ca.issue("cliente");
+    let response = https_get(port, &server_ca, Some(client), "/data").await;
+    assert!(is_ok_response(&response), "{response:?}");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}